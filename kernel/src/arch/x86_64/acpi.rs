@@ -0,0 +1,91 @@
+//! ACPI tables of the machine
+//!
+//! The tables are found once, while the architecture is initialized, and the ones the kernel uses
+//! are parsed and kept here for the rest of the subsystems (clock sources, SMP, power management,
+//! PCI Express...).
+//!
+//! The RSDP can be handed by the bootloader. The one we use (bootloader 0.9) does not report it in
+//! `BootInfo`, so unless an address is given it is searched in the BIOS memory areas.
+use x86_64_custom::acpi::{AcpiError, AcpiTables, Fadt, HpetTable, Madt, Mcfg, Rsdp};
+use x86_64_custom::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+
+use crate::synchronization::once::Once;
+
+/// The ACPI tables used by the kernel. A table the firmware does not provide (or whose checksum is
+/// invalid) is `None`.
+#[derive(Debug)]
+pub struct Acpi {
+    tables: AcpiTables,
+    madt: Option<&'static Madt>,
+    fadt: Option<&'static Fadt>,
+    hpet: Option<&'static HpetTable>,
+    mcfg: Option<&'static Mcfg>,
+}
+
+impl Acpi {
+    /// Returns the entry point to all the ACPI tables.
+    pub fn tables(&self) -> &AcpiTables {
+        &self.tables
+    }
+
+    /// Returns the MADT (interrupt controllers and processors).
+    pub fn madt(&self) -> Option<&'static Madt> {
+        self.madt
+    }
+
+    /// Returns the FADT (power management registers).
+    pub fn fadt(&self) -> Option<&'static Fadt> {
+        self.fadt
+    }
+
+    /// Returns the HPET description table.
+    pub fn hpet(&self) -> Option<&'static HpetTable> {
+        self.hpet
+    }
+
+    /// Returns the MCFG (PCI Express configuration space).
+    pub fn mcfg(&self) -> Option<&'static Mcfg> {
+        self.mcfg
+    }
+}
+
+static ACPI: Once<Acpi> = Once::new();
+
+/// Finds the ACPI tables and parses the ones the kernel uses. Calling it again returns the tables
+/// found the first time.
+///
+/// # Arguments
+/// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+/// * `rsdp_address` - Physical address of the RSDP, if the bootloader reports it.
+pub fn initialize(
+    physical_memory_offset: VirtualMemoryAddress,
+    rsdp_address: Option<PhysicalMemoryAddress>,
+) -> Result<&'static Acpi, AcpiError> {
+    if let Some(acpi) = ACPI.get() {
+        return Ok(acpi);
+    }
+
+    // The bootloader maps the whole physical address space, including the ACPI tables
+    let tables = unsafe {
+        match rsdp_address {
+            Some(address) => {
+                let rsdp = Rsdp::from_address(physical_memory_offset + address)?;
+                AcpiTables::from_rsdp(physical_memory_offset, rsdp)?
+            }
+            None => AcpiTables::search(physical_memory_offset)?,
+        }
+    };
+
+    Ok(ACPI.call_once(|| Acpi {
+        tables,
+        madt: tables.find().ok(),
+        fadt: tables.find().ok(),
+        hpet: tables.find().ok(),
+        mcfg: tables.find().ok(),
+    }))
+}
+
+/// Returns the ACPI tables, or `None` if they were not found (or not searched yet).
+pub fn get() -> Option<&'static Acpi> {
+    ACPI.get()
+}
@@ -0,0 +1,137 @@
+//! Processor identification
+//!
+//! The processor is identified once at boot, on the bootstrap processor, through CPUID. All the
+//! processors of the machine are assumed to be the same model.
+use core::fmt;
+
+use x86_64_custom::cpuid::{self, CpuidString, Feature, Features, Topology};
+
+use crate::synchronization::once::Once;
+
+/// Identification and features of the processor.
+#[derive(Debug)]
+pub struct ProcessorInfo {
+    vendor: CpuidString<12>,
+    brand: Option<CpuidString<48>>,
+    topology: Topology,
+    features: Features,
+}
+
+impl ProcessorInfo {
+    /// Returns the vendor string.
+    pub fn vendor(&self) -> &str {
+        self.vendor.as_str()
+    }
+
+    /// Returns the brand string (the model name), if the processor reports it.
+    pub fn brand(&self) -> Option<&str> {
+        self.brand.as_ref().map(CpuidString::as_str)
+    }
+
+    /// Returns how the logical processors are organized in a package.
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    /// Returns the feature bits read at boot. The ones that depend on the kernel configuration
+    /// (like [Feature::OsXsave]) must be read again with [cpuid::has].
+    pub fn features(&self) -> &Features {
+        &self.features
+    }
+
+    /// Returns true if the processor supports the given feature.
+    ///
+    /// # Arguments
+    /// * `feature` - Feature to check.
+    pub fn has(&self, feature: Feature) -> bool {
+        self.features.has(feature)
+    }
+}
+
+impl fmt::Display for ProcessorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.brand() {
+            Some(brand) => write!(f, "{} ({})", brand, self.vendor())?,
+            None => write!(f, "{}", self.vendor())?,
+        }
+        write!(
+            f,
+            ", {} cores, {} threads per core, features:",
+            self.topology.cores_per_package, self.topology.threads_per_core
+        )?;
+        for feature in self.features.iter() {
+            write!(f, " {}", feature.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+static PROCESSOR: Once<ProcessorInfo> = Once::new();
+
+/// Identifies the processor. Calling it again returns the information read the first time.
+pub fn initialize() -> &'static ProcessorInfo {
+    PROCESSOR.call_once(|| ProcessorInfo {
+        vendor: cpuid::vendor(),
+        brand: cpuid::brand(),
+        topology: cpuid::topology(),
+        features: Features::read(),
+    })
+}
+
+/// Returns the processor information, or `None` if the processor was not identified yet.
+pub fn info() -> Option<&'static ProcessorInfo> {
+    PROCESSOR.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn cpuid_strings_trim_the_padding() {
+        // Brand strings are padded with spaces at the start and with zeros at the end
+        let string = CpuidString::<16>::from_registers([
+            u32::from_le_bytes(*b"  Li"),
+            u32::from_le_bytes(*b"l CP"),
+            u32::from_le_bytes(*b"U\0\0\0"),
+        ]);
+        assert_eq!("Lil CPU", string.as_str());
+
+        // The bytes that do not fit are ignored
+        let string = CpuidString::<4>::from_registers([
+            u32::from_le_bytes(*b"abcd"),
+            u32::from_le_bytes(*b"efgh"),
+        ]);
+        assert_eq!("abcd", string.as_str());
+
+        assert_eq!("", CpuidString::<8>::from_registers([0]).as_str());
+    }
+
+    #[test_case]
+    fn features_match_the_single_queries() {
+        let features = Features::read();
+        for &feature in Feature::ALL {
+            assert_eq!(
+                cpuid::has(feature),
+                features.has(feature),
+                "{}",
+                feature.name()
+            );
+        }
+    }
+
+    #[test_case]
+    fn vendor_is_not_empty() {
+        assert!(!cpuid::vendor().as_str().is_empty());
+        assert_eq!(cpuid::vendor().as_str(), initialize().vendor());
+    }
+
+    #[test_case]
+    fn topology_has_processors() {
+        let topology = cpuid::topology();
+        assert!(topology.threads_per_core > 0);
+        assert!(topology.cores_per_package > 0);
+        assert!(topology.logical_processors_per_package() > 0);
+    }
+}
@@ -0,0 +1,112 @@
+//! x87 FPU, SSE and AVX
+//!
+//! The kernel is built without SSE (soft float), so only user code uses the x87, SSE and AVX
+//! registers. Their state is switched lazily:
+//!
+//! - When a thread is switched in, CR0.TS is set, unless the thread is the last one that used the
+//!   registers (the owner), whose state is still loaded.
+//! - The first x87, SSE or AVX instruction of the thread raises #NM (device not available). The
+//!   handler clears CR0.TS, saves the registers in the save area of the owner, loads the ones of
+//!   the current thread and makes it the owner.
+//!
+//! Threads that never use the registers never pay for saving and restoring them.
+//!
+//! The registers are saved with XSAVE if the processor supports it (including the AVX state, if it
+//! has AVX), with FXSAVE otherwise. The x87 and SIMD floating point exceptions (#MF and #XM) are
+//! reported through exceptions, not through the legacy IRQ 13.
+//!
+//! For more info:
+//! https://wiki.osdev.org/FPU
+//! https://wiki.osdev.org/SSE
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use x86_64_custom::cpuid::Feature;
+use x86_64_custom::fpu::{self, ExtendedState};
+use x86_64_custom::registers::control::{Cr0, Cr4, Xcr0};
+
+use super::cpu;
+
+/// Components saved with XSAVE (XCR0 value), zero if FXSAVE is used.
+static XSAVE_COMPONENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Enables the x87 FPU, SSE and, if available, AVX in the current processor, and resets their
+/// registers. It must run on every processor. CR0.TS is left set, so the first use raises #NM.
+pub(crate) fn initialize() {
+    let processor = cpu::initialize();
+
+    unsafe {
+        let cr0 = Cr0::read() & !(Cr0::EMULATION | Cr0::TASK_SWITCHED);
+        Cr0::write(cr0 | Cr0::MONITOR_COPROCESSOR | Cr0::NUMERIC_ERROR);
+        Cr4::write(Cr4::read() | Cr4::OSFXSR | Cr4::OSXMMEXCPT);
+
+        if processor.has(Feature::Xsave) {
+            Cr4::write(Cr4::read() | Cr4::OSXSAVE);
+            XSAVE_COMPONENTS.store(enable_xsave_components(), Ordering::Relaxed);
+        }
+
+        fpu::initialize_x87();
+    }
+
+    set_available(false);
+}
+
+/// Enables the x87, SSE and AVX components in XCR0. AVX is left disabled if the processor does
+/// not support it or its state does not fit in an [ExtendedState]. Returns the enabled ones.
+///
+/// # Safety
+/// CR4.OSXSAVE must be set.
+unsafe fn enable_xsave_components() -> u64 {
+    let mut components = Xcr0::X87 | Xcr0::SSE;
+    if fpu::supported_components() & Xcr0::AVX != 0 {
+        Xcr0::write(components | Xcr0::AVX);
+        if fpu::xsave_area_size().is_some_and(|size| size <= ExtendedState::SIZE) {
+            components |= Xcr0::AVX;
+        }
+    }
+
+    Xcr0::write(components);
+    components
+}
+
+/// Makes the x87, SSE and AVX instructions usable (clears CR0.TS) or makes them raise #NM (sets
+/// CR0.TS).
+///
+/// # Arguments
+/// * `available` - Whether the registers hold the state of the running thread.
+pub(crate) fn set_available(available: bool) {
+    unsafe {
+        if available {
+            Cr0::clear_task_switched();
+        } else {
+            Cr0::write(Cr0::read() | Cr0::TASK_SWITCHED);
+        }
+    }
+}
+
+/// Saves the x87, SSE and AVX registers.
+///
+/// # Arguments
+/// * `state` - Save area.
+///
+/// # Safety
+/// The registers must be available (see [set_available]).
+pub(crate) unsafe fn save(state: &mut ExtendedState) {
+    match XSAVE_COMPONENTS.load(Ordering::Relaxed) {
+        0 => state.fxsave(),
+        components => state.xsave(components),
+    }
+}
+
+/// Restores the x87, SSE and AVX registers.
+///
+/// # Arguments
+/// * `state` - Save area, created with [ExtendedState::new] or filled by [save].
+///
+/// # Safety
+/// The registers must be available (see [set_available]).
+pub(crate) unsafe fn restore(state: &ExtendedState) {
+    match XSAVE_COMPONENTS.load(Ordering::Relaxed) {
+        0 => state.fxrstor(),
+        components => state.xrstor(components),
+    }
+}
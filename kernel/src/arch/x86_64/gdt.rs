@@ -1,46 +1,136 @@
 //! Global descriptor table initialization
-use lazy_static::lazy_static;
-use x86_64_custom::gdt::{Descriptor, GDTSelectors, GlobalDescriptorTable, TaskStateSegment};
+//!
+//! Every processor gets its own GDT, because the TSS descriptor points to the TSS of the
+//! processor, and its own TSS, because the kernel and interrupt stacks of the processors are
+//! different. Every GDT has the same layout, so the segment selectors are the same for all of
+//! them.
+use core::cell::UnsafeCell;
 
+use x86_64_custom::gdt::{
+    Descriptor, GDTSelectors, GlobalDescriptorTable, TaskStateSegment, DOUBLE_FAULT_IST_INDEX,
+};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::privilege::PrivilegeLevel;
+
+use super::smp::{per_cpu, MAX_CPUS};
 use crate::panic_screen;
+use crate::synchronization::once::Once;
 
 const ERROR_GDT_FULL: &str = "GDT is full. Tried to push a new value into it.";
 
-// Lazy initialize the TSS
-lazy_static! {
-    static ref TSS: TaskStateSegment = {
+/// Size of the double fault stack of every processor.
+const DOUBLE_FAULT_STACK_SIZE: usize = 4096 * 5;
+
+/// Double fault stacks, one per processor.
+struct DoubleFaultStacks(UnsafeCell<[[u8; DOUBLE_FAULT_STACK_SIZE]; MAX_CPUS]>);
+
+unsafe impl Sync for DoubleFaultStacks {}
+
+static DOUBLE_FAULT_STACKS: DoubleFaultStacks =
+    DoubleFaultStacks(UnsafeCell::new([[0; DOUBLE_FAULT_STACK_SIZE]; MAX_CPUS]));
+
+/// TSS of every processor, initialized the first time it is requested.
+static TSS: [Once<TaskStateSegment>; MAX_CPUS] = [const { Once::new() }; MAX_CPUS];
+
+/// GDT of every processor, initialized the first time it is requested.
+static GDT: [Once<(GlobalDescriptorTable, GDTSelectors)>; MAX_CPUS] =
+    [const { Once::new() }; MAX_CPUS];
+
+/// Returns the TSS of a processor.
+///
+/// # Arguments
+/// * `cpu_index` - Index of the processor.
+fn tss(cpu_index: usize) -> &'static TaskStateSegment {
+    TSS[cpu_index].call_once(|| {
+        let stack_bottom = DOUBLE_FAULT_STACKS
+            .0
+            .get()
+            .cast::<[u8; DOUBLE_FAULT_STACK_SIZE]>()
+            .wrapping_add(cpu_index) as u64;
+
         let mut tss = TaskStateSegment::new();
-        tss.init();
+        tss.set_interrupt_stack(
+            DOUBLE_FAULT_IST_INDEX,
+            VirtualMemoryAddress::new(stack_bottom + DOUBLE_FAULT_STACK_SIZE as u64),
+        );
         tss
+    })
+}
+
+/// Returns the GDT of a processor and its segment selectors.
+///
+/// # Arguments
+/// * `cpu_index` - Index of the processor.
+fn gdt(cpu_index: usize) -> &'static (GlobalDescriptorTable, GDTSelectors) {
+    GDT[cpu_index].call_once(|| build_gdt(tss(cpu_index)))
+}
+
+/// Builds a GDT whose TSS descriptor points to the given TSS.
+///
+/// # Arguments
+/// * `tss` - TSS of the processor that is going to load the GDT.
+fn build_gdt(tss: &'static TaskStateSegment) -> (GlobalDescriptorTable, GDTSelectors) {
+    let mut gdt = GlobalDescriptorTable::new();
+
+    // Initialize the segment selectors. The order matters for `sysret`: it loads the user data
+    // segment from the entry after the kernel data one and the user code segment from the next
+    let Ok(cs) = gdt.add_entry(Descriptor::kernel_code_segment()) else {
+        panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
+    };
+    let Ok(ds) = gdt.add_entry(Descriptor::kernel_data_segment()) else {
+        panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
+    };
+    let Ok(user_ds) = gdt.add_entry(Descriptor::user_data_segment()) else {
+        panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
+    };
+    let Ok(user_cs) = gdt.add_entry(Descriptor::user_code_segment()) else {
+        panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
+    };
+    let Ok(tss) = gdt.add_entry(Descriptor::task_state_segment(tss)) else {
+        panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
     };
 
-    pub static ref GDT: (GlobalDescriptorTable, GDTSelectors) = {
-        let mut gdt = GlobalDescriptorTable::new();
-
-        // Initialize the segment selectors
-        let Ok(kernel_code_segment_selector) = gdt.add_entry(Descriptor::kernel_code_segment()) else {
-             panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
-        };
-        gdt.add_entry(Descriptor::kernel_data_segment());
-        gdt.add_entry(Descriptor::user_code_segment());
-        gdt.add_entry(Descriptor::user_data_segment());
-        let Ok(tss_selector) = gdt.add_entry(Descriptor::task_state_segment(&TSS)) else {
-             panic_screen!("{}\n{:?}", ERROR_GDT_FULL, gdt);
-        };
-
-        let selectors = GDTSelectors {
-            cs: kernel_code_segment_selector,
-            tss: tss_selector,
-        };
-
-        (gdt, selectors)
+    let selectors = GDTSelectors {
+        cs,
+        ds,
+        user_cs,
+        user_ds,
+        tss,
     };
+
+    (gdt, selectors)
+}
+
+/// Returns the segment selectors of the GDT. They are the same for every processor.
+pub fn selectors() -> &'static GDTSelectors {
+    &gdt(0).1
 }
 
-/// Loads the GDT
-pub(crate) fn load_gdt() {
-    GDT.0.load();
+/// Loads the GDT and the TSS of a processor. It must run on that processor.
+///
+/// # Arguments
+/// * `cpu_index` - Index of the processor.
+pub(crate) fn load_gdt(cpu_index: usize) {
+    let (gdt, selectors) = gdt(cpu_index);
+    gdt.load();
     // Segment registers and tss should be updated AFTER the GDT is loaded on memory
-    GlobalDescriptorTable::update_selector_registers(&GDT.1);
-    GlobalDescriptorTable::load_tss(&GDT.1.tss);
+    GlobalDescriptorTable::update_selector_registers(selectors);
+    GlobalDescriptorTable::load_tss(&selectors.tss);
+}
+
+/// Sets the stack the CPU switches to when an interrupt or a system call arrives while running user
+/// code. Every thread that can run in user mode needs its own kernel stack, so it must be updated
+/// on every context switch. It is set in the TSS of the current processor.
+///
+/// # Arguments
+/// * `stack_top` - Top address of the kernel stack.
+///
+/// # Safety
+/// The caller must be sure that the stack is valid and not used by anything else while the
+/// current thread runs in user mode.
+pub(crate) unsafe fn set_kernel_stack(stack_top: VirtualMemoryAddress) {
+    // Before the per-CPU data is set up only the bootstrap processor runs
+    let cpu_index = per_cpu::current().map_or(0, |cpu| cpu.index());
+    tss(cpu_index).set_privilege_stack(PrivilegeLevel::Ring0, stack_top);
+    super::syscall::set_kernel_stack(stack_top);
 }
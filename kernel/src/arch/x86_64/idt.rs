@@ -1,37 +1,148 @@
 //! Interrupt descriptor table initialization
+//!
+//! Every handler that can interrupt user code is a trap handler, whose entry point switches to the
+//! kernel GS base (see [per_cpu](super::smp::per_cpu)).
 
 use super::interrupts::{
-    hardware::{keyboard_interrupt_handler, timer_interrupt_handler},
+    hardware::{
+        KEYBOARD_INTERRUPT_HANDLER, LOCAL_APIC_SPURIOUS_INTERRUPT_HANDLER,
+        LOCAL_APIC_TIMER_INTERRUPT_HANDLER, RTC_INTERRUPT_HANDLER, TIMER_INTERRUPT_HANDLER,
+        TLB_SHOOTDOWN_INTERRUPT_HANDLER,
+    },
     software::{
-        breakpoint_handler, divide_by_zero_handler, double_fault_handler, page_fault_handler,
+        BREAKPOINT_HANDLER, DEVICE_NOT_AVAILABLE_HANDLER, DIVIDE_BY_ZERO_HANDLER,
+        DOUBLE_FAULT_HANDLER, GENERAL_PROTECTION_FAULT_HANDLER, INVALID_OPCODE_HANDLER,
+        PAGE_FAULT_HANDLER, SIMD_FLOATING_POINT_HANDLER, X87_FLOATING_POINT_HANDLER,
     },
 };
-use lazy_static::lazy_static;
+use super::syscall;
+use crate::synchronization::lazy::Lazy;
 use x86_64_custom::{
-    gdt::DOUBLE_FAULT_IST_INDEX, idt::InterruptDescriptorTable, interrupts::InterruptIndex,
+    gdt::DOUBLE_FAULT_IST_INDEX,
+    idt::InterruptDescriptorTable,
+    interrupts::{InterruptIndex, LocalApicInterruptIndex},
+    privilege::PrivilegeLevel,
 };
 
-lazy_static! {
-    pub static ref IDT: InterruptDescriptorTable = {
-        let mut idt = InterruptDescriptorTable::new();
-
-        // Setup software interrupts
-        idt.breakpoint.set_handler_function(breakpoint_handler);
-        idt.divide_by_zero
-            .set_handler_function(divide_by_zero_handler);
-        idt.page_fault.set_handler_function(page_fault_handler);
-        idt.double_fault
-            .set_handler_function(double_fault_handler)
-            .set_stack_index(DOUBLE_FAULT_IST_INDEX);
-
-        // Setup hardware interrupts
-        idt[InterruptIndex::Timer.as_usize()].set_handler_function(timer_interrupt_handler);
-        idt[InterruptIndex::Keyboard.as_usize()].set_handler_function(keyboard_interrupt_handler);
-
-        idt
-    };
-}
+pub static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
+    let mut idt = InterruptDescriptorTable::new();
+
+    // Setup software interrupts
+    // `int3` is also allowed from user mode, debuggers use it
+    idt.breakpoint
+        .set_trap_handler(BREAKPOINT_HANDLER)
+        .set_privilege_level(PrivilegeLevel::Ring3);
+    idt.divide_by_zero.set_trap_handler(DIVIDE_BY_ZERO_HANDLER);
+    idt.invalid_opcode.set_trap_handler(INVALID_OPCODE_HANDLER);
+    idt.general_protection_fault
+        .set_trap_handler(GENERAL_PROTECTION_FAULT_HANDLER);
+    idt.page_fault.set_trap_handler(PAGE_FAULT_HANDLER);
+    idt.device_not_available
+        .set_trap_handler(DEVICE_NOT_AVAILABLE_HANDLER);
+    idt.x87_floating_point
+        .set_trap_handler(X87_FLOATING_POINT_HANDLER);
+    idt.simd_floating_point
+        .set_trap_handler(SIMD_FLOATING_POINT_HANDLER);
+    idt.double_fault
+        .set_trap_handler(DOUBLE_FAULT_HANDLER)
+        .set_stack_index(DOUBLE_FAULT_IST_INDEX);
+
+    // Setup hardware interrupts
+    idt[InterruptIndex::Timer.as_usize()].set_trap_handler(TIMER_INTERRUPT_HANDLER);
+    idt[InterruptIndex::Keyboard.as_usize()].set_trap_handler(KEYBOARD_INTERRUPT_HANDLER);
+    idt[InterruptIndex::RealTimeClock.as_usize()].set_trap_handler(RTC_INTERRUPT_HANDLER);
+    idt[LocalApicInterruptIndex::Timer.as_usize()]
+        .set_trap_handler(LOCAL_APIC_TIMER_INTERRUPT_HANDLER);
+    idt[LocalApicInterruptIndex::TlbShootdown.as_usize()]
+        .set_trap_handler(TLB_SHOOTDOWN_INTERRUPT_HANDLER);
+    idt[LocalApicInterruptIndex::Spurious.as_usize()]
+        .set_trap_handler(LOCAL_APIC_SPURIOUS_INTERRUPT_HANDLER);
+
+    // Setup the legacy system call gate
+    syscall::install_interrupt_gate(&mut idt);
+
+    idt
+});
 
 pub(crate) fn load_idt() {
     IDT.load();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x86_64_custom::idt::{Entry, GateType};
+    use x86_64_custom::memory::address::VirtualMemoryAddress;
+    use x86_64_custom::registers::segments::SegmentSelector;
+
+    /// Address of a handler with bits in the low, middle and high parts of the entry.
+    const HANDLER_ADDRESS: u64 = 0xffff_8123_4567_89ab;
+
+    #[test_case]
+    fn entry_options_round_trip() {
+        let mut entry = Entry::<()>::missing();
+        assert!(!entry.options().is_present());
+
+        let options =
+            unsafe { entry.set_handler_address(VirtualMemoryAddress::new(HANDLER_ADDRESS)) };
+        assert!(options.is_present());
+        assert_eq!(GateType::Interrupt, options.gate_type());
+        assert_eq!(PrivilegeLevel::Ring0, options.privilege_level());
+        assert_eq!(None, options.stack_index());
+
+        options.set_gate_type(GateType::Trap);
+        assert_eq!(GateType::Trap, options.gate_type());
+        options.set_gate_type(GateType::Interrupt);
+        assert_eq!(GateType::Interrupt, options.gate_type());
+
+        options.set_privilege_level(PrivilegeLevel::Ring3);
+        assert_eq!(PrivilegeLevel::Ring3, options.privilege_level());
+        // The other fields are not touched
+        assert_eq!(GateType::Interrupt, options.gate_type());
+        assert!(options.is_present());
+
+        for index in [0, DOUBLE_FAULT_IST_INDEX, 6] {
+            options.set_stack_index(index);
+            assert_eq!(Some(index), options.stack_index());
+        }
+        assert_eq!(PrivilegeLevel::Ring3, options.privilege_level());
+
+        assert_eq!(HANDLER_ADDRESS, entry.handler_address().as_u64());
+    }
+
+    #[test_case]
+    fn entry_code_selector_round_trip() {
+        let mut entry = Entry::<()>::missing();
+        let selector = SegmentSelector::new(3, PrivilegeLevel::Ring0);
+
+        unsafe { entry.set_code_selector(selector) }.set_privilege_level(PrivilegeLevel::Ring3);
+
+        assert_eq!(*selector, entry.code_selector());
+        assert_eq!(PrivilegeLevel::Ring3, entry.options().privilege_level());
+    }
+
+    #[test_case]
+    fn kernel_idt_entries_are_set_up() {
+        // `int3` can be used from user mode, the exceptions can not be raised with `int`
+        let breakpoint = IDT.breakpoint.options();
+        assert!(breakpoint.is_present());
+        assert_eq!(PrivilegeLevel::Ring3, breakpoint.privilege_level());
+        assert_eq!(
+            BREAKPOINT_HANDLER.address(),
+            IDT.breakpoint.handler_address().as_u64()
+        );
+        assert_eq!(
+            PrivilegeLevel::Ring0,
+            IDT.page_fault.options().privilege_level()
+        );
+
+        assert_eq!(
+            Some(DOUBLE_FAULT_IST_INDEX),
+            IDT.double_fault.options().stack_index()
+        );
+        assert_eq!(
+            PrivilegeLevel::Ring3,
+            IDT[syscall::SYSCALL_VECTOR].options().privilege_level()
+        );
+    }
+}
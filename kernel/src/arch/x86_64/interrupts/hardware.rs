@@ -1,16 +1,28 @@
-use crate::interrupts::{keyboard_handler, timer_handler};
-use crate::synchronization::spinlock::Mutex;
-use x86_64_custom::interrupts::InterruptIndex;
-use x86_64_custom::{create_interrupt_handler, interrupts::IBMPcAt8259};
+use crate::arch::x86_64::local_apic::{self, LOCAL_APIC};
+use crate::arch::x86_64::smp;
+use crate::interrupts::{keyboard_handler, rtc_handler, timer_handler};
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::thread;
+use x86_64_custom::idt::TrapFrame;
+use x86_64_custom::interrupts::{InterruptIndex, LocalApicInterruptIndex};
+use x86_64_custom::{create_interrupt_handler, create_trap_handler, interrupts::IBMPcAt8259};
 
-pub static PICS: Mutex<IBMPcAt8259> = Mutex::new(IBMPcAt8259::new());
+pub static PICS: IrqSafeMutex<IBMPcAt8259> = IrqSafeMutex::new(IBMPcAt8259::new());
 
-create_interrupt_handler!(timer_interrupt_handler, InterruptIndex::Timer, PICS, {
-    timer_handler();
-});
+create_interrupt_handler!(
+    TIMER_INTERRUPT_HANDLER,
+    InterruptIndex::Timer,
+    PICS,
+    {
+        timer_handler();
+    },
+    {
+        thread::schedule();
+    }
+);
 
 create_interrupt_handler!(
-    keyboard_interrupt_handler,
+    KEYBOARD_INTERRUPT_HANDLER,
     InterruptIndex::Keyboard,
     PICS,
     {
@@ -20,5 +32,53 @@ create_interrupt_handler!(
         let scancode: u8 = unsafe { port.read() };
 
         keyboard_handler(scancode);
+    },
+    {
+        thread::schedule();
+    }
+);
+
+create_interrupt_handler!(
+    RTC_INTERRUPT_HANDLER,
+    InterruptIndex::RealTimeClock,
+    PICS,
+    {
+        rtc_handler();
+    },
+    {
+        thread::schedule();
     }
 );
+
+create_interrupt_handler!(
+    LOCAL_APIC_TIMER_INTERRUPT_HANDLER,
+    LocalApicInterruptIndex::Timer,
+    LOCAL_APIC,
+    {
+        local_apic::timer_handler();
+    },
+    {
+        thread::schedule();
+    }
+);
+
+create_interrupt_handler!(
+    TLB_SHOOTDOWN_INTERRUPT_HANDLER,
+    LocalApicInterruptIndex::TlbShootdown,
+    LOCAL_APIC,
+    {
+        smp::tlb_shootdown_handler();
+    }
+);
+
+create_trap_handler!(
+    LOCAL_APIC_SPURIOUS_INTERRUPT_HANDLER,
+    LocalApicInterruptIndex::Spurious.as_u8(),
+    local_apic_spurious_interrupt_handler
+);
+
+/// The spurious interrupt must not be acknowledged, so it does not use `create_interrupt_handler`.
+extern "C" fn local_apic_spurious_interrupt_handler(_frame: &mut TrapFrame) {
+    x86_64_custom::interrupts::statistics::INTERRUPT_STATISTICS
+        .record(LocalApicInterruptIndex::Spurious.as_u8());
+}
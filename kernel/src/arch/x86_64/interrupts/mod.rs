@@ -1,13 +1,14 @@
 //! This modules takes care of all the x86 interruption details and then passes the information to
 //! the actual kernel handlers.
 //!
-//! The x86-interrupt calling convention:
+//! The handlers are trap handlers:
 //
 //! Since we don't know when an exception occurs, we can't backup any registers before. This means
 //! we can't use a calling convention that relies on caller-saved registers for exception handlers.
-//! Instead, we need a calling convention that preserves all registers. The x86-interrupt calling
-//! convention is such a calling convention, so it guarantees that all register values are restored
-//! to their original values on function return.
+//! Instead, the entry points generated by `create_trap_handler` save every register before calling
+//! the handler and restore them before returning. They also switch to the kernel GS base when the
+//! interrupt arrives while running user code, which the `x86-interrupt` calling convention can not
+//! do.
 //
 //! https://os.phil-opp.com/cpu-exceptions/#the-interrupt-calling-convention
 
@@ -1,30 +1,135 @@
-use x86_64_custom::idt::InterruptStackFrame;
+use x86_64_custom::create_trap_handler;
+use x86_64_custom::idt::{ExceptionVector, TrapFrame};
+use x86_64_custom::interrupts::statistics::INTERRUPT_STATISTICS;
 
-use crate::{panic_screen, println};
+use crate::{panic_screen, println, thread};
 
-pub extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
-    println!("Exception BREAKPOINT reached\n {:#?}", stack_frame);
+/// Exit code of the threads finished by a fault in user mode.
+const FAULT_EXIT_CODE: usize = usize::MAX;
+
+create_trap_handler!(
+    BREAKPOINT_HANDLER,
+    ExceptionVector::Breakpoint as u8,
+    breakpoint_handler
+);
+
+/// Prints every register of the code that hit the breakpoint. `int3` is a trap, so the code
+/// continues with the next instruction.
+extern "C" fn breakpoint_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::Breakpoint.as_u8());
+    println!("Exception BREAKPOINT reached\n{}", frame);
+}
+
+create_trap_handler!(
+    DIVIDE_BY_ZERO_HANDLER,
+    ExceptionVector::DivideByZero as u8,
+    divide_by_zero_handler
+);
+
+extern "C" fn divide_by_zero_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::DivideByZero.as_u8());
+    println!("Exception DIVIDED BY ZERO reached\n{}", frame);
+}
+
+create_trap_handler!(
+    DEVICE_NOT_AVAILABLE_HANDLER,
+    ExceptionVector::DeviceNotAvailable as u8,
+    device_not_available_handler
+);
+
+/// Raised by the first x87, SSE or AVX instruction after a context switch, see
+/// [fpu](crate::arch::x86_64::fpu).
+extern "C" fn device_not_available_handler(_frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::DeviceNotAvailable.as_u8());
+    thread::claim_fpu();
 }
 
-pub extern "x86-interrupt" fn divide_by_zero_handler(stack_frame: InterruptStackFrame) {
-    println!("Exception DIVIDED BY ZERO reached\n {:#?}", stack_frame);
+create_trap_handler!(
+    X87_FLOATING_POINT_HANDLER,
+    ExceptionVector::X87FloatingPoint as u8,
+    x87_floating_point_handler
+);
+
+extern "C" fn x87_floating_point_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::X87FloatingPoint.as_u8());
+    fault("x87 FLOATING POINT", frame);
 }
 
-pub extern "x86-interrupt" fn page_fault_handler(
-    stack_frame: InterruptStackFrame,
-    error_code: u64,
-) {
-    println!("Error code: {error_code}");
-    println!("Exception PAGE FAULT reached\n {:#?}", stack_frame);
+create_trap_handler!(
+    SIMD_FLOATING_POINT_HANDLER,
+    ExceptionVector::SimdFloatingPoint as u8,
+    simd_floating_point_handler
+);
+
+extern "C" fn simd_floating_point_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::SimdFloatingPoint.as_u8());
+    fault("SIMD FLOATING POINT", frame);
 }
 
-pub extern "x86-interrupt" fn double_fault_handler(
-    stack_frame: InterruptStackFrame,
-    error_code: u64,
-) -> ! {
-    panic_screen!(
-        "Exception DOUBLE FAULT reached\n\n{}Error code: {}",
-        stack_frame,
-        error_code
+/// Handles a fault that can not be recovered from. Returning would execute the faulting
+/// instruction again, so the thread is finished if it was running user code. A fault in the kernel
+/// is a bug.
+///
+/// # Arguments
+/// * `name` - Name of the exception.
+/// * `frame` - State of the interrupted code.
+fn fault(name: &str, frame: &TrapFrame) {
+    if !frame.from_user_mode() {
+        panic_screen!("Exception {} reached\n\n{}", name, frame);
+    }
+
+    println!(
+        "Exception {} reached in user mode, finishing the thread\n{}",
+        name, frame
     );
+    thread::exit(FAULT_EXIT_CODE);
+}
+
+create_trap_handler!(
+    PAGE_FAULT_HANDLER,
+    ExceptionVector::PageFault as u8,
+    page_fault_handler,
+    error_code
+);
+
+/// Nothing is mapped on demand, so every page fault is an access to a wrong address.
+extern "C" fn page_fault_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::PageFault.as_u8());
+    fault("PAGE FAULT", frame);
+}
+
+create_trap_handler!(
+    GENERAL_PROTECTION_FAULT_HANDLER,
+    ExceptionVector::GeneralProtectionFault as u8,
+    general_protection_fault_handler,
+    error_code
+);
+
+/// Raised among others by privileged instructions (`hlt`, `cli`) in user mode.
+extern "C" fn general_protection_fault_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::GeneralProtectionFault.as_u8());
+    fault("GENERAL PROTECTION FAULT", frame);
+}
+
+create_trap_handler!(
+    INVALID_OPCODE_HANDLER,
+    ExceptionVector::InvalidOpcode as u8,
+    invalid_opcode_handler
+);
+
+extern "C" fn invalid_opcode_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::InvalidOpcode.as_u8());
+    fault("INVALID OPCODE", frame);
+}
+
+create_trap_handler!(
+    DOUBLE_FAULT_HANDLER,
+    ExceptionVector::DoubleFault as u8,
+    double_fault_handler,
+    error_code
+);
+
+extern "C" fn double_fault_handler(frame: &mut TrapFrame) {
+    INTERRUPT_STATISTICS.record(ExceptionVector::DoubleFault.as_u8());
+    panic_screen!("Exception DOUBLE FAULT reached\n\n{}", frame);
 }
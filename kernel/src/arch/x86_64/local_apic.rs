@@ -0,0 +1,145 @@
+//! Local APIC timer and tickless idle
+//!
+//! When the processor has a local APIC, its timer replaces the PIT as the source of the kernel
+//! tick. Besides being per processor, it can be reprogrammed cheaply, which allows us to stop the
+//! periodic tick while the CPU is idle: instead of waking up every millisecond to find out there
+//! is nothing to do, the timer is programmed in one-shot mode to fire when the nearest kernel timer
+//! is due. When the CPU wakes up (by the timer or by any other interrupt) the tick counter is
+//! advanced by the time spent idle and the periodic tick is restarted.
+//!
+//! Time smaller than a tick spent idle is lost when the CPU is woken up by another interrupt, the
+//! wall clock is resynchronized with the RTC anyway.
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use x86_64::instructions::interrupts::{enable, enable_and_hlt};
+use x86_64_custom::cpuid;
+use x86_64_custom::interrupts::{LocalApic, LocalApicTimerDivide, LocalApicTimerMode};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+
+use super::{paging, physical_memory_offset};
+use crate::interrupts::timer;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::thread;
+use crate::time::{clock_source, ticks, TICKS_PER_SECOND};
+
+/// Divide configuration of the timer.
+const TIMER_DIVIDE: LocalApicTimerDivide = LocalApicTimerDivide::By16;
+
+pub static LOCAL_APIC: IrqSafeMutex<LocalApic> = IrqSafeMutex::new(LocalApic::new());
+
+/// Timer count that corresponds to a kernel tick, zero if the local APIC timer is not used.
+static COUNT_PER_TICK: AtomicU32 = AtomicU32::new(0);
+
+/// Set while the CPU is idle with the periodic tick stopped.
+static IDLE: AtomicBool = AtomicBool::new(false);
+
+/// Initializes the local APIC and, if it is present, calibrates its timer and starts it as the
+/// kernel tick source. Returns false if there is no local APIC (the PIT keeps driving the tick).
+///
+/// Must be called with the interrupts disabled, after the clock sources are initialized (the timer
+/// is calibrated against them).
+///
+/// # Arguments
+/// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+pub(crate) fn initialize(physical_memory_offset: VirtualMemoryAddress) -> bool {
+    if !cpuid::has_apic() {
+        return false;
+    }
+
+    // The bootloader maps the whole physical address space, including the registers, but cached.
+    // The page tables are shared, so the application processors find them uncached too
+    let mapped =
+        unsafe { paging::map_uncached(physical_memory_offset, LocalApic::physical_base()) };
+    if mapped.is_err() {
+        return false;
+    }
+
+    let mut local_apic = LOCAL_APIC.lock();
+    unsafe {
+        local_apic.initialize(physical_memory_offset);
+        local_apic.start_masked_timer(u32::MAX, TIMER_DIVIDE);
+    }
+    // The timer counts down, we measure how much it decreased
+    let frequency = clock_source::calibrate(|| {
+        u64::from(u32::MAX - unsafe { local_apic.timer_current_count() })
+    });
+    let count_per_tick = u32::try_from(frequency / TICKS_PER_SECOND).unwrap_or(u32::MAX);
+    if count_per_tick == 0 {
+        unsafe { local_apic.stop_timer() };
+        return false;
+    }
+
+    COUNT_PER_TICK.store(count_per_tick, Ordering::Relaxed);
+    unsafe { local_apic.start_timer(count_per_tick, TIMER_DIVIDE, LocalApicTimerMode::Periodic) };
+    true
+}
+
+/// Enables the local APIC of an application processor. Its timer is not started: the kernel tick
+/// is driven by the bootstrap processor.
+pub(crate) fn initialize_application_processor() {
+    if let Some(physical_memory_offset) = physical_memory_offset() {
+        unsafe { LOCAL_APIC.lock().initialize(physical_memory_offset) };
+    }
+}
+
+/// Local APIC timer interrupt handler.
+pub(crate) fn timer_handler() {
+    // While idle, the ticks are accounted when the CPU wakes up
+    if !IDLE.load(Ordering::Relaxed) {
+        timer::handler();
+    }
+}
+
+/// Halts the CPU until the next interrupt. If the local APIC timer drives the kernel tick, the
+/// periodic tick is stopped until the nearest kernel timer or sleeping thread is due.
+///
+/// Must be called with the interrupts disabled, so nothing can change the timers (or any other
+/// condition the caller checked before going idle) between the check and the halt. Returns with
+/// the interrupts enabled.
+pub fn idle() {
+    let count_per_tick = COUNT_PER_TICK.load(Ordering::Relaxed);
+    if count_per_tick == 0 {
+        enable_and_hlt();
+        return;
+    }
+
+    let max_idle_ticks = u64::from(u32::MAX / count_per_tick);
+    let idle_ticks = [timer::next_deadline(), thread::next_wake_up()]
+        .into_iter()
+        .flatten()
+        .min()
+        .map_or(max_idle_ticks, |deadline| {
+            deadline.as_ticks().saturating_sub(ticks())
+        })
+        .min(max_idle_ticks);
+
+    // It is not worth stopping the tick if a timer is due in the next one
+    if idle_ticks <= 1 {
+        enable_and_hlt();
+        return;
+    }
+
+    // `idle_ticks` is capped so this does not overflow
+    let initial_count = idle_ticks as u32 * count_per_tick;
+    unsafe {
+        LOCAL_APIC
+            .lock()
+            .start_timer(initial_count, TIMER_DIVIDE, LocalApicTimerMode::OneShot)
+    };
+    IDLE.store(true, Ordering::Relaxed);
+
+    enable_and_hlt();
+
+    // Any interrupt could have woken us up, not only the timer
+    x86_64::instructions::interrupts::disable();
+    IDLE.store(false, Ordering::Relaxed);
+    let elapsed_ticks = {
+        let mut local_apic = LOCAL_APIC.lock();
+        let remaining_count = unsafe { local_apic.timer_current_count() };
+        unsafe {
+            local_apic.start_timer(count_per_tick, TIMER_DIVIDE, LocalApicTimerMode::Periodic)
+        };
+        (initial_count - remaining_count) / count_per_tick
+    };
+    timer::advance(u64::from(elapsed_ticks));
+    enable();
+}
@@ -1,32 +1,86 @@
 //! This module contains all the initialization code for the x86_64 architecture.
+pub mod acpi;
+pub mod cpu;
+pub(crate) mod fpu;
 mod gdt;
 mod idt;
 mod interrupts;
+mod local_apic;
 mod paging;
+pub mod power;
+pub mod smp;
+mod syscall;
+mod user_mode;
 
-use crate::synchronization::spinlock::Mutex;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::time::{clock_source, TICKS_PER_SECOND};
 use x86_64_custom::interrupts::IBMPcAt8259;
 use x86_64_custom::memory::address::VirtualMemoryAddress;
 use x86_64_custom::memory::Translator;
+use x86_64_custom::timers::{Hpet, Pit8254, PitMode};
 
-pub use paging::TRANSLATOR;
+pub use gdt::selectors;
+pub(crate) use gdt::set_kernel_stack;
+pub use local_apic::{idle, LOCAL_APIC};
+pub(crate) use paging::{no_execute_enabled, page_flags, switch_address_space, unmap_page};
+pub use paging::{physical_memory_offset, TRANSLATOR};
+pub use syscall::SYSCALL_VECTOR;
+pub use user_mode::enter_user_mode;
 // TODO The idea here is to define a trait that abstacts away the interruption habdling either if
 // it is with the IBM PC/AT 8259 Architecture or with the APIC interface.
-pub static PICS: Mutex<IBMPcAt8259> = Mutex::new(IBMPcAt8259::new());
+pub static PICS: IrqSafeMutex<IBMPcAt8259> = IrqSafeMutex::new(IBMPcAt8259::new());
+pub static PIT: IrqSafeMutex<Pit8254> = IrqSafeMutex::new(Pit8254::new());
+
+/// IRQ line of the PIT channel 0.
+const PIT_IRQ: u8 = 0;
 
 /// Initializes the x86_64 arch
 pub fn initialize_x86_64_arch(physical_memory_offset: VirtualMemoryAddress) {
     // Initialize system tables
-    gdt::load_gdt();
+    gdt::load_gdt(0);
+    smp::initialize_bootstrap_processor();
     idt::load_idt();
+    syscall::initialize();
+    fpu::initialize();
 
     // Initialize interrupts
     unsafe { PICS.lock().initialize() };
+    // The timer interrupt drives the kernel tick counter
+    unsafe {
+        PIT.lock()
+            .set_frequency(TICKS_PER_SECOND as u32, PitMode::Periodic)
+    };
+    // The clock sources are calibrated against the PIT (or the HPET), before enabling the
+    // interrupts so the measure is not disturbed
+    // bootloader 0.9 does not report the RSDP, it is searched in the BIOS memory areas
+    let _ = acpi::initialize(physical_memory_offset, None);
+    clock_source::initialize(find_hpet(physical_memory_offset));
+    // If there is a local APIC, its timer replaces the PIT as the tick source
+    if local_apic::initialize(physical_memory_offset) {
+        unsafe { PICS.lock().set_masked(PIT_IRQ, true) };
+    }
     x86_64::instructions::interrupts::enable(); // TODO: Write our own asm code for this
 
     // Setup paging translation offset
     // TODO: we are reassigning a static mut, check if we can do this in some other way
     unsafe { TRANSLATOR = Translator::new(physical_memory_offset) }
+    paging::set_physical_memory_offset(physical_memory_offset);
+}
+
+/// Finds the HPET through the ACPI tables.
+fn find_hpet(physical_memory_offset: VirtualMemoryAddress) -> Option<Hpet> {
+    let hpet_table = acpi::get()?.hpet()?;
+    // The bootloader maps the whole physical address space, including the HPET registers, but
+    // cached
+    let base = unsafe {
+        paging::map_uncached(
+            physical_memory_offset,
+            hpet_table.base_address.physical_address(),
+        )
+    }
+    .ok()?;
+
+    Some(unsafe { Hpet::new(base) })
 }
 
 // NOTE: For debugging
@@ -1,7 +1,142 @@
-use x86_64_custom::memory::address::VirtualMemoryAddress;
-use x86_64_custom::memory::Translator;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use x86_64_custom::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+use x86_64_custom::memory::mapper::Mapper;
+use x86_64_custom::memory::paging::page::Page;
+use x86_64_custom::memory::paging::page_size::Size4KiB;
+use x86_64_custom::memory::paging::page_table::{PageTable, PageTableEntryFlags, PageTableLevel};
+use x86_64_custom::memory::paging::paging_error::PagingError;
+use x86_64_custom::memory::{tlb, Translator};
+use x86_64_custom::registers::control::Cr3;
+use x86_64_custom::registers::msr::Msr;
 
 pub static mut TRANSLATOR: Translator = Translator::new(VirtualMemoryAddress::zero());
 
+/// Virtual address where the bootloader mapped the whole physical memory. Zero until the arch is
+/// initialized.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
+/// Sets the virtual address where the whole physical memory is mapped.
+///
+/// # Arguments
+/// * `physical_memory_offset` - Offset given by the bootloader.
+pub(crate) fn set_physical_memory_offset(physical_memory_offset: VirtualMemoryAddress) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
+}
+
+/// Returns the virtual address where the whole physical memory is mapped, if the arch is already
+/// initialized.
+pub fn physical_memory_offset() -> Option<VirtualMemoryAddress> {
+    match PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) {
+        0 => None,
+        offset => Some(VirtualMemoryAddress::new(offset)),
+    }
+}
+
+/// Unmaps the 4KiB page that starts at the given address, any access to it will page fault. The
+/// frame it was mapped to is not reused.
+///
+/// Fails with `PagingError::PageNotMapped` if the arch is not initialized yet.
+///
+/// # Arguments
+/// * `address` - Start address of the page.
+///
+/// # Safety
+///
+/// This is unsafe because the programmer must be sure that nothing is going to access the page.
+pub(crate) unsafe fn unmap_page(address: VirtualMemoryAddress) -> Result<(), PagingError> {
+    let physical_memory_offset = physical_memory_offset().ok_or(PagingError::PageNotMapped)?;
+    let page = Page::<Size4KiB>::from_starting_address(address)?;
+
+    Mapper::<Size4KiB>::new(physical_memory_offset)
+        .unmap(page)
+        .map(|_| ())
+}
+
+/// Returns the effective flags of the page that contains `address` in the current address space,
+/// or `None` if it is not mapped (or the arch is not initialized yet). See
+/// [Translator::page_flags].
+///
+/// # Arguments
+/// * `address` - Address to look up.
+pub(crate) fn page_flags(address: VirtualMemoryAddress) -> Option<u64> {
+    let physical_memory_offset = physical_memory_offset()?;
+    // The bootloader maps every page table at the physical memory offset
+    unsafe { Translator::new(physical_memory_offset).page_flags(address) }
+}
+
+/// Disables the cache for the page of the physical memory window that maps `address`, as memory
+/// mapped registers need (a cached read could return a stale value, and writes could be delayed
+/// or merged). Returns the virtual address of `address` in the window.
+///
+/// The bootloader maps the window with 2MiB pages, so the rest of the 2MiB page is not cached
+/// either. Memory mapped registers are grouped in regions without RAM, so nothing else slows
+/// down.
+///
+/// Fails with `PagingError::PageNotMapped` if `address` is outside of the window.
+///
+/// # Arguments
+/// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+/// * `address` - Physical address of the registers.
+///
+/// # Safety
+///
+/// This is unsafe because the programmer must be sure that the page holds memory mapped registers,
+/// and that no other processor is using it yet.
+pub(crate) unsafe fn map_uncached(
+    physical_memory_offset: VirtualMemoryAddress,
+    address: PhysicalMemoryAddress,
+) -> Result<VirtualMemoryAddress, PagingError> {
+    let virtual_address = physical_memory_offset + address;
+    let levels = [
+        PageTableLevel::Level4,
+        PageTableLevel::Level3,
+        PageTableLevel::Level2,
+        PageTableLevel::Level1,
+    ];
+
+    let mut page_table: &mut PageTable = &mut *(physical_memory_offset + Cr3::read()).as_mut_ptr();
+    for (index, level) in levels.iter().enumerate() {
+        let entry = &mut page_table[virtual_address.get_page_table_index(*level)];
+        if !entry.is_present() {
+            return Err(PagingError::PageNotMapped);
+        }
+
+        // Huge pages are mapped by a level 3 or level 2 entry, there are no more levels
+        if index == levels.len() - 1 || entry.is_huge() {
+            entry.set_flags(PageTableEntryFlags::NO_CACHE | PageTableEntryFlags::WRITE_THROUGH);
+            tlb::flush_local(virtual_address);
+            return Ok(virtual_address);
+        }
+
+        page_table = &mut *(physical_memory_offset + entry.address()).as_mut_ptr();
+    }
+
+    unreachable!("the last level always returns")
+}
+
+/// No-Execute Enable bit of the EFER MSR.
+const EFER_NO_EXECUTE_ENABLE: u64 = 1 << 11;
+
+/// Loads the given PML4, unless it is already loaded (reloading it flushes the TLB for nothing).
+///
+/// # Arguments
+/// * `level_4_table` - Physical address of the PML4.
+///
+/// # Safety
+/// The address space must map the kernel.
+pub(crate) unsafe fn switch_address_space(level_4_table: PhysicalMemoryAddress) {
+    if Cr3::read().as_u64() != level_4_table.as_u64() {
+        Cr3::write(level_4_table);
+    }
+}
+
+/// Returns if the no-execute page flag can be used. If it is disabled, the flag is a reserved
+/// bit and any page that has it set page faults.
+pub(crate) fn no_execute_enabled() -> bool {
+    let efer = Msr::IA32_EFER;
+    unsafe { efer.read() & EFER_NO_EXECUTE_ENABLE != 0 }
+}
+
 // NOTE: For debug
 //use crate::memory::Translator;
@@ -0,0 +1,248 @@
+//! Power management
+//!
+//! - Shutdown: enters the ACPI soft off sleep state (S5). The SLP_TYPa and SLP_TYPb values of the
+//!   state are read from the `\_S5` object of the DSDT and written, together with SLP_EN, to the
+//!   PM1a and PM1b control registers described in the FADT.
+//! - Reboot: tries, in order, the ACPI reset register, the reset line of the 8042 keyboard
+//!   controller and a triple fault (an exception with an empty IDT), that always resets the
+//!   processor.
+//! - Halt: disables the interrupts and halts the processor for good.
+//!
+//! For more info:
+//! https://wiki.osdev.org/Shutdown
+//! https://wiki.osdev.org/Reboot
+//! https://uefi.org/specs/ACPI/6.5/04_ACPI_Hardware_Specification.html#pm1-control-registers
+use core::arch::asm;
+use core::time::Duration;
+
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+use x86_64_custom::acpi::{AcpiError, Fadt, GenericAddress};
+
+use super::{acpi, physical_memory_offset};
+use crate::time::clock_source::{self, ClockSourceKind};
+
+/// Sleep state that turns the machine off.
+const SOFT_OFF: u8 = 5;
+
+/// PM1 control register: the SCI interrupt is enabled (the machine is in ACPI mode).
+const SCI_ENABLE: u16 = 1 << 0;
+
+/// PM1 control register: type of the sleep state entered when SLP_EN is set.
+const SLEEP_TYPE_SHIFT: u16 = 10;
+const SLEEP_TYPE_MASK: u16 = 0b111 << SLEEP_TYPE_SHIFT;
+
+/// PM1 control register: enters the sleep state.
+const SLEEP_ENABLE: u16 = 1 << 13;
+
+/// Time the firmware has to switch the machine to ACPI mode.
+const ACPI_MODE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time given to each shutdown or reset mechanism before trying the next one.
+const RESET_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Iterations spun instead of sleeping when the clock source does not advance (see [wait_until]).
+const SPIN_ITERATIONS: usize = 100_000_000;
+
+// 8042 keyboard controller
+const KEYBOARD_CONTROLLER_PORT: u16 = 0x64;
+const KEYBOARD_CONTROLLER_INPUT_FULL: u8 = 1 << 1;
+const KEYBOARD_CONTROLLER_RESET: u8 = 0xfe;
+
+/// Represents all the possible errors that can happen when turning the machine off.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PowerError {
+    /// The ACPI tables were not found.
+    NoAcpi,
+
+    /// The FADT or the DSDT could not be read.
+    Acpi(AcpiError),
+
+    /// The DSDT does not define the soft off state (`\_S5`) with constants.
+    NoSoftOffState,
+
+    /// The firmware did not switch the machine to ACPI mode.
+    AcpiModeTimeout,
+
+    /// The machine is still running after entering the soft off state.
+    StillRunning,
+}
+
+/// Turns the machine off. Only returns, with the reason, if it could not.
+pub fn shutdown() -> PowerError {
+    match enter_soft_off() {
+        Ok(()) => PowerError::StillRunning,
+        Err(error) => error,
+    }
+}
+
+/// Resets the machine.
+pub fn reboot() -> ! {
+    let reset = acpi::get()
+        .and_then(|acpi| acpi.fadt())
+        .and_then(Fadt::reset);
+    if let Some((register, value)) = reset {
+        write_reset_register(register, value);
+        wait(RESET_TIMEOUT);
+    }
+
+    pulse_keyboard_controller_reset();
+    wait(RESET_TIMEOUT);
+
+    triple_fault()
+}
+
+/// Disables the interrupts and halts the processor. Only an NMI wakes it up, and it halts again.
+pub fn halt() -> ! {
+    loop {
+        interrupts::disable();
+        x86_64::instructions::hlt();
+    }
+}
+
+/// Enters the soft off state. Returns if the machine is still running after [RESET_TIMEOUT].
+fn enter_soft_off() -> Result<(), PowerError> {
+    let acpi = acpi::get().ok_or(PowerError::NoAcpi)?;
+    let fadt = acpi
+        .fadt()
+        .ok_or(PowerError::Acpi(AcpiError::TableNotFound))?;
+    let (sleep_type_a, sleep_type_b) = acpi
+        .tables()
+        .dsdt()
+        .map_err(PowerError::Acpi)?
+        .sleep_types(SOFT_OFF)
+        .ok_or(PowerError::NoSoftOffState)?;
+    let (pm1a_port, pm1b_port) = fadt.pm1_control_ports();
+
+    enable_acpi_mode(fadt, pm1a_port)?;
+    unsafe {
+        enter_sleep_state(pm1a_port, sleep_type_a);
+        if let Some(port) = pm1b_port {
+            enter_sleep_state(port, sleep_type_b);
+        }
+    }
+    wait(RESET_TIMEOUT);
+
+    Ok(())
+}
+
+/// Switches the machine from legacy mode to ACPI mode, if it is not already in it. Until then the
+/// firmware owns the power management registers.
+///
+/// # Arguments
+/// * `fadt` - The FADT.
+/// * `pm1a_port` - I/O port of the PM1a control register.
+fn enable_acpi_mode(fadt: &Fadt, pm1a_port: u16) -> Result<(), PowerError> {
+    let mut pm1a = Port::<u16>::new(pm1a_port);
+    let mut acpi_mode = || unsafe { pm1a.read() } & SCI_ENABLE != 0;
+    let (smi_command_port, acpi_enable) = (fadt.smi_command_port, fadt.acpi_enable);
+    // Without a SMI command port the machine is always in ACPI mode
+    if acpi_mode() || smi_command_port == 0 || acpi_enable == 0 {
+        return Ok(());
+    }
+
+    unsafe { Port::<u8>::new(smi_command_port as u16).write(acpi_enable) };
+    if wait_until(ACPI_MODE_TIMEOUT, acpi_mode) {
+        Ok(())
+    } else {
+        Err(PowerError::AcpiModeTimeout)
+    }
+}
+
+/// Writes the sleep type and SLP_EN in a PM1 control register, keeping the rest of its bits.
+///
+/// # Arguments
+/// * `port` - I/O port of the PM1 control register.
+/// * `sleep_type` - SLP_TYP value of the sleep state.
+///
+/// # Safety
+/// The machine enters the sleep state.
+unsafe fn enter_sleep_state(port: u16, sleep_type: u8) {
+    let mut pm1 = Port::<u16>::new(port);
+    let value = pm1.read() & !SLEEP_TYPE_MASK;
+    let sleep_type = (u16::from(sleep_type) << SLEEP_TYPE_SHIFT) & SLEEP_TYPE_MASK;
+    pm1.write(value | sleep_type | SLEEP_ENABLE);
+}
+
+/// Writes the reset value in the ACPI reset register. Registers in the PCI configuration space
+/// are not supported.
+///
+/// # Arguments
+/// * `register` - The reset register.
+/// * `value` - Value that resets the machine.
+fn write_reset_register(register: GenericAddress, value: u8) {
+    match register.address_space {
+        GenericAddress::SYSTEM_IO => unsafe {
+            Port::<u8>::new(register.address as u16).write(value)
+        },
+        GenericAddress::SYSTEM_MEMORY => {
+            if let Some(physical_memory_offset) = physical_memory_offset() {
+                let address = physical_memory_offset + register.physical_address();
+                unsafe { address.as_mut_ptr::<u8>().write_volatile(value) };
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Asks the 8042 keyboard controller to pulse the reset line of the processor.
+fn pulse_keyboard_controller_reset() {
+    let mut controller = Port::<u8>::new(KEYBOARD_CONTROLLER_PORT);
+    // The command can not be written until the controller reads the previous one
+    wait_until(RESET_TIMEOUT, || unsafe {
+        controller.read() & KEYBOARD_CONTROLLER_INPUT_FULL == 0
+    });
+    unsafe { controller.write(KEYBOARD_CONTROLLER_RESET) };
+}
+
+/// Resets the processor with a triple fault: with an empty IDT, the breakpoint exception can not
+/// be delivered, neither the double fault that follows, and the processor shuts down and resets.
+fn triple_fault() -> ! {
+    // Limit and base of the IDT: a table without entries
+    let empty_idt = [0u16; 5];
+    unsafe {
+        interrupts::disable();
+        asm!("lidt [{}]", "int3", in(reg) &empty_idt);
+    }
+
+    halt()
+}
+
+/// Busy-waits until the condition holds or the timeout expires. Returns false on timeout.
+///
+/// The tick counter does not advance with the interrupts disabled, in that case the timeout is a
+/// fixed number of iterations instead.
+///
+/// # Arguments
+/// * `timeout` - Maximum time to wait.
+/// * `condition` - Condition checked on every iteration.
+fn wait_until(timeout: Duration, mut condition: impl FnMut() -> bool) -> bool {
+    let ticks_stopped =
+        clock_source::clock_source_kind() == ClockSourceKind::Ticks && !interrupts::are_enabled();
+    let start = clock_source::nanoseconds();
+    let timeout = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+
+    let mut iterations = 0;
+    while !condition() {
+        let expired = if ticks_stopped {
+            iterations >= SPIN_ITERATIONS
+        } else {
+            clock_source::nanoseconds().wrapping_sub(start) >= timeout
+        };
+        if expired {
+            return false;
+        }
+        iterations += 1;
+        core::hint::spin_loop();
+    }
+
+    true
+}
+
+/// Busy-waits for the given duration (see [wait_until]).
+///
+/// # Arguments
+/// * `duration` - Time to wait.
+fn wait(duration: Duration) {
+    wait_until(duration, || false);
+}
@@ -0,0 +1,287 @@
+//! Symmetric multiprocessing
+//!
+//! At boot only one processor runs, the bootstrap processor (BSP). The other ones, the application
+//! processors (APs), are listed in the ACPI MADT and wait to be started with the INIT-SIPI-SIPI
+//! sequence:
+//! - An INIT IPI resets the processor. We wait 10ms.
+//! - A startup IPI (SIPI) makes it run the [trampoline] in real mode. If the processor is not
+//!   online after 200µs, a second SIPI is sent, as the specification recommends.
+//!
+//! The APs are started one at a time, since all of them use the same trampoline. Every AP gets an
+//! index (the BSP is the zero), its own kernel stack, GDT, TSS and double fault stack, and its
+//! [per-CPU data](per_cpu). Then it loads the shared IDT, enables system calls, the FPU and its
+//! local APIC and halts waiting for interrupts. The scheduler only runs on the BSP yet, so the APs
+//! only serve IPIs.
+//!
+//! TLB shootdowns: when a page is unmapped, [tlb::flush] invalidates it in the current processor
+//! and calls [shootdown], which sends the `TlbShootdown` IPI to the other online processors and
+//! waits until every one of them invalidated the page too.
+//!
+//! For more info:
+//! https://wiki.osdev.org/Symmetric_Multiprocessing
+//! Intel 64 and IA-32 Architectures Software Developer's Manual, Volume 3, Section 8.4
+pub mod per_cpu;
+mod trampoline;
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use x86_64_custom::acpi::AcpiError;
+use x86_64_custom::cpuid;
+use x86_64_custom::interrupts::LocalApicInterruptIndex;
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::memory::tlb;
+
+use super::local_apic::{self, LOCAL_APIC};
+use super::{acpi, fpu, gdt, idt, physical_memory_offset, syscall, unmap_page};
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::time::clock_source;
+use trampoline::Trampoline;
+
+/// Maximum number of processors used, including the bootstrap one. The rest are left halted.
+pub const MAX_CPUS: usize = 8;
+
+/// Size of the kernel stack of every application processor.
+const STACK_SIZE: usize = 4096 * 4;
+
+/// Time waited after the INIT IPI.
+const INIT_DELAY: Duration = Duration::from_millis(10);
+
+/// Time waited for the processor after the first startup IPI.
+const FIRST_STARTUP_TIMEOUT: Duration = Duration::from_micros(200);
+
+/// Time waited for the processor after the second startup IPI.
+const SECOND_STARTUP_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Represents all the possible errors that can happen when starting the application processors.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SmpError {
+    /// The memory and the architecture are not initialized yet.
+    NotInitialized,
+
+    /// The processor does not have a local APIC, IPIs can not be sent.
+    NoLocalApic,
+
+    /// The MADT could not be read.
+    Acpi(AcpiError),
+
+    /// There is no free page in the first MiB for the trampoline.
+    NoLowMemory,
+
+    /// There are no free frames left for the page tables.
+    OutOfMemory,
+
+    /// The kernel PML4 can not be loaded from protected mode.
+    PageTablesAboveFourGiB,
+
+    /// The trampoline page is already mapped to another frame.
+    TrampolineAlreadyMapped,
+
+    /// A processor did not answer the startup IPIs. It could start later using the trampoline,
+    /// so no more processors are started.
+    StartupTimeout { apic_id: u8 },
+}
+
+/// A kernel stack of an application processor.
+#[repr(C, align(16))]
+struct Stack([u8; STACK_SIZE]);
+
+/// Kernel stacks of the application processors.
+struct Stacks(UnsafeCell<[Stack; MAX_CPUS - 1]>);
+
+unsafe impl Sync for Stacks {}
+
+static STACKS: Stacks = Stacks(UnsafeCell::new(
+    [const { Stack([0; STACK_SIZE]) }; MAX_CPUS - 1],
+));
+
+/// Serializes the TLB shootdowns. It is only held with `try_lock`, see [shootdown].
+static SHOOTDOWN_LOCK: IrqSafeMutex<()> = IrqSafeMutex::new(());
+
+/// Page of the TLB shootdown in progress.
+static SHOOTDOWN_ADDRESS: AtomicU64 = AtomicU64::new(0);
+
+/// Processors that did not invalidate the page of the TLB shootdown in progress yet.
+static SHOOTDOWN_REMAINING: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets up the per-CPU data of the bootstrap processor. It must run right after its GDT is loaded.
+pub(crate) fn initialize_bootstrap_processor() {
+    per_cpu::initialize(0, cpuid::initial_apic_id()).set_online();
+}
+
+/// Starts the application processors listed in the MADT, up to [MAX_CPUS] processors in total.
+/// Processors with an APIC ID that does not fit in 8 bits (x2APIC only) are skipped.
+///
+/// Must be called on the bootstrap processor, after the memory is initialized.
+pub fn initialize() -> Result<(), SmpError> {
+    let physical_memory_offset = physical_memory_offset().ok_or(SmpError::NotInitialized)?;
+    if !LOCAL_APIC.lock().is_initialized() {
+        return Err(SmpError::NoLocalApic);
+    }
+    let madt = acpi::initialize(physical_memory_offset, None)
+        .map_err(SmpError::Acpi)?
+        .madt()
+        .ok_or(SmpError::Acpi(AcpiError::TableNotFound))?;
+
+    let bootstrap_apic_id = per_cpu::current().map_or(0, |cpu| u32::from(cpu.apic_id()));
+    let mut application_processors = madt
+        .processors()
+        .filter(|&apic_id| apic_id != bootstrap_apic_id)
+        .filter_map(|apic_id| u8::try_from(apic_id).ok())
+        .take(MAX_CPUS - 1)
+        .peekable();
+    if application_processors.peek().is_none() {
+        return Ok(());
+    }
+
+    let trampoline = Trampoline::install(physical_memory_offset)?;
+    unsafe { tlb::set_shootdown_handler(shootdown) };
+    for (index, apic_id) in (1..).zip(application_processors) {
+        unsafe { start_processor(&trampoline, index, apic_id)? };
+    }
+
+    // Nothing runs the trampoline anymore
+    if let Some(page) = trampoline.identity_mapping() {
+        let _ = unsafe { unmap_page(page) };
+    }
+
+    Ok(())
+}
+
+/// Starts an application processor with the INIT-SIPI-SIPI sequence and waits until it is online.
+///
+/// # Arguments
+/// * `trampoline` - Installed trampoline.
+/// * `index` - Index given to the processor.
+/// * `apic_id` - Local APIC ID of the processor.
+///
+/// # Safety
+/// No other processor can be running the trampoline.
+unsafe fn start_processor(
+    trampoline: &Trampoline,
+    index: usize,
+    apic_id: u8,
+) -> Result<(), SmpError> {
+    let stack_bottom = STACKS.0.get().cast::<Stack>().wrapping_add(index - 1) as u64;
+    trampoline.prepare(
+        VirtualMemoryAddress::new(stack_bottom + STACK_SIZE as u64),
+        application_processor_entry,
+        index,
+    );
+
+    LOCAL_APIC.lock().send_init(apic_id);
+    clock_source::precise_sleep(INIT_DELAY);
+    for timeout in [FIRST_STARTUP_TIMEOUT, SECOND_STARTUP_TIMEOUT] {
+        LOCAL_APIC.lock().send_startup(apic_id, trampoline.vector());
+        if wait_online(index, timeout) {
+            return Ok(());
+        }
+    }
+
+    Err(SmpError::StartupTimeout { apic_id })
+}
+
+/// Waits until a processor is online. Returns false if it is not online after the timeout.
+///
+/// # Arguments
+/// * `index` - Index of the processor.
+/// * `timeout` - Maximum time to wait.
+fn wait_online(index: usize, timeout: Duration) -> bool {
+    let start = clock_source::nanoseconds();
+    let timeout = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+    while clock_source::nanoseconds().wrapping_sub(start) < timeout {
+        if per_cpu::cpu(index).is_some_and(|cpu| cpu.is_online()) {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+
+    false
+}
+
+/// Kernel entry point of the application processors, jumped to by the trampoline.
+///
+/// # Arguments
+/// * `index` - Index of the processor.
+extern "C" fn application_processor_entry(index: usize) -> ! {
+    gdt::load_gdt(index);
+    let cpu = per_cpu::initialize(index, cpuid::initial_apic_id());
+    idt::load_idt();
+    syscall::initialize();
+    fpu::initialize();
+    local_apic::initialize_application_processor();
+    cpu.set_online();
+
+    loop {
+        x86_64::instructions::interrupts::enable_and_hlt();
+    }
+}
+
+/// Invalidates a page in the TLB of the other online processors. Registered as the shootdown
+/// handler of [tlb::flush].
+///
+/// # Arguments
+/// * `address` - Address whose translation must be invalidated.
+fn shootdown(address: VirtualMemoryAddress) {
+    // The handler is registered after the per-CPU data of the bootstrap processor is set up
+    let current = per_cpu::current().expect("TLB shootdown without per-CPU data");
+
+    // Other processor could be waiting for us to take part in its shootdown, and we could have
+    // the interrupts disabled, so we take part while waiting for the lock
+    let _guard = loop {
+        match SHOOTDOWN_LOCK.try_lock() {
+            Ok(guard) => break guard,
+            Err(_) => {
+                acknowledge_shootdown(current);
+                core::hint::spin_loop();
+            }
+        }
+    };
+
+    // The counter must be set before any processor sees its flag, or it could be decremented
+    // before being set. The targets are kept, since a processor could come online meanwhile.
+    let mut targets = [None; MAX_CPUS];
+    let mut remaining = 0;
+    for cpu in per_cpu::online_cpus().filter(|cpu| cpu.index() != current.index()) {
+        targets[remaining] = Some(cpu);
+        remaining += 1;
+    }
+    if remaining == 0 {
+        return;
+    }
+
+    SHOOTDOWN_ADDRESS.store(address.as_u64(), Ordering::Relaxed);
+    SHOOTDOWN_REMAINING.store(remaining, Ordering::Release);
+    for cpu in targets.iter().flatten() {
+        cpu.tlb_shootdown_pending().store(true, Ordering::Release);
+    }
+    unsafe {
+        LOCAL_APIC
+            .lock()
+            .broadcast_ipi(LocalApicInterruptIndex::TlbShootdown.as_u8())
+    };
+    while SHOOTDOWN_REMAINING.load(Ordering::Acquire) != 0 {
+        core::hint::spin_loop();
+    }
+}
+
+/// Invalidates the page of the TLB shootdown in progress, if the processor has to.
+///
+/// # Arguments
+/// * `cpu` - Per-CPU data of the current processor.
+fn acknowledge_shootdown(cpu: &per_cpu::PerCpu) {
+    if cpu.tlb_shootdown_pending().swap(false, Ordering::AcqRel) {
+        tlb::flush_local(VirtualMemoryAddress::new(
+            SHOOTDOWN_ADDRESS.load(Ordering::Relaxed),
+        ));
+        SHOOTDOWN_REMAINING.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// TLB shootdown IPI handler.
+pub(crate) fn tlb_shootdown_handler() {
+    // Only the online processors get the IPI, and they set up their per-CPU data before
+    let cpu = per_cpu::current().expect("TLB shootdown IPI without per-CPU data");
+    acknowledge_shootdown(cpu);
+}
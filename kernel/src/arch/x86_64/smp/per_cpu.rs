@@ -0,0 +1,185 @@
+//! Per-CPU data
+//!
+//! Every processor has a block of data that describes it, and the GS base of every processor
+//! points to its own block. This way the block of the processor running the code is found without
+//! knowing which processor it is: the first field of the block is its own address, read with
+//! `gs:0`.
+//!
+//! User code can change its GS base, so the kernel keeps two of them: while the kernel runs, the
+//! per-CPU block is in IA32_GS_BASE; while user code runs, it is in IA32_KERNEL_GS_BASE and the
+//! user one in IA32_GS_BASE. Every entry point from user mode (system calls and trap handlers)
+//! exchanges them with `swapgs`, and swaps them back before returning to user mode.
+use core::arch::asm;
+use core::mem::offset_of;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use x86_64_custom::registers::msr::Msr;
+
+use super::MAX_CPUS;
+use crate::synchronization::once::Once;
+
+/// Data of a processor.
+#[repr(C)]
+pub struct PerCpu {
+    /// Address of the block. It must be the first field, see [current].
+    address: AtomicU64,
+
+    /// Index of the processor, the bootstrap processor is the zero.
+    index: usize,
+
+    /// Local APIC ID of the processor.
+    apic_id: u8,
+
+    /// Set when the processor finished its initialization and can serve interrupts.
+    online: AtomicBool,
+
+    /// Set when the processor must invalidate the page of the TLB shootdown in progress.
+    tlb_shootdown_pending: AtomicBool,
+
+    /// Top of the kernel stack of the thread running in the processor, `syscall` switches to it.
+    kernel_stack_top: AtomicU64,
+
+    /// Scratch space for the user stack pointer while the `syscall` entry switches stacks.
+    user_stack_pointer: AtomicU64,
+
+    /// Number of spinlocks held by the code running in the processor. The running thread is not
+    /// preempted while it is not zero.
+    preemption_disabled: AtomicUsize,
+}
+
+/// Offset of the kernel stack top in the block, for the `syscall` entry.
+pub(crate) const KERNEL_STACK_TOP_OFFSET: usize = offset_of!(PerCpu, kernel_stack_top);
+
+/// Offset of the user stack pointer scratch space in the block, for the `syscall` entry.
+pub(crate) const USER_STACK_POINTER_OFFSET: usize = offset_of!(PerCpu, user_stack_pointer);
+
+impl PerCpu {
+    /// Returns the index of the processor.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the local APIC ID of the processor.
+    pub fn apic_id(&self) -> u8 {
+        self.apic_id
+    }
+
+    /// Returns true if the processor finished its initialization.
+    pub fn is_online(&self) -> bool {
+        self.online.load(Ordering::Acquire)
+    }
+
+    /// Marks the processor as initialized.
+    pub(super) fn set_online(&self) {
+        self.online.store(true, Ordering::Release);
+    }
+
+    /// Sets the kernel stack the `syscall` entry switches to.
+    ///
+    /// # Arguments
+    /// * `stack_top` - Top address of the kernel stack of the current thread.
+    pub(crate) fn set_kernel_stack_top(&self, stack_top: u64) {
+        self.kernel_stack_top.store(stack_top, Ordering::Relaxed);
+    }
+
+    /// Keeps the running thread from being preempted until the matching [enable_preemption].
+    /// Calls can be nested.
+    ///
+    /// [enable_preemption]: PerCpu::enable_preemption
+    pub(crate) fn disable_preemption(&self) {
+        // Sequentially consistent, so the compiler does not move it after the lock is taken
+        self.preemption_disabled.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Undoes a [disable_preemption](PerCpu::disable_preemption).
+    pub(crate) fn enable_preemption(&self) {
+        self.preemption_disabled.fetch_sub(1, Ordering::SeqCst);
+    }
+
+    /// Returns true if the running thread can be preempted.
+    pub fn is_preemptible(&self) -> bool {
+        self.preemption_disabled.load(Ordering::SeqCst) == 0
+    }
+
+    /// Returns the flag set when the processor must take part in the TLB shootdown in progress.
+    pub(super) fn tlb_shootdown_pending(&self) -> &AtomicBool {
+        &self.tlb_shootdown_pending
+    }
+}
+
+/// Per-CPU data of every processor, indexed by the processor index.
+static CPUS: [Once<PerCpu>; MAX_CPUS] = [const { Once::new() }; MAX_CPUS];
+
+/// Set when the per-CPU data of the bootstrap processor is ready. Until then, GS is not valid.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Creates the per-CPU data of the current processor and points its GS base to it.
+///
+/// # Arguments
+/// * `index` - Index of the processor.
+/// * `apic_id` - Local APIC ID of the processor.
+pub(crate) fn initialize(index: usize, apic_id: u8) -> &'static PerCpu {
+    let cpu = CPUS[index].call_once(|| PerCpu {
+        address: AtomicU64::new(0),
+        index,
+        apic_id,
+        online: AtomicBool::new(false),
+        tlb_shootdown_pending: AtomicBool::new(false),
+        kernel_stack_top: AtomicU64::new(0),
+        user_stack_pointer: AtomicU64::new(0),
+        preemption_disabled: AtomicUsize::new(0),
+    });
+
+    let address = cpu as *const PerCpu as u64;
+    cpu.address.store(address, Ordering::Relaxed);
+
+    // The kernel one is loaded, user code starts with a zero GS base
+    let (mut gs_base, mut kernel_gs_base) = (Msr::IA32_GS_BASE, Msr::IA32_KERNEL_GS_BASE);
+    unsafe {
+        gs_base.write(address);
+        kernel_gs_base.write(0);
+    }
+
+    INITIALIZED.store(true, Ordering::Release);
+    cpu
+}
+
+/// Returns the data of the processor running the code, or `None` if the per-CPU data is not set
+/// up yet (only the bootstrap processor runs then).
+pub fn current() -> Option<&'static PerCpu> {
+    if !INITIALIZED.load(Ordering::Acquire) {
+        return None;
+    }
+
+    let address: u64;
+    // Safety: the kernel GS base points to the block of the processor, whose first field is its
+    // address
+    unsafe {
+        asm!(
+            "mov {}, gs:[0]",
+            out(reg) address,
+            options(nostack, readonly, preserves_flags)
+        );
+        Some(&*(address as *const PerCpu))
+    }
+}
+
+/// Returns the data of a processor, if it was started.
+///
+/// # Arguments
+/// * `index` - Index of the processor.
+pub fn cpu(index: usize) -> Option<&'static PerCpu> {
+    CPUS.get(index).and_then(Once::get)
+}
+
+/// Returns an iterator over the data of the processors that are online.
+pub fn online_cpus() -> impl Iterator<Item = &'static PerCpu> {
+    CPUS.iter()
+        .filter_map(Once::get)
+        .filter(|cpu| cpu.is_online())
+}
+
+/// Returns the number of processors that are online.
+pub fn online_count() -> usize {
+    online_cpus().count()
+}
@@ -0,0 +1,297 @@
+//! Start up code of the application processors
+//!
+//! A processor started with a startup IPI runs in real mode at the beginning of the page given in
+//! the IPI, which must be in the first MiB. The trampoline is assembled in the kernel but never
+//! run from there: it is copied to a free page of low memory. From there it:
+//! - Loads a temporary GDT and switches to protected mode.
+//! - Loads the CR4, CR3 and EFER values of the bootstrap processor, which enables PAE, long mode
+//!   and the kernel page tables.
+//! - Enables paging with the CR0 value of the bootstrap processor, and far jumps to a 64 bit code
+//!   segment.
+//! - Switches to the stack of the processor and jumps to its kernel entry point.
+//!
+//! The instructions after enabling paging are fetched from the physical address of the page, so
+//! the page must be identity mapped. CR3 is loaded in protected mode, so the kernel PML4 must be
+//! in the first 4GiB.
+//!
+//! Since the page can be anywhere in low memory, the trampoline computes its own absolute
+//! addresses from CS and patches them in before using them. The bootstrap processor fills the
+//! [TrampolineData] at its end before starting every processor.
+use core::mem::{offset_of, size_of};
+use core::ptr::{addr_of, addr_of_mut, write_volatile};
+
+use x86_64_custom::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+use x86_64_custom::memory::paging::page_table::{
+    PageTable, PageTableEntry, PageTableEntryFlags, PageTableLevel,
+};
+use x86_64_custom::memory::Translator;
+use x86_64_custom::registers::control::{Cr0, Cr3, Cr4};
+use x86_64_custom::registers::msr::Msr;
+
+use super::SmpError;
+use crate::memory::address_space::PAGE_SIZE;
+use crate::memory::frame_allocator::allocate_frame;
+
+/// Long Mode Active bit of the EFER MSR. It is set by the processor, not written.
+const EFER_LONG_MODE_ACTIVE: u64 = 1 << 10;
+
+/// Values the trampoline loads, at its end.
+#[repr(C)]
+struct TrampolineData {
+    cr0: u64,
+    cr3: u64,
+    cr4: u64,
+    efer: u64,
+
+    /// Top of the kernel stack of the processor.
+    stack_top: u64,
+
+    /// Kernel entry point of the processor.
+    entry: u64,
+
+    /// Value passed to the entry point in RDI.
+    argument: u64,
+}
+
+core::arch::global_asm!(
+    r#"
+    .pushsection .text.smp_trampoline, "ax"
+    .balign 16
+    .global smp_trampoline_start
+    .global smp_trampoline_data
+    .global smp_trampoline_end
+
+    .code16
+smp_trampoline_start:
+    cli
+    cld
+    // EBX = physical address of the trampoline, DS = CS
+    xorl %ebx, %ebx
+    movw %cs, %bx
+    movw %bx, %ds
+    shll $4, %ebx
+
+    // Patch the absolute addresses
+    leal (smp_trampoline_gdt - smp_trampoline_start)(%ebx), %eax
+    movl %eax, (smp_trampoline_gdt_pointer - smp_trampoline_start + 2)
+    leal (smp_trampoline_protected_mode - smp_trampoline_start)(%ebx), %eax
+    movl %eax, (smp_trampoline_protected_mode_pointer - smp_trampoline_start)
+    leal (smp_trampoline_long_mode - smp_trampoline_start)(%ebx), %eax
+    movl %eax, (smp_trampoline_long_mode_pointer - smp_trampoline_start)
+
+    lgdtl (smp_trampoline_gdt_pointer - smp_trampoline_start)
+    movl %cr0, %eax
+    orl $1, %eax
+    movl %eax, %cr0
+    ljmpl *(smp_trampoline_protected_mode_pointer - smp_trampoline_start)
+
+    .code32
+smp_trampoline_protected_mode:
+    movw $0x10, %ax
+    movw %ax, %ds
+    movw %ax, %es
+    movw %ax, %ss
+
+    movl (smp_trampoline_data - smp_trampoline_start + {cr4})(%ebx), %eax
+    movl %eax, %cr4
+    movl (smp_trampoline_data - smp_trampoline_start + {cr3})(%ebx), %eax
+    movl %eax, %cr3
+    movl $0xc0000080, %ecx
+    movl (smp_trampoline_data - smp_trampoline_start + {efer})(%ebx), %eax
+    movl (smp_trampoline_data - smp_trampoline_start + {efer} + 4)(%ebx), %edx
+    wrmsr
+    // Enabling paging with long mode enabled activates it (compatibility mode)
+    movl (smp_trampoline_data - smp_trampoline_start + {cr0})(%ebx), %eax
+    movl %eax, %cr0
+    ljmpl *(smp_trampoline_long_mode_pointer - smp_trampoline_start)(%ebx)
+
+    .code64
+smp_trampoline_long_mode:
+    // The upper half of the registers is undefined after the mode switch
+    movl %ebx, %ebx
+    movq (smp_trampoline_data - smp_trampoline_start + {stack_top})(%rbx), %rsp
+    movq (smp_trampoline_data - smp_trampoline_start + {argument})(%rbx), %rdi
+    xorl %ebp, %ebp
+    // Fake return address, so the stack is aligned as if the entry point was called
+    pushq $0
+    jmpq *(smp_trampoline_data - smp_trampoline_start + {entry})(%rbx)
+
+    .balign 8
+smp_trampoline_gdt:
+    .quad 0
+    // 32 bit code
+    .quad 0x00cf9a000000ffff
+    // 32 bit data
+    .quad 0x00cf92000000ffff
+    // 64 bit code
+    .quad 0x00af9a000000ffff
+smp_trampoline_gdt_pointer:
+    .word smp_trampoline_gdt_pointer - smp_trampoline_gdt - 1
+    .long 0
+smp_trampoline_protected_mode_pointer:
+    .long 0
+    .word 0x08
+smp_trampoline_long_mode_pointer:
+    .long 0
+    .word 0x18
+
+    .balign 8
+smp_trampoline_data:
+    .fill {data_size}, 1, 0
+smp_trampoline_end:
+    .popsection
+    "#,
+    cr0 = const offset_of!(TrampolineData, cr0),
+    cr3 = const offset_of!(TrampolineData, cr3),
+    cr4 = const offset_of!(TrampolineData, cr4),
+    efer = const offset_of!(TrampolineData, efer),
+    stack_top = const offset_of!(TrampolineData, stack_top),
+    entry = const offset_of!(TrampolineData, entry),
+    argument = const offset_of!(TrampolineData, argument),
+    data_size = const size_of::<TrampolineData>(),
+    options(att_syntax)
+);
+
+extern "C" {
+    static smp_trampoline_start: u8;
+    static smp_trampoline_data: u8;
+    static smp_trampoline_end: u8;
+}
+
+/// The trampoline copied to a page of low memory.
+pub(super) struct Trampoline {
+    /// Physical address of the page.
+    page: PhysicalMemoryAddress,
+
+    /// Data of the copy, through the physical memory mapping.
+    data: *mut TrampolineData,
+
+    /// True if the identity mapping of the page was added by us.
+    identity_mapped: bool,
+}
+
+impl Trampoline {
+    /// Copies the trampoline to a free page of low memory, identity maps it and fills the values
+    /// of the control registers with the ones of the current processor.
+    ///
+    /// # Arguments
+    /// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+    pub(super) fn install(physical_memory_offset: VirtualMemoryAddress) -> Result<Self, SmpError> {
+        let level_4_table = Cr3::read().as_u64();
+        if level_4_table > u64::from(u32::MAX) {
+            return Err(SmpError::PageTablesAboveFourGiB);
+        }
+
+        // The frames are handed out in increasing order, so the first ones are in low memory
+        let page = allocate_frame().ok_or(SmpError::NoLowMemory)?;
+        if page.as_u64() >= 0x10_0000 {
+            return Err(SmpError::NoLowMemory);
+        }
+        let identity_mapped = unsafe { identity_map(page, physical_memory_offset)? };
+
+        let (start, data, end) = (
+            addr_of!(smp_trampoline_start) as usize,
+            addr_of!(smp_trampoline_data) as usize,
+            addr_of!(smp_trampoline_end) as usize,
+        );
+        let destination = (physical_memory_offset + page).as_mut_ptr::<u8>();
+        unsafe { core::ptr::copy_nonoverlapping(start as *const u8, destination, end - start) };
+
+        let trampoline = Self {
+            page,
+            data: destination.wrapping_add(data - start).cast(),
+            identity_mapped,
+        };
+        let efer = Msr::IA32_EFER;
+        unsafe {
+            write_volatile(addr_of_mut!((*trampoline.data).cr0), Cr0::read());
+            write_volatile(addr_of_mut!((*trampoline.data).cr3), level_4_table);
+            write_volatile(
+                addr_of_mut!((*trampoline.data).cr4),
+                Cr4::read() & !Cr4::PCID_ENABLE,
+            );
+            write_volatile(
+                addr_of_mut!((*trampoline.data).efer),
+                efer.read() & !EFER_LONG_MODE_ACTIVE,
+            );
+        }
+
+        Ok(trampoline)
+    }
+
+    /// Returns the page number given in the startup IPI.
+    pub(super) fn vector(&self) -> u8 {
+        (self.page.as_u64() / PAGE_SIZE) as u8
+    }
+
+    /// Returns the start address of the page, if its identity mapping was added by
+    /// [install](Self::install) and must be removed.
+    pub(super) fn identity_mapping(&self) -> Option<VirtualMemoryAddress> {
+        self.identity_mapped
+            .then(|| VirtualMemoryAddress::new(self.page.as_u64()))
+    }
+
+    /// Sets the stack and the entry point of the next processor started.
+    ///
+    /// # Arguments
+    /// * `stack_top` - Top address of the kernel stack of the processor.
+    /// * `entry` - Entry point of the processor.
+    /// * `argument` - Value passed to the entry point.
+    ///
+    /// # Safety
+    /// No processor can be running the trampoline.
+    pub(super) unsafe fn prepare(
+        &self,
+        stack_top: VirtualMemoryAddress,
+        entry: extern "C" fn(usize) -> !,
+        argument: usize,
+    ) {
+        write_volatile(addr_of_mut!((*self.data).stack_top), stack_top.as_u64());
+        write_volatile(addr_of_mut!((*self.data).entry), entry as usize as u64);
+        write_volatile(addr_of_mut!((*self.data).argument), argument as u64);
+    }
+}
+
+/// Maps a page at its own physical address in the current address space, allocating the missing
+/// page tables. Returns false if it was already identity mapped.
+///
+/// # Arguments
+/// * `page` - Physical address of the page.
+/// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+///
+/// # Safety
+/// The page tables must be mapped at `physical_memory_offset`.
+unsafe fn identity_map(
+    page: PhysicalMemoryAddress,
+    physical_memory_offset: VirtualMemoryAddress,
+) -> Result<bool, SmpError> {
+    let address = VirtualMemoryAddress::new(page.as_u64());
+    match Translator::new(physical_memory_offset).translate_address(address) {
+        Some(translated) if translated.as_u64() == page.as_u64() => return Ok(false),
+        Some(_) => return Err(SmpError::TrampolineAlreadyMapped),
+        None => {}
+    }
+
+    let flags = PageTableEntryFlags::PRESENT | PageTableEntryFlags::WRITABLE;
+    let mut table: &mut PageTable = &mut *(physical_memory_offset + Cr3::read()).as_mut_ptr();
+    for level in [
+        PageTableLevel::Level4,
+        PageTableLevel::Level3,
+        PageTableLevel::Level2,
+    ] {
+        let entry = &mut table[address.get_page_table_index(level)];
+        if !entry.is_present() {
+            let frame = allocate_frame().ok_or(SmpError::OutOfMemory)?;
+            core::ptr::write_bytes(
+                (physical_memory_offset + frame).as_mut_ptr::<u8>(),
+                0,
+                PAGE_SIZE as usize,
+            );
+            *entry = PageTableEntry::new(flags, frame);
+        }
+        table = &mut *(physical_memory_offset + entry.address()).as_mut_ptr();
+    }
+    table[address.get_page_table_index(PageTableLevel::Level1)] = PageTableEntry::new(flags, page);
+
+    Ok(true)
+}
@@ -0,0 +1,194 @@
+//! System call entry points
+//!
+//! User code enters the kernel with the `syscall` instruction or, for legacy code, with the
+//! `int 0x80` software interrupt. Both use the same registers:
+//! - RAX: system call number. The result is returned in it.
+//! - RDI, RSI, RDX, R10, R8, R9: arguments.
+//!
+//! Every other register is preserved, except RCX and R11 when using `syscall` (the instruction
+//! itself overwrites them with the return address and RFLAGS).
+//!
+//! `syscall` does not switch the stack, so the entry point switches to the kernel stack of the
+//! current thread by itself. The stack top and the scratch space for the user stack pointer are
+//! in the per-CPU data, reached through GS. It runs with the interrupts disabled (see
+//! `SYSCALL_RFLAGS_MASK`) until the user stack pointer is saved in the kernel stack. Both entry
+//! points switch to the kernel GS base with `swapgs` (see [per_cpu](super::smp::per_cpu)).
+//!
+//! For more info:
+//! https://wiki.osdev.org/SYSENTER
+use core::arch::naked_asm;
+
+use x86_64_custom::idt::InterruptDescriptorTable;
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::privilege::PrivilegeLevel;
+use x86_64_custom::registers::msr::Msr;
+
+use super::gdt::selectors;
+use super::smp::per_cpu;
+use crate::syscall;
+
+/// Interrupt vector of the legacy `int 0x80` system call gate.
+pub const SYSCALL_VECTOR: usize = 0x80;
+
+/// System Call Extensions bit of the EFER MSR.
+const EFER_SYSCALL_ENABLE: u64 = 1;
+
+/// RFLAGS bits cleared when entering the kernel with `syscall`: trap (8), interrupts (9) and
+/// direction (10).
+const SYSCALL_RFLAGS_MASK: u64 = 0x700;
+
+/// Registers saved by the entry points. The order matches the pushes, the first field is the
+/// last one pushed.
+#[repr(C)]
+struct SyscallFrame {
+    rax: u64,
+    rdi: u64,
+    rsi: u64,
+    rdx: u64,
+    r10: u64,
+    r8: u64,
+    r9: u64,
+}
+
+/// Enables the `syscall` instruction: sets the segments it loads, its entry point and the RFLAGS
+/// bits it clears.
+pub(crate) fn initialize() {
+    let selectors = selectors();
+    // `syscall` loads CS from STAR[32..48] and SS from the next entry. `sysret` loads SS from the
+    // entry after STAR[48..64] and CS from the one after that.
+    debug_assert!(*selectors.ds == *selectors.cs + 8);
+    debug_assert!(*selectors.user_cs == *selectors.user_ds + 8);
+    let star = (u64::from(*selectors.user_ds - 8) << 48) | (u64::from(*selectors.cs) << 32);
+
+    let (mut efer, mut star_msr, mut lstar, mut fmask) = (
+        Msr::IA32_EFER,
+        Msr::IA32_STAR,
+        Msr::IA32_LSTAR,
+        Msr::IA32_FMASK,
+    );
+    unsafe {
+        efer.write(efer.read() | EFER_SYSCALL_ENABLE);
+        star_msr.write(star);
+        lstar.write(syscall_entry as unsafe extern "C" fn() as usize as u64);
+        fmask.write(SYSCALL_RFLAGS_MASK);
+    }
+}
+
+/// Installs the `int 0x80` gate. User code is allowed to use it.
+///
+/// # Arguments
+/// * `idt` - Interrupt descriptor table being built.
+pub(crate) fn install_interrupt_gate(idt: &mut InterruptDescriptorTable) {
+    let address =
+        VirtualMemoryAddress::new(interrupt_entry as unsafe extern "C" fn() as usize as u64);
+    unsafe { idt[SYSCALL_VECTOR].set_handler_address(address) }
+        .set_privilege_level(PrivilegeLevel::Ring3);
+}
+
+/// Sets the kernel stack used by the `syscall` entry point.
+///
+/// # Arguments
+/// * `stack_top` - Top address of the kernel stack of the current thread.
+pub(super) fn set_kernel_stack(stack_top: VirtualMemoryAddress) {
+    // No user code runs before the per-CPU data is set up
+    if let Some(cpu) = per_cpu::current() {
+        cpu.set_kernel_stack_top(stack_top.as_u64());
+    }
+}
+
+/// Dispatches the system call saved in `frame` and stores its result in RAX. Called by the entry
+/// points with the interrupts disabled; they are enabled while the system call runs.
+extern "C" fn handle_syscall(frame: &mut SyscallFrame) {
+    x86_64::instructions::interrupts::enable();
+    let arguments = [
+        frame.rdi, frame.rsi, frame.rdx, frame.r10, frame.r8, frame.r9,
+    ];
+    frame.rax = syscall::dispatch(frame.rax, arguments);
+    x86_64::instructions::interrupts::disable();
+}
+
+/// Entry point of the `syscall` instruction. RCX holds the return address and R11 the user
+/// RFLAGS.
+///
+/// `sysret` does not check RCX before dropping to ring 3; the return address is always canonical
+/// because nothing is mapped for user code in the last page of the lower half (see
+/// [USER_SPACE_END](crate::memory::address_space::USER_SPACE_END)).
+#[unsafe(naked)]
+unsafe extern "C" fn syscall_entry() {
+    naked_asm!(
+        // `syscall` only comes from user mode
+        "swapgs",
+        "mov gs:[{user_stack_pointer}], rsp",
+        "mov rsp, gs:[{kernel_stack_top}]",
+        "push qword ptr gs:[{user_stack_pointer}]",
+        "push r11",
+        "push rcx",
+        // SyscallFrame
+        "push r9",
+        "push r8",
+        "push r10",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push rax",
+        "mov rdi, rsp",
+        "call {handle_syscall}",
+        "pop rax",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop r10",
+        "pop r8",
+        "pop r9",
+        "pop rcx",
+        "pop r11",
+        "pop rsp",
+        "swapgs",
+        "sysretq",
+        user_stack_pointer = const per_cpu::USER_STACK_POINTER_OFFSET,
+        kernel_stack_top = const per_cpu::KERNEL_STACK_TOP_OFFSET,
+        handle_syscall = sym handle_syscall,
+    )
+}
+
+/// Entry point of the `int 0x80` gate. The CPU already switched to the kernel stack and pushed
+/// the interrupt frame.
+#[unsafe(naked)]
+unsafe extern "C" fn interrupt_entry() {
+    naked_asm!(
+        // Code segment of the caller, after RIP
+        "test qword ptr [rsp + 8], 3",
+        "jz 2f",
+        "swapgs",
+        "2:",
+        // The handler can clobber them, but `int` must preserve them
+        "push rcx",
+        "push r11",
+        // SyscallFrame
+        "push r9",
+        "push r8",
+        "push r10",
+        "push rdx",
+        "push rsi",
+        "push rdi",
+        "push rax",
+        "mov rdi, rsp",
+        "cld",
+        "call {handle_syscall}",
+        "pop rax",
+        "pop rdi",
+        "pop rsi",
+        "pop rdx",
+        "pop r10",
+        "pop r8",
+        "pop r9",
+        "pop r11",
+        "pop rcx",
+        "test qword ptr [rsp + 8], 3",
+        "jz 3f",
+        "swapgs",
+        "3:",
+        "iretq",
+        handle_syscall = sym handle_syscall,
+    )
+}
@@ -0,0 +1,64 @@
+//! Jump to user mode (ring 3)
+//!
+//! There is no instruction to "call" less privileged code. Instead, we build the frame that the
+//! CPU pushes when an interrupt arrives while running in ring 3 and return from that fake
+//! interrupt with `iretq`: the CPU loads the user segments, stack and instruction pointer from the
+//! frame and drops the privilege level.
+//!
+//! Once in user mode, the code goes back to the kernel through interrupts (and exceptions), that
+//! switch to the kernel stack stored in the TSS (see [set_kernel_stack]). The kernel GS base is
+//! swapped out before, as every return to user mode does (see
+//! [per_cpu](super::smp::per_cpu)).
+//!
+//! More info:
+//! https://wiki.osdev.org/Getting_to_Ring_3
+//!
+//! [set_kernel_stack]: super::gdt::set_kernel_stack
+use core::arch::asm;
+
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+
+use super::gdt::selectors;
+
+/// RFLAGS of the user code: interrupts enabled (bit 9) and the reserved bit 1 set.
+const USER_RFLAGS: u64 = 0x202;
+
+/// Jumps to `entry` in user mode, with the stack pointer at `stack_top`. It never returns: the
+/// kernel stack of the current thread is reused by the interrupts that arrive while running the
+/// user code.
+///
+/// # Arguments
+/// * `entry` - Address of the first user instruction.
+/// * `stack_top` - Top address of the user stack.
+///
+/// # Safety
+/// The caller must be sure that the code and the stack are mapped as user accessible, and that
+/// the kernel stack of the current thread is set in the TSS.
+pub unsafe fn enter_user_mode(entry: VirtualMemoryAddress, stack_top: VirtualMemoryAddress) -> ! {
+    let selectors = selectors();
+    let user_cs = u64::from(*selectors.user_cs);
+    let user_ds = u64::from(*selectors.user_ds);
+
+    asm!(
+        // The data segments are ignored in 64 bit mode, but they are loaded to not leak the kernel
+        // ones to user mode
+        "mov ds, {ds:x}",
+        "mov es, {ds:x}",
+        // An interrupt between `swapgs` and `iretq` would run with the user GS base
+        "cli",
+        "swapgs",
+        // Frame of an interrupt from ring 3
+        "push {ds}",
+        "push {rsp}",
+        "push {rflags}",
+        "push {cs}",
+        "push {rip}",
+        "iretq",
+        ds = in(reg) user_ds,
+        rsp = in(reg) stack_top.as_u64(),
+        rflags = in(reg) USER_RFLAGS,
+        cs = in(reg) user_cs,
+        rip = in(reg) entry.as_u64(),
+        options(noreturn)
+    );
+}
@@ -1 +1,205 @@
+//! PS/2 keyboard driver
+//!
+//! The keyboard interrupt handler pushes the scancodes read from the controller into a fixed size
+//! queue and wakes up the task waiting for them. The scancodes are consumed as an asynchronous
+//! [ScancodeStream], so the decoding happens in a task, outside of the interrupt handler.
 // TODO! Right now using https://crates.io/crates/pc-keyboard
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::task::{Context, Poll};
+
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+
+use crate::print;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::task::{AtomicWaker, Stream};
+
+/// Number of scancodes the queue can hold.
+const SCANCODE_QUEUE_CAPACITY: usize = 128;
+
+/// Scancodes received and not consumed yet.
+static SCANCODES: IrqSafeMutex<ScancodeQueue<SCANCODE_QUEUE_CAPACITY>> =
+    IrqSafeMutex::new(ScancodeQueue::new());
+
+/// Waker of the task consuming the scancodes.
+static WAKER: AtomicWaker = AtomicWaker::new();
+
+/// Whether the scancode stream was already taken.
+static STREAM_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// Number of scancodes dropped because the queue was full.
+static DROPPED_SCANCODES: AtomicU64 = AtomicU64::new(0);
+
+/// Represents all the possible errors that can happen when using a scancode queue.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ScancodeQueueError {
+    QueueIsFull,
+}
+
+/// A fixed size FIFO queue of scancodes.
+struct ScancodeQueue<const CAPACITY: usize> {
+    scancodes: [u8; CAPACITY],
+
+    /// Position of the oldest scancode.
+    head: usize,
+
+    /// Number of scancodes in the queue.
+    len: usize,
+}
+
+impl<const CAPACITY: usize> ScancodeQueue<CAPACITY> {
+    /// Creates a new empty queue.
+    const fn new() -> Self {
+        Self {
+            scancodes: [0; CAPACITY],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Pushes a scancode at the end of the queue.
+    ///
+    /// # Arguments
+    /// * `scancode` - Scancode to push.
+    fn push(&mut self, scancode: u8) -> Result<(), ScancodeQueueError> {
+        if self.len == CAPACITY {
+            return Err(ScancodeQueueError::QueueIsFull);
+        }
+
+        self.scancodes[(self.head + self.len) % CAPACITY] = scancode;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Pops the oldest scancode. Returns `None` if the queue is empty.
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let scancode = self.scancodes[self.head];
+        self.head = (self.head + 1) % CAPACITY;
+        self.len -= 1;
+        Some(scancode)
+    }
+}
+
+/// Adds a scancode to the queue and wakes up the task consuming them. Called by the keyboard
+/// interrupt handler.
+///
+/// If the queue is full the scancode is dropped and counted in [dropped_scancodes].
+///
+/// # Arguments
+/// * `scancode` - Scancode read from the keyboard controller.
+pub(crate) fn add_scancode(scancode: u8) {
+    if SCANCODES.lock().push(scancode).is_err() {
+        DROPPED_SCANCODES.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    WAKER.wake();
+}
+
+/// Returns the number of scancodes that were dropped because nobody consumed them in time.
+pub fn dropped_scancodes() -> u64 {
+    DROPPED_SCANCODES.load(Ordering::Relaxed)
+}
+
+/// Asynchronous stream of the scancodes received from the keyboard.
+///
+/// There can only be one stream, so every scancode is consumed once.
+pub struct ScancodeStream {
+    _private: (),
+}
+
+impl ScancodeStream {
+    /// Takes the scancode stream. Returns `None` if it was already taken.
+    pub fn take() -> Option<Self> {
+        if STREAM_TAKEN.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+
+        Some(Self { _private: () })
+    }
+}
+
+impl Stream for ScancodeStream {
+    type Item = u8;
+
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<u8>> {
+        if let Some(scancode) = SCANCODES.lock().pop() {
+            return Poll::Ready(Some(scancode));
+        }
+
+        // The waker is registered before checking again, so a scancode pushed in between is not
+        // lost
+        WAKER.register(context.waker());
+        match SCANCODES.lock().pop() {
+            Some(scancode) => {
+                WAKER.take();
+                Poll::Ready(Some(scancode))
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ScancodeStream {
+    fn drop(&mut self) {
+        STREAM_TAKEN.store(false, Ordering::Release);
+    }
+}
+
+// TODO: Do something more than printing...
+// TODO: Own keyboard driver!
+/// Task that decodes the scancodes and prints the keys.
+pub async fn print_keypresses() {
+    let Some(mut scancodes) = ScancodeStream::take() else {
+        return;
+    };
+    let mut keyboard: Keyboard<layouts::Us104Key, ScancodeSet1> =
+        Keyboard::new(HandleControl::Ignore);
+
+    while let Some(scancode) = scancodes.next().await {
+        if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+            if let Some(key) = keyboard.process_keyevent(key_event) {
+                match key {
+                    DecodedKey::Unicode(character) => print!("{}", character),
+                    DecodedKey::RawKey(key) => print!("{:?}", key),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn scancodes_are_popped_in_order() {
+        let mut queue = ScancodeQueue::<4>::new();
+
+        for scancode in 1..=3 {
+            assert!(queue.push(scancode).is_ok());
+        }
+        assert_eq!(Some(1), queue.pop());
+
+        // Wraps around the end of the buffer
+        assert!(queue.push(4).is_ok());
+        assert!(queue.push(5).is_ok());
+        for scancode in 2..=5 {
+            assert_eq!(Some(scancode), queue.pop());
+        }
+        assert_eq!(None, queue.pop());
+    }
+
+    #[test_case]
+    fn push_full_queue_should_fail() {
+        let mut queue = ScancodeQueue::<2>::new();
+        assert!(queue.push(1).is_ok());
+        assert!(queue.push(2).is_ok());
+
+        assert_eq!(Err(ScancodeQueueError::QueueIsFull), queue.push(3));
+    }
+}
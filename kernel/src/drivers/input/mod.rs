@@ -1 +1 @@
-mod keyboard;
+pub mod keyboard;
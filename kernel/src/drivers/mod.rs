@@ -1,3 +1,4 @@
 pub mod input;
+pub mod rtc;
 pub mod screen;
 pub mod serial;
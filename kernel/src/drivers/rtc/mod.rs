@@ -0,0 +1,45 @@
+//! CMOS Real Time Clock driver
+use crate::arch::x86_64::acpi::{self, Acpi};
+use crate::arch::x86_64::PICS;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::time::DateTime;
+use x86_64_custom::acpi::Fadt;
+use x86_64_custom::timers::CmosRtc;
+
+/// IRQ line of the RTC (in the secondary PIC).
+const RTC_IRQ: u8 = 8;
+
+/// Number of registers of the CMOS, the index port has only 7 bits for them.
+const CMOS_REGISTERS: u8 = 0x80;
+
+pub static RTC: IrqSafeMutex<CmosRtc> = IrqSafeMutex::new(CmosRtc::new());
+
+/// Reads the current date and time from the RTC. The century is read from the register given by
+/// the ACPI FADT; without ACPI, or if the FADT has none, the 21st century is assumed.
+pub fn read() -> DateTime {
+    let time = unsafe { RTC.lock().read(century_register()) };
+    DateTime::from(time)
+}
+
+/// Returns the CMOS register that holds the century, if the ACPI FADT gives a valid one.
+fn century_register() -> Option<u8> {
+    acpi::get()
+        .and_then(Acpi::fadt)
+        .and_then(Fadt::century_register)
+        .filter(|&register| register < CMOS_REGISTERS)
+}
+
+/// Enables the RTC update interrupt, it fires once per second every time the RTC finishes
+/// updating its registers.
+pub fn enable_update_interrupt() {
+    unsafe {
+        RTC.lock().enable_update_interrupt();
+        PICS.lock().set_masked(RTC_IRQ, false);
+    }
+}
+
+/// Acknowledges the RTC interrupt, otherwise it does not fire again. Must only be called by the
+/// RTC interrupt handler.
+pub(crate) fn acknowledge_interrupt() {
+    unsafe { RTC.lock().acknowledge_interrupt() };
+}
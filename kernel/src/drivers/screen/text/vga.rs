@@ -9,14 +9,13 @@
 //! Most of the code is from https://os.phil-opp.com/vga-text-mode/
 use crate::drivers::screen::text::Writer;
 use crate::memory::volatile::Volatile;
-use crate::synchronization::spinlock::Mutex;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::synchronization::lazy::Lazy;
 use core::clone::Clone;
 use core::fmt::Write;
 use core::marker::Copy;
 use core::ops::{Deref, DerefMut};
 use core::prelude::v1::derive;
-use lazy_static::lazy_static;
-use x86_64::instructions::interrupts::without_interrupts;
 // use spin::Mutex;
 
 // The vga buffer is a 80x25 matrix
@@ -26,15 +25,15 @@ const BUFFER_LAST_ROW: usize = BUFFER_HEIGHT - 1;
 const DEFAULT_COLOR: ColorCode = ColorCode::new(Color::LightGray, Color::Black);
 
 // Global instance of the VGA buffer
-lazy_static! {
-    pub static ref WRITER: Mutex<VGAWriter> = Mutex::new(VGAWriter {
+pub static WRITER: Lazy<IrqSafeMutex<VGAWriter>> = Lazy::new(|| {
+    IrqSafeMutex::new(VGAWriter {
         column_position: 0,
         color: DEFAULT_COLOR,
         // This is a raw pointer to 0xb8000 adress in memory. We set the Buffer to the beginning of
         // this VGA Text buffer
         buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
-    });
-}
+    })
+});
 
 // We only need 4 bits to represent the color, but Rust does not have a u4 type, so we use the
 // minimum unsigned integer possible: u8.
@@ -240,34 +239,23 @@ impl core::fmt::Write for VGAWriter {
 
 #[doc(hidden)]
 pub fn _print(args: core::fmt::Arguments) {
-    // TODO: Disabling the interrupts is specific for x86 at the moment, if we are going to support
-    // more architectures, we need to refactor this.
-    without_interrupts(|| {
-        WRITER.lock().write_fmt(args).unwrap();
-    })
+    WRITER.lock().write_fmt(args).unwrap();
 }
 
 #[doc(hidden)]
 pub fn _set_color(foreground: super::PrintColor, background: super::PrintColor) {
-    // TODO: Disabling the interrupts is specific for x86 at the moment, if we are going to support
-    // more architectures, we need to refactor this.
-    without_interrupts(|| {
-        WRITER.lock().set_color(foreground, background);
-    })
+    WRITER.lock().set_color(foreground, background);
 }
 
 #[doc(hidden)]
 pub fn _clear_screen(background: Option<super::PrintColor>) {
-    // TODO: Disabling the interrupts is specific for x86 at the moment, if we are going to support
-    // more architectures, we need to refactor this.
-    without_interrupts(|| {
-        WRITER.lock().clear_screen(background);
-    })
+    WRITER.lock().clear_screen(background);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use x86_64::instructions::interrupts::without_interrupts;
 
     #[test_case]
     fn test_println_many() {
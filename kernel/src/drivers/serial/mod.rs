@@ -1,30 +1,37 @@
-use crate::synchronization::spinlock::Mutex;
-use lazy_static::lazy_static;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::synchronization::lazy::Lazy;
 use uart_16550::SerialPort;
-use x86_64::instructions::interrupts::without_interrupts;
 
 /// Port address to where we are going to write our data.
 const PORT_ADDRESS: u16 = 0x3f8;
 
 // Lazy instance of the therial port.
-lazy_static! {
-    pub static ref SERIAL1: Mutex<SerialPort> = {
-        let mut serial_port = unsafe { SerialPort::new(PORT_ADDRESS) };
-        serial_port.init();
-        Mutex::new(serial_port)
-    };
-}
+pub static SERIAL1: Lazy<IrqSafeMutex<SerialPort>> = Lazy::new(|| {
+    let mut serial_port = unsafe { SerialPort::new(PORT_ADDRESS) };
+    serial_port.init();
+    IrqSafeMutex::new(serial_port)
+});
 
 #[doc(hidden)]
 pub fn _print(args: ::core::fmt::Arguments) {
     use core::fmt::Write;
 
-    without_interrupts(|| {
-        SERIAL1
-            .lock()
-            .write_fmt(args)
-            .expect("Printing to serial failed");
-    })
+    SERIAL1
+        .lock()
+        .write_fmt(args)
+        .expect("Printing to serial failed");
+}
+
+/// Prints through the serial interface without taking the `SERIAL1` lock. It is meant for
+/// diagnostics that can be printed while the lock is held (for example, by the lock debugging
+/// code), the output could be interleaved with other prints.
+pub(crate) fn _print_unlocked(args: ::core::fmt::Arguments) {
+    use core::fmt::Write;
+
+    // The port was already initialized by `SERIAL1`, or it will be when it is used for the first
+    // time. We do not initialize it here, it would reset an ongoing transmission.
+    let mut serial_port = unsafe { SerialPort::new(PORT_ADDRESS) };
+    let _ = serial_port.write_fmt(args);
 }
 
 /// Print through the serial interface.
@@ -0,0 +1,319 @@
+//! Deferred interrupt work (a.k.a. bottom halves)
+//!
+//! Interrupt handlers (the top halves) should do as little as possible: while they run the
+//! interrupted code is stopped and, since the handlers run with interrupts disabled, other
+//! interrupts are delayed. Taking locks inside them is also dangerous, if the interrupted code was
+//! holding the same lock the system deadlocks.
+//!
+//! Instead, a handler just reads what it needs from the hardware and pushes a work item into a
+//! queue. The kernel drains that queue later from a normal context, with interrupts enabled,
+//! where it is safe to take locks and do slow work (such as decoding a scancode and printing it).
+//!
+//! The queue is a bounded lock-free multi producer multi consumer queue, based on Dmitry Vyukov's
+//! design. Pushing never blocks, so it is safe to do it from any interrupt handler.
+//!
+//! For more information:
+//! https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue
+//! https://www.kernel.org/doc/html/latest/core-api/workqueue.html
+use crate::thread;
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// Number of work items the global queue can hold.
+const DEFERRED_WORK_CAPACITY: usize = 256;
+
+/// Global queue of deferred work.
+static DEFERRED_WORK: WorkQueue<DEFERRED_WORK_CAPACITY> = WorkQueue::new();
+
+/// Number of work items that were dropped because the global queue was full.
+static DROPPED_WORK: AtomicU64 = AtomicU64::new(0);
+
+/// Represents all the possible errors that can happen when using a work queue.
+#[derive(Debug)]
+pub enum WorkQueueError {
+    QueueIsFull,
+}
+
+/// A unit of deferred work: a function and the argument it will be called with.
+#[derive(Clone, Copy)]
+pub struct Work {
+    function: fn(usize),
+    argument: usize,
+}
+
+impl Work {
+    /// Creates a new work item.
+    ///
+    /// # Arguments
+    /// * `function` - Function to execute when the work is processed.
+    /// * `argument` - Argument passed to `function`.
+    pub const fn new(function: fn(usize), argument: usize) -> Self {
+        Self { function, argument }
+    }
+
+    /// Executes the work.
+    pub fn run(self) {
+        (self.function)(self.argument)
+    }
+}
+
+/// A queue slot.
+struct Slot {
+    /// Tells the producers and consumers if this slot can be written or read in the current lap
+    /// around the ring buffer.
+    sequence: AtomicUsize,
+
+    /// Stored work.
+    work: UnsafeCell<MaybeUninit<Work>>,
+}
+
+/// A bounded lock-free queue of work items.
+///
+/// `CAPACITY` must be a power of two.
+pub struct WorkQueue<const CAPACITY: usize> {
+    slots: [Slot; CAPACITY],
+
+    /// Position where the next work item will be pushed.
+    enqueue_position: AtomicUsize,
+
+    /// Position where the next work item will be popped from.
+    dequeue_position: AtomicUsize,
+}
+
+impl<const CAPACITY: usize> WorkQueue<CAPACITY> {
+    const MASK: usize = CAPACITY - 1;
+
+    /// Creates a new empty queue.
+    pub const fn new() -> Self {
+        assert!(
+            CAPACITY.is_power_of_two(),
+            "capacity must be a power of two"
+        );
+
+        let mut slots = [const {
+            Slot {
+                sequence: AtomicUsize::new(0),
+                work: UnsafeCell::new(MaybeUninit::uninit()),
+            }
+        }; CAPACITY];
+
+        // Every slot starts waiting to be written in the first lap.
+        let mut index = 0;
+        while index < CAPACITY {
+            slots[index].sequence = AtomicUsize::new(index);
+            index += 1;
+        }
+
+        Self {
+            slots,
+            enqueue_position: AtomicUsize::new(0),
+            dequeue_position: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pushes a work item at the end of the queue. If the queue is full the work is given back
+    /// inside the error.
+    ///
+    /// This function does not block.
+    pub fn push(&self, work: Work) -> Result<(), (WorkQueueError, Work)> {
+        let mut position = self.enqueue_position.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & Self::MASK];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let difference = sequence as isize - position as isize;
+
+            if difference == 0 {
+                // The slot is free in this lap, try to claim it.
+                match self.enqueue_position.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.work.get()).write(work) };
+                        // Publish the work to the consumers.
+                        slot.sequence
+                            .store(position.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => position = current,
+                }
+            } else if difference < 0 {
+                // The slot still holds the work from the previous lap: the queue is full.
+                return Err((WorkQueueError::QueueIsFull, work));
+            } else {
+                // Other producer claimed this position, try again with the new one.
+                position = self.enqueue_position.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops the oldest work item from the queue. Returns `None` if the queue is empty.
+    ///
+    /// This function does not block.
+    pub fn pop(&self) -> Option<Work> {
+        let mut position = self.dequeue_position.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[position & Self::MASK];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let difference = sequence as isize - position.wrapping_add(1) as isize;
+
+            if difference == 0 {
+                // The slot has been written in this lap, try to claim it.
+                match self.dequeue_position.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let work = unsafe { (*slot.work.get()).assume_init_read() };
+                        // Free the slot for the next lap.
+                        slot.sequence
+                            .store(position.wrapping_add(CAPACITY), Ordering::Release);
+                        return Some(work);
+                    }
+                    Err(current) => position = current,
+                }
+            } else if difference < 0 {
+                // Nothing has been written here yet: the queue is empty.
+                return None;
+            } else {
+                // Other consumer claimed this position, try again with the new one.
+                position = self.dequeue_position.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns if the queue has no work.
+    pub fn is_empty(&self) -> bool {
+        self.enqueue_position.load(Ordering::Acquire)
+            == self.dequeue_position.load(Ordering::Acquire)
+    }
+}
+
+impl<const CAPACITY: usize> Default for WorkQueue<CAPACITY> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl<const CAPACITY: usize> Sync for WorkQueue<CAPACITY> {}
+
+/// Schedules a function to be executed later from the kernel context. This is meant to be used
+/// from the interrupt handlers.
+///
+/// If the queue is full the work is dropped and counted in [dropped_work].
+///
+/// # Arguments
+/// * `function` - Function to execute.
+/// * `argument` - Argument passed to `function`.
+pub fn defer(function: fn(usize), argument: usize) -> Result<(), WorkQueueError> {
+    defer_work(Work::new(function, argument))
+}
+
+/// Schedules a work item to be executed later from the kernel context. See [defer].
+///
+/// # Arguments
+/// * `work` - Work to execute.
+pub fn defer_work(work: Work) -> Result<(), WorkQueueError> {
+    DEFERRED_WORK.push(work).map_err(|(error, _)| {
+        DROPPED_WORK.fetch_add(1, Ordering::Relaxed);
+        error
+    })
+}
+
+/// Executes all the pending deferred work.
+///
+/// This must be called from the kernel context (never from an interrupt handler) and with
+/// interrupts enabled, so new work can keep arriving while the queue is drained.
+pub fn run_deferred_work() {
+    while let Some(work) = DEFERRED_WORK.pop() {
+        work.run();
+    }
+}
+
+/// Gives the CPU to other threads, or halts it until the next interrupt, if there is no pending
+/// deferred work.
+///
+/// Checking the queue and halting is done with interrupts disabled, and `sti; hlt` is executed as
+/// a single step, so a work item pushed right after the check still wakes up the CPU. While
+/// halted, the periodic tick is stopped if possible (see [idle](crate::arch::x86_64::idle)).
+pub fn wait_for_deferred_work() {
+    x86_64::instructions::interrupts::disable();
+    if DEFERRED_WORK.is_empty() {
+        thread::idle();
+    } else {
+        x86_64::instructions::interrupts::enable();
+    }
+}
+
+/// Returns if there is deferred work waiting to be executed.
+pub fn has_deferred_work() -> bool {
+    !DEFERRED_WORK.is_empty()
+}
+
+/// Returns the number of work items that were dropped because the queue was full.
+pub fn dropped_work() -> u64 {
+    DROPPED_WORK.load(Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static ACCUMULATOR: AtomicUsize = AtomicUsize::new(0);
+
+    fn accumulate(value: usize) {
+        // Shift before adding so the order of execution changes the result
+        let current = ACCUMULATOR.load(Ordering::Relaxed);
+        ACCUMULATOR.store(current * 10 + value, Ordering::Relaxed);
+    }
+
+    #[test_case]
+    fn pop_empty_queue() {
+        let queue = WorkQueue::<4>::new();
+
+        assert!(queue.is_empty());
+        assert!(queue.pop().is_none());
+    }
+
+    #[test_case]
+    fn work_runs_in_order() {
+        let queue = WorkQueue::<4>::new();
+        ACCUMULATOR.store(0, Ordering::Relaxed);
+
+        for value in 1..=3 {
+            assert!(queue.push(Work::new(accumulate, value)).is_ok());
+        }
+        while let Some(work) = queue.pop() {
+            work.run();
+        }
+
+        assert_eq!(123, ACCUMULATOR.load(Ordering::Relaxed));
+    }
+
+    #[test_case]
+    fn push_full_queue_should_fail() {
+        let queue = WorkQueue::<2>::new();
+        assert!(queue.push(Work::new(accumulate, 1)).is_ok());
+        assert!(queue.push(Work::new(accumulate, 2)).is_ok());
+
+        if queue.push(Work::new(accumulate, 3)).is_ok() {
+            panic!("Queue should be full!")
+        }
+    }
+
+    #[test_case]
+    fn slots_are_reused_after_pop() {
+        let queue = WorkQueue::<2>::new();
+
+        for value in 0..10 {
+            assert!(queue.push(Work::new(accumulate, value)).is_ok());
+            assert_eq!(value, queue.pop().map(|work| work.argument).unwrap());
+        }
+        assert!(queue.is_empty());
+    }
+}
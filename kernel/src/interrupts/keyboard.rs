@@ -1,24 +1,8 @@
-use lazy_static::lazy_static;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
+use crate::drivers::input::keyboard::add_scancode;
 
-use crate::{print, synchronization::spinlock::Mutex};
-
-lazy_static! {
-    static ref KEYBOARD: Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-        Mutex::new(Keyboard::new(HandleControl::Ignore));
-}
-
-// TODO: Do something more than printing...
-// TODO: Own keyboard driver!
+/// Keyboard interrupt top half. The scancode is queued and decoded later by the task consuming
+/// the [scancode stream](crate::drivers::input::keyboard::ScancodeStream), so the interrupt
+/// handler does not take the `WRITER` lock.
 pub fn handler(scancode: u8) {
-    let mut keyboard = KEYBOARD.lock();
-
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            match key {
-                DecodedKey::Unicode(character) => print!("{}", character),
-                DecodedKey::RawKey(key) => print!("{:?}", key),
-            }
-        }
-    }
+    add_scancode(scancode);
 }
@@ -1,6 +1,11 @@
 //! This module contains architecture agnostic interruptor handlers
+pub mod deferred;
 mod keyboard;
-mod timer;
+mod rtc;
+pub mod statistics;
+pub mod timer;
 
 pub use keyboard::handler as keyboard_handler;
+pub use rtc::handler as rtc_handler;
+pub use statistics::{print_interrupt_latency, print_interrupt_statistics};
 pub use timer::handler as timer_handler;
@@ -0,0 +1,20 @@
+//! RTC interrupt handler
+//!
+//! The PIT and the RTC oscillators drift apart, the RTC is much more accurate over long periods
+//! of time. Every time the RTC finishes an update, we resynchronize the wall clock with it.
+use crate::drivers::rtc;
+use crate::interrupts::deferred::defer;
+use crate::time::wall_clock;
+
+/// RTC interrupt top half. Reading the RTC takes a while (it waits for the update to finish), so
+/// the synchronization is deferred.
+pub fn handler() {
+    rtc::acknowledge_interrupt();
+    // If the queue is full we skip this synchronization, the next update will do it
+    let _ = defer(synchronize_wall_clock, 0);
+}
+
+/// RTC interrupt bottom half, synchronizes the wall clock with the RTC.
+fn synchronize_wall_clock(_: usize) {
+    wall_clock::synchronize(rtc::read());
+}
@@ -0,0 +1,20 @@
+//! Interrupt statistics
+//!
+//! The counters are kept by the architecture code (every generated IRQ handler and every exception
+//! handler updates them), here we just expose them to the rest of the kernel.
+use crate::println;
+
+pub use x86_64_custom::interrupts::statistics::{InterruptStatistics, INTERRUPT_STATISTICS};
+
+/// Prints a table with the number of times each interrupt vector was handled.
+pub fn print_interrupt_statistics() {
+    println!("{}", INTERRUPT_STATISTICS);
+}
+
+/// Prints the handlers duration histograms.
+///
+/// Latency tracking is disabled by default, it must be enabled with
+/// [set_latency_tracking](InterruptStatistics::set_latency_tracking) to collect measurements.
+pub fn print_interrupt_latency() {
+    println!("{}", INTERRUPT_STATISTICS.latency());
+}
@@ -1,5 +1,366 @@
-// use crate::print;
+//! Kernel timers
+//!
+//! Besides advancing the tick counter, the timer interrupt expires the software timers scheduled
+//! by the rest of the kernel. A timer is a callback that must be executed once after a delay
+//! (one-shot) or every time a period elapses (periodic).
+//!
+//! The timers are kept in a fixed size table (we do not have a heap yet). The nearest deadline is
+//! cached in an atomic so the interrupt handler only takes the table lock when some timer is
+//! actually due.
+//!
+//! The callbacks are not executed inside the interrupt handler, they are pushed to the
+//! [deferred work queue](crate::interrupts::deferred) and run later from the kernel context, so
+//! they are free to take locks and print.
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
 
+use crate::interrupts::deferred::{defer_work, Work};
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::thread;
+use crate::time::{self, duration_to_ticks, Instant};
+
+/// Maximum number of timers that can be scheduled at the same time.
+const MAX_TIMERS: usize = 64;
+
+/// Value of `NEXT_DEADLINE` when there are no timers scheduled.
+const NO_DEADLINE: u64 = u64::MAX;
+
+/// Global table of timers.
+static TIMERS: IrqSafeMutex<TimerQueue> = IrqSafeMutex::new(TimerQueue::new());
+
+/// Tick of the nearest timer deadline.
+static NEXT_DEADLINE: AtomicU64 = AtomicU64::new(NO_DEADLINE);
+
+/// Represents all the possible errors that can happen when using timers.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TimerError {
+    /// There are no free slots to schedule a new timer.
+    QueueIsFull,
+
+    /// The waited condition was not met before the timeout.
+    TimedOut,
+}
+
+/// Identifies a scheduled timer. It is used to cancel it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimerHandle {
+    /// Slot of the timer in the table.
+    index: usize,
+
+    /// Generation of the slot when the timer was scheduled. It prevents cancelling a newer timer
+    /// that reused the slot of an already expired one.
+    generation: u64,
+}
+
+/// A scheduled timer.
+#[derive(Clone, Copy)]
+struct Timer {
+    /// Tick in which the timer expires.
+    deadline: u64,
+
+    /// Period in ticks, zero for one-shot timers.
+    period: u64,
+
+    /// Callback to run when the timer expires.
+    work: Work,
+}
+
+/// Table slot.
+#[derive(Clone, Copy)]
+struct TimerSlot {
+    timer: Option<Timer>,
+    generation: u64,
+}
+
+/// Fixed size table of timers.
+pub(crate) struct TimerQueue {
+    slots: [TimerSlot; MAX_TIMERS],
+}
+
+impl TimerQueue {
+    /// Creates an empty table.
+    pub(crate) const fn new() -> Self {
+        Self {
+            slots: [TimerSlot {
+                timer: None,
+                generation: 0,
+            }; MAX_TIMERS],
+        }
+    }
+
+    /// Adds a new timer.
+    ///
+    /// # Arguments
+    /// * `deadline` - Tick in which the timer expires.
+    /// * `period` - Period in ticks, zero for one-shot timers.
+    /// * `work` - Callback to run when the timer expires.
+    fn insert(
+        &mut self,
+        deadline: u64,
+        period: u64,
+        work: Work,
+    ) -> Result<TimerHandle, TimerError> {
+        let (index, slot) = self
+            .slots
+            .iter_mut()
+            .enumerate()
+            .find(|(_, slot)| slot.timer.is_none())
+            .ok_or(TimerError::QueueIsFull)?;
+
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.timer = Some(Timer {
+            deadline,
+            period,
+            work,
+        });
+
+        Ok(TimerHandle {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    /// Removes a timer. Returns `false` if the timer already expired or was cancelled.
+    ///
+    /// # Arguments
+    /// * `handle` - Handle returned when the timer was scheduled.
+    fn remove(&mut self, handle: TimerHandle) -> bool {
+        let slot = &mut self.slots[handle.index];
+        if slot.generation != handle.generation || slot.timer.is_none() {
+            return false;
+        }
+
+        slot.timer = None;
+        true
+    }
+
+    /// Expires all the timers with a deadline lower or equal than `now`, calling `expired` with
+    /// each one of their callbacks. Periodic timers are rescheduled.
+    ///
+    /// # Arguments
+    /// * `now` - Current tick.
+    /// * `expired` - Function called with the callback of every expired timer.
+    fn expire(&mut self, now: u64, mut expired: impl FnMut(Work)) {
+        for slot in self.slots.iter_mut() {
+            let Some(timer) = slot.timer.as_mut() else {
+                continue;
+            };
+
+            if timer.deadline > now {
+                continue;
+            }
+
+            expired(timer.work);
+            // One-shot timers have a period of zero
+            match (now - timer.deadline).checked_div(timer.period) {
+                None => slot.timer = None,
+                // If we missed some periods (for example if the interrupts were disabled for a
+                // long time) we skip them instead of firing the timer several times in a row.
+                Some(missed_periods) => timer.deadline += (missed_periods + 1) * timer.period,
+            }
+        }
+    }
+
+    /// Returns the nearest deadline of the scheduled timers.
+    fn next_deadline(&self) -> Option<u64> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.timer.map(|timer| timer.deadline))
+            .min()
+    }
+}
+
+/// Runs the given closure with the timers table locked and then refreshes the cached nearest
+/// deadline.
+fn with_timers<R>(f: impl FnOnce(&mut TimerQueue) -> R) -> R {
+    let mut timers = TIMERS.lock();
+    let result = f(&mut timers);
+    NEXT_DEADLINE.store(
+        timers.next_deadline().unwrap_or(NO_DEADLINE),
+        Ordering::Relaxed,
+    );
+    result
+}
+
+/// Schedules a callback to be executed once after the given delay.
+///
+/// # Arguments
+/// * `delay` - Time to wait before running the callback.
+/// * `callback` - Function to execute.
+/// * `argument` - Argument passed to `callback`.
+pub fn schedule_once(
+    delay: Duration,
+    callback: fn(usize),
+    argument: usize,
+) -> Result<TimerHandle, TimerError> {
+    let deadline = time::ticks().saturating_add(duration_to_ticks(delay));
+    with_timers(|timers| timers.insert(deadline, 0, Work::new(callback, argument)))
+}
+
+/// Schedules a callback to be executed every time the given period elapses. The first execution
+/// happens one period after calling this function.
+///
+/// # Arguments
+/// * `period` - Time between executions. Periods shorter than a tick are rounded up to a tick.
+/// * `callback` - Function to execute.
+/// * `argument` - Argument passed to `callback`.
+pub fn schedule_periodic(
+    period: Duration,
+    callback: fn(usize),
+    argument: usize,
+) -> Result<TimerHandle, TimerError> {
+    let period = duration_to_ticks(period).max(1);
+    let deadline = time::ticks().saturating_add(period);
+    with_timers(|timers| timers.insert(deadline, period, Work::new(callback, argument)))
+}
+
+/// Cancels a timer. Returns `false` if the timer already expired (one-shot timers) or was
+/// already cancelled.
+///
+/// A callback that already expired but is still waiting in the deferred work queue is executed
+/// anyway.
+///
+/// # Arguments
+/// * `handle` - Handle returned when the timer was scheduled.
+pub fn cancel(handle: TimerHandle) -> bool {
+    with_timers(|timers| timers.remove(handle))
+}
+
+/// Returns the instant when the nearest timer expires, if there is any timer scheduled.
+pub fn next_deadline() -> Option<Instant> {
+    match NEXT_DEADLINE.load(Ordering::Relaxed) {
+        NO_DEADLINE => None,
+        deadline => Some(Instant::from_ticks(deadline)),
+    }
+}
+
+/// Busy-waits until `condition` returns true or the timeout elapses. This is meant for drivers
+/// that poll the hardware waiting for a status change.
+///
+/// Interrupts must be enabled, otherwise the tick counter does not advance and the timeout never
+/// expires.
+///
+/// # Arguments
+/// * `timeout` - Maximum time to wait.
+/// * `condition` - Condition to poll.
+pub fn poll_with_timeout(
+    timeout: Duration,
+    mut condition: impl FnMut() -> bool,
+) -> Result<(), TimerError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if condition() {
+            return Ok(());
+        }
+
+        if Instant::now() >= deadline {
+            return Err(TimerError::TimedOut);
+        }
+
+        core::hint::spin_loop();
+    }
+}
+
+/// Timer interrupt handler, advances the kernel tick counter and expires the due timers.
 pub fn handler() {
-    // print!(".");
+    advance(1);
+}
+
+/// Advances the kernel tick counter, expires the due timers and accounts the elapsed time to the
+/// running thread. Must be called with the interrupts disabled.
+///
+/// # Arguments
+/// * `ticks` - Number of ticks elapsed.
+pub(crate) fn advance(ticks: u64) {
+    time::advance(ticks);
+    thread::tick(ticks);
+
+    let now = time::ticks();
+    if now < NEXT_DEADLINE.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut timers = TIMERS.lock();
+    timers.expire(now, |work| {
+        // If the queue is full the callback is lost, it is counted as dropped work
+        let _ = defer_work(work);
+    });
+    NEXT_DEADLINE.store(
+        timers.next_deadline().unwrap_or(NO_DEADLINE),
+        Ordering::Relaxed,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nothing(_: usize) {}
+
+    #[test_case]
+    fn one_shot_timer_expires_once() {
+        let mut timers = TimerQueue::new();
+        assert!(timers.insert(10, 0, Work::new(nothing, 1)).is_ok());
+
+        let mut expired = 0;
+        timers.expire(9, |_| expired += 1);
+        assert_eq!(0, expired);
+
+        timers.expire(10, |_| expired += 1);
+        timers.expire(20, |_| expired += 1);
+        assert_eq!(1, expired);
+        assert_eq!(None, timers.next_deadline());
+    }
+
+    #[test_case]
+    fn periodic_timer_is_rescheduled() {
+        let mut timers = TimerQueue::new();
+        assert!(timers.insert(10, 10, Work::new(nothing, 1)).is_ok());
+
+        let mut expired = 0;
+        timers.expire(10, |_| expired += 1);
+        assert_eq!(Some(20), timers.next_deadline());
+
+        // Missed periods are skipped
+        timers.expire(45, |_| expired += 1);
+        assert_eq!(2, expired);
+        assert_eq!(Some(50), timers.next_deadline());
+    }
+
+    #[test_case]
+    fn cancelled_timer_does_not_expire() {
+        let mut timers = TimerQueue::new();
+        let handle = timers.insert(10, 0, Work::new(nothing, 1)).unwrap();
+
+        assert!(timers.remove(handle));
+        assert!(!timers.remove(handle));
+
+        let mut expired = 0;
+        timers.expire(10, |_| expired += 1);
+        assert_eq!(0, expired);
+    }
+
+    #[test_case]
+    fn stale_handle_does_not_cancel_reused_slot() {
+        let mut timers = TimerQueue::new();
+        let old_handle = timers.insert(10, 0, Work::new(nothing, 1)).unwrap();
+        timers.expire(10, |_| {});
+
+        let new_handle = timers.insert(20, 0, Work::new(nothing, 2)).unwrap();
+        assert!(!timers.remove(old_handle));
+        assert!(timers.remove(new_handle));
+    }
+
+    #[test_case]
+    fn full_queue_should_fail() {
+        let mut timers = TimerQueue::new();
+        for _ in 0..MAX_TIMERS {
+            assert!(timers.insert(10, 0, Work::new(nothing, 1)).is_ok());
+        }
+
+        assert_eq!(
+            Err(TimerError::QueueIsFull),
+            timers.insert(10, 0, Work::new(nothing, 1))
+        );
+    }
 }
@@ -16,19 +16,25 @@ extern crate alloc;
 pub mod arch;
 pub mod drivers;
 pub mod interrupts;
+pub mod loader;
 pub mod memory;
 pub mod os_core;
 pub mod synchronization;
+pub mod syscall;
+pub mod task;
 pub mod tests;
+pub mod thread;
+pub mod time;
 
 // "Global scope" exports
 pub use drivers::screen::text::PrintColor;
 
 /// Halts the CPU until the next interrupt hits. This prevents the CPU to spin endessly and waste
-/// cycles doing nothing.
+/// cycles doing nothing. The periodic tick is stopped while halted if possible.
 pub fn hlt_loop() -> ! {
     loop {
-        x86_64::instructions::hlt();
+        x86_64::instructions::interrupts::disable();
+        arch::x86_64::idle();
     }
 }
 
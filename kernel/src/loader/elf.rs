@@ -0,0 +1,438 @@
+//! ELF64 parser
+//!
+//! Only what is needed to load a statically linked executable is parsed: the file header and the
+//! program headers. Sections are ignored. Every field is read with bounds checks, the file can
+//! come from anywhere.
+//!
+//! The loadable segments must fit below the user stack of the [loader](super), their total size
+//! is limited by [MAX_SEGMENTS_SIZE] and the entry point must be in an executable segment.
+//!
+//! For more info:
+//! https://wiki.osdev.org/ELF
+//! https://refspecs.linuxfoundation.org/elf/gabi4+/ch4.eheader.html
+use super::{USER_STACK_SIZE, USER_STACK_TOP};
+use crate::memory::address_space::{PAGE_SIZE, USER_SPACE_END};
+
+/// Magic number at the start of every ELF file.
+const MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+
+/// `EI_CLASS` value of 64 bit files.
+const CLASS_64: u8 = 2;
+
+/// `EI_DATA` value of little endian files.
+const DATA_LITTLE_ENDIAN: u8 = 1;
+
+/// Current ELF version.
+const VERSION_CURRENT: u8 = 1;
+
+/// `e_type` value of executable files.
+const TYPE_EXECUTABLE: u16 = 2;
+
+/// `e_machine` value of x86_64.
+const MACHINE_X86_64: u16 = 0x3e;
+
+/// Maximum memory mapped for the loadable segments of an executable, in bytes.
+pub const MAX_SEGMENTS_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Size of the file header.
+const HEADER_SIZE: usize = 64;
+
+/// Size of a 64 bit program header.
+pub const PROGRAM_HEADER_SIZE: usize = 56;
+
+/// Program header type of a loadable segment.
+pub const PT_LOAD: u32 = 1;
+
+/// Program header type of the segment that holds the program header table itself.
+pub const PT_PHDR: u32 = 6;
+
+/// Segment flag: executable.
+pub const PF_X: u32 = 1;
+
+/// Segment flag: writable.
+pub const PF_W: u32 = 2;
+
+/// Represents all the possible errors found when parsing an ELF file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElfError {
+    /// The file is too short to hold the headers.
+    Truncated,
+
+    /// The file does not start with the ELF magic number.
+    BadMagic,
+
+    /// The file is not a 64 bit little endian ELF of the current version.
+    UnsupportedFormat,
+
+    /// The file is not an x86_64 executable (for example, it is a shared object).
+    NotExecutable,
+
+    /// The program header table is malformed.
+    BadProgramHeaders,
+
+    /// A segment content is out of the file, or its file size exceeds its memory size.
+    BadSegment,
+
+    /// A segment is out of the user part of the address space.
+    OutOfUserSpace,
+
+    /// A segment overlaps the user stack.
+    OverlapsStack,
+
+    /// The loadable segments need more than [MAX_SEGMENTS_SIZE] bytes of memory.
+    TooLarge,
+
+    /// The entry point is not in an executable segment.
+    BadEntry,
+}
+
+/// Reads a little endian `u16` at `offset`.
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ElfError> {
+    let bytes = data.get(offset..offset + 2).ok_or(ElfError::Truncated)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Reads a little endian `u32` at `offset`.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, ElfError> {
+    let bytes = data.get(offset..offset + 4).ok_or(ElfError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// Reads a little endian `u64` at `offset`.
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, ElfError> {
+    let bytes = data.get(offset..offset + 8).ok_or(ElfError::Truncated)?;
+    Ok(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+/// A program header: describes a segment of the program.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProgramHeader {
+    /// Type of the segment.
+    pub kind: u32,
+
+    /// `PF_*` flags of the segment.
+    pub flags: u32,
+
+    /// Offset of the segment content in the file.
+    pub offset: u64,
+
+    /// Virtual address of the segment.
+    pub virtual_address: u64,
+
+    /// Size of the segment content in the file.
+    pub file_size: u64,
+
+    /// Size of the segment in memory. The bytes after the file content are zero (`.bss`).
+    pub memory_size: u64,
+
+    /// Alignment of the segment.
+    pub align: u64,
+}
+
+impl ProgramHeader {
+    /// Parses the program header at `offset`.
+    fn parse(data: &[u8], offset: usize) -> Result<Self, ElfError> {
+        Ok(Self {
+            kind: read_u32(data, offset)?,
+            flags: read_u32(data, offset + 4)?,
+            offset: read_u64(data, offset + 8)?,
+            virtual_address: read_u64(data, offset + 16)?,
+            // Physical address (offset + 24) is ignored
+            file_size: read_u64(data, offset + 32)?,
+            memory_size: read_u64(data, offset + 40)?,
+            align: read_u64(data, offset + 48)?,
+        })
+    }
+
+    /// Checks that a loadable segment can be loaded from a file of `file_len` bytes.
+    fn validate(&self, file_len: usize) -> Result<(), ElfError> {
+        let file_end = self
+            .offset
+            .checked_add(self.file_size)
+            .ok_or(ElfError::BadSegment)?;
+        if file_end > file_len as u64 || self.file_size > self.memory_size {
+            return Err(ElfError::BadSegment);
+        }
+
+        let end = self
+            .virtual_address
+            .checked_add(self.memory_size)
+            .filter(|&end| end <= USER_SPACE_END)
+            .ok_or(ElfError::OutOfUserSpace)?;
+        // The pages of the stack would be mapped over the ones of the segment, merging their flags
+        if end > USER_STACK_TOP - USER_STACK_SIZE {
+            return Err(ElfError::OverlapsStack);
+        }
+        if self.memory_size > MAX_SEGMENTS_SIZE {
+            return Err(ElfError::TooLarge);
+        }
+
+        // The file offset and the address must be at the same offset in the page, since the
+        // content is copied page by page
+        if self.align > 1
+            && (!self.align.is_power_of_two()
+                || self.offset % self.align != self.virtual_address % self.align)
+        {
+            return Err(ElfError::BadSegment);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the range of pages covered by the segment: the start address of its first page
+    /// and the end address of its last one.
+    pub fn pages(&self) -> (u64, u64) {
+        let start = self.virtual_address & !(PAGE_SIZE - 1);
+        let end = (self.virtual_address + self.memory_size).div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        (start, end)
+    }
+}
+
+/// A parsed ELF64 executable.
+pub struct Elf<'a> {
+    data: &'a [u8],
+
+    /// Address of the first instruction.
+    entry: u64,
+
+    /// Offset of the program header table in the file.
+    program_headers_offset: usize,
+
+    /// Number of program headers.
+    program_headers_count: usize,
+}
+
+impl<'a> Elf<'a> {
+    /// Parses and validates an ELF64 executable for x86_64: its header, its program headers and
+    /// its loadable segments.
+    ///
+    /// # Arguments
+    /// * `data` - Content of the file.
+    pub fn parse(data: &'a [u8]) -> Result<Self, ElfError> {
+        if data.len() < HEADER_SIZE {
+            return Err(ElfError::Truncated);
+        }
+        if data[..4] != MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        if data[4] != CLASS_64 || data[5] != DATA_LITTLE_ENDIAN || data[6] != VERSION_CURRENT {
+            return Err(ElfError::UnsupportedFormat);
+        }
+        if read_u16(data, 16)? != TYPE_EXECUTABLE || read_u16(data, 18)? != MACHINE_X86_64 {
+            return Err(ElfError::NotExecutable);
+        }
+
+        let entry = read_u64(data, 24)?;
+        let program_headers_offset = read_u64(data, 32)?;
+        let program_header_size = read_u16(data, 54)?;
+        let program_headers_count = read_u16(data, 56)? as usize;
+
+        if program_header_size as usize != PROGRAM_HEADER_SIZE {
+            return Err(ElfError::BadProgramHeaders);
+        }
+        let program_headers_offset = usize::try_from(program_headers_offset)
+            .ok()
+            .filter(|&offset| {
+                offset
+                    .checked_add(program_headers_count * PROGRAM_HEADER_SIZE)
+                    .is_some_and(|end| end <= data.len())
+            })
+            .ok_or(ElfError::BadProgramHeaders)?;
+
+        let elf = Self {
+            data,
+            entry,
+            program_headers_offset,
+            program_headers_count,
+        };
+        let mut segments_size = 0;
+        for segment in elf.load_segments() {
+            segment.validate(data.len())?;
+            let (start, end) = segment.pages();
+            segments_size += end - start;
+            if segments_size > MAX_SEGMENTS_SIZE {
+                return Err(ElfError::TooLarge);
+            }
+        }
+        if !elf.load_segments().any(|segment| {
+            segment.flags & PF_X != 0
+                && (segment.virtual_address..segment.virtual_address + segment.memory_size)
+                    .contains(&entry)
+        }) {
+            return Err(ElfError::BadEntry);
+        }
+
+        Ok(elf)
+    }
+
+    /// Returns the address of the first instruction.
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// Returns the number of program headers.
+    pub fn program_headers_count(&self) -> usize {
+        self.program_headers_count
+    }
+
+    /// Returns the program headers.
+    pub fn program_headers(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        (0..self.program_headers_count).map(|index| {
+            let offset = self.program_headers_offset + index * PROGRAM_HEADER_SIZE;
+            // The whole table was checked to be in the file
+            ProgramHeader::parse(self.data, offset).unwrap()
+        })
+    }
+
+    /// Returns the program headers of the loadable segments.
+    pub fn load_segments(&self) -> impl Iterator<Item = ProgramHeader> + '_ {
+        self.program_headers()
+            .filter(|header| header.kind == PT_LOAD)
+    }
+
+    /// Returns the content of a loadable segment stored in the file.
+    ///
+    /// # Arguments
+    /// * `segment` - Program header of the segment, returned by [load_segments](Self::load_segments).
+    pub fn segment_data(&self, segment: &ProgramHeader) -> &'a [u8] {
+        let start = segment.offset as usize;
+        &self.data[start..start + segment.file_size as usize]
+    }
+
+    /// Returns the address where the program header table is in memory once loaded, if it is
+    /// loaded at all: either the address of the `PT_PHDR` segment or the address of the loadable
+    /// segment that contains it.
+    pub fn program_headers_address(&self) -> Option<u64> {
+        if let Some(header) = self.program_headers().find(|header| header.kind == PT_PHDR) {
+            return Some(header.virtual_address);
+        }
+
+        let offset = self.program_headers_offset as u64;
+        let table_end = offset + (self.program_headers_count * PROGRAM_HEADER_SIZE) as u64;
+        self.load_segments()
+            .find(|segment| {
+                segment.offset <= offset && table_end <= segment.offset + segment.file_size
+            })
+            .map(|segment| segment.virtual_address + (offset - segment.offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Size of the executable built by [executable].
+    const EXECUTABLE_SIZE: usize = HEADER_SIZE + PROGRAM_HEADER_SIZE + 16;
+
+    /// Builds a minimal executable: a single loadable segment at 0x40_0000 with the whole file
+    /// (headers included) and 16 bytes of code, plus 0x1000 bytes of `.bss`.
+    fn executable() -> [u8; EXECUTABLE_SIZE] {
+        let mut data = [0u8; EXECUTABLE_SIZE];
+        let mut write =
+            |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        write(0, &MAGIC);
+        write(4, &[CLASS_64, DATA_LITTLE_ENDIAN, VERSION_CURRENT]);
+        write(16, &TYPE_EXECUTABLE.to_le_bytes());
+        write(18, &MACHINE_X86_64.to_le_bytes());
+        write(20, &1u32.to_le_bytes());
+        let entry = 0x40_0000 + (HEADER_SIZE + PROGRAM_HEADER_SIZE) as u64;
+        write(24, &entry.to_le_bytes());
+        write(32, &(HEADER_SIZE as u64).to_le_bytes());
+        write(52, &(HEADER_SIZE as u16).to_le_bytes());
+        write(54, &(PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+        write(56, &1u16.to_le_bytes());
+
+        let header = HEADER_SIZE;
+        write(header, &PT_LOAD.to_le_bytes());
+        write(header + 4, &(PF_X | 4).to_le_bytes());
+        write(header + 8, &0u64.to_le_bytes());
+        write(header + 16, &0x40_0000u64.to_le_bytes());
+        write(header + 32, &(EXECUTABLE_SIZE as u64).to_le_bytes());
+        write(
+            header + 40,
+            &(EXECUTABLE_SIZE as u64 + 0x1000).to_le_bytes(),
+        );
+        write(header + 48, &0x1000u64.to_le_bytes());
+
+        data
+    }
+
+    #[test_case]
+    fn valid_executables_are_parsed() {
+        let data = executable();
+        let elf = Elf::parse(&data).unwrap();
+
+        assert_eq!(0x40_0078, elf.entry());
+        assert_eq!(1, elf.load_segments().count());
+        let segment = elf.load_segments().next().unwrap();
+        assert_eq!((0x40_0000, 0x40_2000), segment.pages());
+        assert_eq!(EXECUTABLE_SIZE, elf.segment_data(&segment).len());
+        assert_eq!(Some(0x40_0040), elf.program_headers_address());
+    }
+
+    #[test_case]
+    fn invalid_executables_are_rejected() {
+        assert_eq!(Some(ElfError::Truncated), Elf::parse(&[0; 16]).err());
+
+        let mut data = executable();
+        data[0] = 0;
+        assert_eq!(Some(ElfError::BadMagic), Elf::parse(&data).err());
+
+        let mut data = executable();
+        data[4] = 1;
+        assert_eq!(Some(ElfError::UnsupportedFormat), Elf::parse(&data).err());
+
+        // Shared object
+        let mut data = executable();
+        data[16] = 3;
+        assert_eq!(Some(ElfError::NotExecutable), Elf::parse(&data).err());
+
+        // Two program headers, the second one out of the file
+        let mut data = executable();
+        data[56] = 2;
+        assert_eq!(Some(ElfError::BadProgramHeaders), Elf::parse(&data).err());
+
+        // File size bigger than the file
+        let mut data = executable();
+        data[HEADER_SIZE + 33] = 0x10;
+        assert_eq!(Some(ElfError::BadSegment), Elf::parse(&data).err());
+
+        // Segment in the kernel half
+        let mut data = executable();
+        data[HEADER_SIZE + 23] = 0xff;
+        assert_eq!(Some(ElfError::OutOfUserSpace), Elf::parse(&data).err());
+    }
+
+    #[test_case]
+    fn segments_over_the_stack_are_rejected() {
+        let mut data = executable();
+        let address = USER_STACK_TOP - USER_STACK_SIZE - PAGE_SIZE;
+        data[HEADER_SIZE + 16..HEADER_SIZE + 24].copy_from_slice(&address.to_le_bytes());
+        data[24..32].copy_from_slice(&address.to_le_bytes());
+
+        assert_eq!(Some(ElfError::OverlapsStack), Elf::parse(&data).err());
+    }
+
+    #[test_case]
+    fn too_large_segments_are_rejected() {
+        let mut data = executable();
+        let memory_size = MAX_SEGMENTS_SIZE + PAGE_SIZE;
+        data[HEADER_SIZE + 40..HEADER_SIZE + 48].copy_from_slice(&memory_size.to_le_bytes());
+
+        assert_eq!(Some(ElfError::TooLarge), Elf::parse(&data).err());
+    }
+
+    #[test_case]
+    fn entry_out_of_executable_segments_is_rejected() {
+        // Out of every segment
+        let mut data = executable();
+        data[24..32].copy_from_slice(&0x50_0000u64.to_le_bytes());
+        assert_eq!(Some(ElfError::BadEntry), Elf::parse(&data).err());
+
+        // In a segment that is not executable
+        let mut data = executable();
+        data[HEADER_SIZE + 4..HEADER_SIZE + 8].copy_from_slice(&4u32.to_le_bytes());
+        assert_eq!(Some(ElfError::BadEntry), Elf::parse(&data).err());
+    }
+}
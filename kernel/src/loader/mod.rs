@@ -0,0 +1,322 @@
+//! Program loader
+//!
+//! Runs statically linked ELF64 executables in user mode. Every program gets a new
+//! [address space](AddressSpace) where its loadable segments are mapped with the permissions they
+//! request, and a thread that switches to it and jumps to the program entry point.
+//!
+//! The initial stack follows the System V ABI. From the stack pointer upwards:
+//! - `argc`.
+//! - `argv` pointers, ended by a null pointer.
+//! - `envp` pointers, ended by a null pointer.
+//! - Auxiliary vector: pairs of type and value, ended by `AT_NULL`.
+//! - Padding, and the argument and environment strings at the top.
+//!
+//! For more info:
+//! https://refspecs.linuxbase.org/elf/x86_64-abi-0.99.pdf (section 3.4)
+pub mod elf;
+
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::memory::paging::page_table::PageTableEntryFlags;
+
+use crate::arch::x86_64::{enter_user_mode, no_execute_enabled};
+use crate::memory::address_space::{AddressSpace, AddressSpaceError, PAGE_SIZE};
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::thread::{self, JoinHandle, ThreadError};
+use elf::{Elf, ElfError, PF_W, PF_X, PROGRAM_HEADER_SIZE};
+
+/// Top address of the user stack. The page above it is left unmapped.
+const USER_STACK_TOP: u64 = 0x0000_7fff_ffff_f000;
+
+/// Size of the user stack.
+const USER_STACK_SIZE: u64 = 16 * PAGE_SIZE;
+
+/// Maximum size of the arguments, environment and auxiliary vector in the initial stack.
+const ARGUMENTS_MAX_SIZE: usize = 2048;
+
+/// Maximum number of programs loaded but not started yet.
+const MAX_PENDING_PROGRAMS: usize = 4;
+
+/// Auxiliary vector types.
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_ENTRY: u64 = 9;
+
+/// Programs waiting for their thread to start them.
+static PENDING_PROGRAMS: IrqSafeMutex<[Option<Program>; MAX_PENDING_PROGRAMS]> =
+    IrqSafeMutex::new([const { None }; MAX_PENDING_PROGRAMS]);
+
+/// Represents all the possible errors that can happen when loading a program.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoaderError {
+    /// The file is not a valid executable.
+    Elf(ElfError),
+
+    /// The address space of the program could not be built.
+    AddressSpace(AddressSpaceError),
+
+    /// The arguments and the environment do not fit in the initial stack.
+    ArgumentsTooLarge,
+
+    /// There are too many programs waiting to be started.
+    TooManyPrograms,
+
+    /// The thread of the program could not be spawned.
+    Thread(ThreadError),
+}
+
+/// A program loaded in its address space, ready to be started.
+pub struct Program {
+    address_space: AddressSpace,
+    entry: u64,
+    stack_pointer: u64,
+}
+
+impl Program {
+    /// Returns the address space of the program.
+    pub fn address_space(&self) -> &AddressSpace {
+        &self.address_space
+    }
+
+    /// Returns the address of the first instruction of the program.
+    pub fn entry(&self) -> u64 {
+        self.entry
+    }
+
+    /// Returns the initial stack pointer of the program.
+    pub fn stack_pointer(&self) -> u64 {
+        self.stack_pointer
+    }
+}
+
+/// Builds the initial stack of a program in `buffer`, whose end is mapped at `top`. Returns the
+/// initial stack pointer, 16 bytes aligned.
+///
+/// # Arguments
+/// * `buffer` - Top part of the stack.
+/// * `top` - Top address of the stack.
+/// * `arguments` - Arguments of the program.
+/// * `environment` - Environment variables of the program, as `NAME=value`.
+/// * `auxiliary_vector` - Auxiliary vector entries, without the final `AT_NULL`.
+fn build_stack(
+    buffer: &mut [u8],
+    top: u64,
+    arguments: &[&str],
+    environment: &[&str],
+    auxiliary_vector: &[(u64, u64)],
+) -> Result<u64, LoaderError> {
+    let strings_size: usize = arguments
+        .iter()
+        .chain(environment)
+        .map(|string| string.len() + 1)
+        .sum();
+    let words = 1 + arguments.len() + 1 + environment.len() + 1 + 2 * (auxiliary_vector.len() + 1);
+    if strings_size + words * 8 + 15 > buffer.len() {
+        return Err(LoaderError::ArgumentsTooLarge);
+    }
+
+    let strings_start = top - strings_size as u64;
+    let stack_pointer = (strings_start - (words * 8) as u64) & !15;
+    let base = buffer.len() - (top - stack_pointer) as usize;
+    let index = |address: u64| base + (address - stack_pointer) as usize;
+
+    let mut word_address = stack_pointer;
+    let mut push = |buffer: &mut [u8], value: u64| {
+        let start = index(word_address);
+        buffer[start..start + 8].copy_from_slice(&value.to_le_bytes());
+        word_address += 8;
+    };
+
+    push(buffer, arguments.len() as u64);
+    let mut string_address = strings_start;
+    for strings in [arguments, environment] {
+        for string in strings {
+            let start = index(string_address);
+            buffer[start..start + string.len()].copy_from_slice(string.as_bytes());
+            buffer[start + string.len()] = 0;
+            push(buffer, string_address);
+            string_address += string.len() as u64 + 1;
+        }
+        push(buffer, 0);
+    }
+    for &(kind, value) in auxiliary_vector.iter().chain(&[(AT_NULL, 0)]) {
+        push(buffer, kind);
+        push(buffer, value);
+    }
+
+    Ok(stack_pointer)
+}
+
+/// Loads an executable into a new address space: its loadable segments, with `.bss` zeroed, and
+/// a user stack with the arguments, the environment and the auxiliary vector.
+///
+/// # Arguments
+/// * `data` - Content of the ELF file.
+/// * `arguments` - Arguments of the program. By convention, the first one is its name.
+/// * `environment` - Environment variables of the program, as `NAME=value`.
+pub fn load(data: &[u8], arguments: &[&str], environment: &[&str]) -> Result<Program, LoaderError> {
+    let elf = Elf::parse(data).map_err(LoaderError::Elf)?;
+    let mut address_space = AddressSpace::new().map_err(LoaderError::AddressSpace)?;
+    let no_execute = if no_execute_enabled() {
+        PageTableEntryFlags::NO_EXECUTE
+    } else {
+        0
+    };
+
+    for segment in elf.load_segments() {
+        let mut flags = 0;
+        if segment.flags & PF_W != 0 {
+            flags |= PageTableEntryFlags::WRITABLE;
+        }
+        if segment.flags & PF_X == 0 {
+            flags |= no_execute;
+        }
+
+        let (start, end) = segment.pages();
+        for page in (start..end).step_by(PAGE_SIZE as usize) {
+            address_space
+                .map_user_page(page, flags)
+                .map_err(LoaderError::AddressSpace)?;
+        }
+
+        // Pages shared with other segments may have content, so `.bss` is zeroed explicitly
+        let bss = segment.virtual_address + segment.file_size;
+        address_space
+            .write(segment.virtual_address, elf.segment_data(&segment))
+            .and_then(|_| {
+                address_space.zero(bss, (segment.memory_size - segment.file_size) as usize)
+            })
+            .map_err(LoaderError::AddressSpace)?;
+    }
+
+    for page in (USER_STACK_TOP - USER_STACK_SIZE..USER_STACK_TOP).step_by(PAGE_SIZE as usize) {
+        address_space
+            .map_user_page(page, PageTableEntryFlags::WRITABLE | no_execute)
+            .map_err(LoaderError::AddressSpace)?;
+    }
+
+    let program_headers = elf.program_headers_address();
+    let auxiliary_vector = [
+        (AT_PHDR, program_headers.unwrap_or(0)),
+        (AT_PHENT, PROGRAM_HEADER_SIZE as u64),
+        (AT_PHNUM, elf.program_headers_count() as u64),
+        (AT_PAGESZ, PAGE_SIZE),
+        (AT_ENTRY, elf.entry()),
+    ];
+    // AT_PHDR is only given if the program headers are loaded
+    let auxiliary_vector = match program_headers {
+        Some(_) => &auxiliary_vector[..],
+        None => &auxiliary_vector[1..],
+    };
+
+    let mut stack = [0u8; ARGUMENTS_MAX_SIZE];
+    let stack_pointer = build_stack(
+        &mut stack,
+        USER_STACK_TOP,
+        arguments,
+        environment,
+        auxiliary_vector,
+    )?;
+    let used = (USER_STACK_TOP - stack_pointer) as usize;
+    address_space
+        .write(stack_pointer, &stack[stack.len() - used..])
+        .map_err(LoaderError::AddressSpace)?;
+
+    Ok(Program {
+        address_space,
+        entry: elf.entry(),
+        stack_pointer,
+    })
+}
+
+/// Loads an executable and starts it in user mode, in a new thread. The exit code of the thread
+/// is the one the program passes to the `exit` system call.
+///
+/// # Arguments
+/// * `data` - Content of the ELF file.
+/// * `arguments` - Arguments of the program. By convention, the first one is its name.
+/// * `environment` - Environment variables of the program, as `NAME=value`.
+pub fn spawn(
+    data: &[u8],
+    arguments: &[&str],
+    environment: &[&str],
+) -> Result<JoinHandle, LoaderError> {
+    let program = load(data, arguments, environment)?;
+
+    let slot = {
+        let mut programs = PENDING_PROGRAMS.lock();
+        let slot = programs
+            .iter()
+            .position(Option::is_none)
+            .ok_or(LoaderError::TooManyPrograms)?;
+        programs[slot] = Some(program);
+        slot
+    };
+
+    thread::spawn(start_program, slot).map_err(|error| {
+        PENDING_PROGRAMS.lock()[slot] = None;
+        LoaderError::Thread(error)
+    })
+}
+
+/// Thread function of a program: switches to its address space and jumps to its entry point.
+///
+/// # Arguments
+/// * `slot` - Slot of the program in the pending programs table.
+fn start_program(slot: usize) -> usize {
+    let program = PENDING_PROGRAMS.lock()[slot]
+        .take()
+        .expect("the program of a new thread is missing");
+
+    // The address space is never freed, it lives as long as the thread (and longer)
+    unsafe {
+        thread::set_address_space(program.address_space.level_4_table());
+        enter_user_mode(
+            VirtualMemoryAddress::new(program.entry),
+            VirtualMemoryAddress::new(program.stack_pointer),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_word(buffer: &[u8], index: usize) -> u64 {
+        u64::from_le_bytes(buffer[index..index + 8].try_into().unwrap())
+    }
+
+    #[test_case]
+    fn initial_stack_follows_the_abi() {
+        let mut buffer = [0u8; 256];
+        let top = 0x1000;
+        let stack_pointer = build_stack(
+            &mut buffer,
+            top,
+            &["a", "bc"],
+            &["X=1"],
+            &[(AT_PAGESZ, 4096)],
+        )
+        .unwrap();
+
+        assert_eq!(0xfa0, stack_pointer);
+        let base = buffer.len() - (top - stack_pointer) as usize;
+        let words: [u64; 10] = core::array::from_fn(|index| read_word(&buffer, base + index * 8));
+        assert_eq!(
+            [2, 0xff7, 0xff9, 0, 0xffc, 0, AT_PAGESZ, 4096, AT_NULL, 0],
+            words
+        );
+        assert_eq!(b"a\0bc\0X=1\0", &buffer[buffer.len() - 9..]);
+    }
+
+    #[test_case]
+    fn too_large_arguments_are_rejected() {
+        let mut buffer = [0u8; 64];
+        assert_eq!(
+            Err(LoaderError::ArgumentsTooLarge),
+            build_stack(&mut buffer, 0x1000, &["a"; 8], &[], &[])
+        );
+    }
+}
@@ -12,7 +12,11 @@ use bootloader::BootInfo;
 #[no_mangle]
 #[cfg(not(test))]
 pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
-    use lil_os::arch::x86_64::TRANSLATOR;
+    use lil_os::arch::x86_64::{cpu, smp, TRANSLATOR};
+    use lil_os::drivers::input::keyboard::print_keypresses;
+    use lil_os::drivers::rtc;
+    use lil_os::task;
+    use lil_os::time::{clock_source, wall_clock};
     use lil_os::{
         arch::x86_64::initialize_x86_64_arch, os_core::messages::init_with_message, println,
     };
@@ -23,10 +27,30 @@ pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
 
     let physical_memory_offset = VirtualMemoryAddress::new(boot_info.physical_memory_offset);
 
+    init_with_message("processor identification", cpu::initialize);
+
     init_with_message("x86_64 architecture", || {
         initialize_x86_64_arch(physical_memory_offset)
     });
 
+    init_with_message("memory", || {
+        lil_os::memory::initialize(&boot_info.memory_map)
+    });
+
+    let mut smp_result = Ok(());
+    init_with_message("application processors", || smp_result = smp::initialize());
+    if let Err(error) = smp_result {
+        println!("Could not start every application processor: {:?}", error);
+    }
+    println!("Processors online: {}", smp::per_cpu::online_count());
+
+    init_with_message("wall clock", || {
+        wall_clock::synchronize(rtc::read());
+        rtc::enable_update_interrupt();
+    });
+    println!("Current date and time: {}", wall_clock::now());
+    println!("Clock source: {}", clock_source::clock_source().name());
+
     /*
     println!("Translated address: {:?}", unsafe {
         TRANSLATOR.translate_address(VirtualMemoryAddress::new(0xb8000))
@@ -63,12 +87,12 @@ pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
 
     test_map(physical_memory_offset);
 
-    #[allow(clippy::empty_loop)]
-    loop {
-        // Halts the CPU until the next interrupt hits. This prevents the CPU to spin endessly
-        // and waste cycles doing nothing.
-        x86_64::instructions::hlt();
-    }
+    task::spawn(print_keypresses()).expect("failed to spawn the keyboard task");
+
+    // Run the tasks and the work deferred by the interrupt handlers. When there is nothing to do
+    // the CPU is halted until the next interrupt hits. This prevents the CPU to spin endessly and
+    // waste cycles doing nothing.
+    task::run()
 }
 
 /// This function is called on panic
@@ -0,0 +1,284 @@
+//! User address spaces
+//!
+//! Every user program gets its own PML4. Its entries start as a copy of the kernel PML4, so the
+//! kernel is mapped in every address space (not accessible from user mode), and the pages of the
+//! program are added on top of them.
+//!
+//! The kernel lives in the lower half, so the page tables of a user page can be shared with the
+//! kernel. A shared intermediate table (one without the user flag) is never modified: it is
+//! cloned into a new frame first, and only the clone is modified. The entries of the clone still
+//! point to the kernel tables, so changes done later by the kernel in the lower levels are seen
+//! by the address space, but changes in the cloned level are not.
+//!
+//! Address spaces are never freed yet: their frames are leaked like every other frame.
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use x86_64_custom::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+use x86_64_custom::memory::paging::page_table::{
+    PageTable, PageTableEntry, PageTableEntryFlags, PageTableLevel,
+};
+use x86_64_custom::registers::control::Cr3;
+
+use super::frame_allocator::allocate_frame;
+use crate::arch::x86_64::physical_memory_offset;
+
+/// First address after the user part of the address space. It is one page below the end of the
+/// lower half: a `syscall` in the last page would return to a non-canonical address, and Intel
+/// processors raise the #GP of such a `sysret` in ring 0, with the user stack pointer already
+/// loaded.
+pub const USER_SPACE_END: u64 = 0x0000_7fff_ffff_f000;
+
+/// Size of a page.
+pub const PAGE_SIZE: u64 = 4096;
+
+/// Bits of a page table entry that hold the physical address.
+const ADDRESS_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+/// Physical address of the kernel PML4, zero until [initialize] is called.
+static KERNEL_LEVEL_4_TABLE: AtomicU64 = AtomicU64::new(0);
+
+/// Represents all the possible errors that can happen when building an address space.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressSpaceError {
+    /// The memory is not initialized yet.
+    NotInitialized,
+
+    /// There are no free frames left.
+    OutOfMemory,
+
+    /// The address does not belong to the user half of the address space.
+    OutOfUserSpace,
+
+    /// The page is already mapped by the kernel.
+    AlreadyMapped,
+
+    /// The page is not mapped as a user page.
+    NotMapped,
+}
+
+/// Saves the current PML4 as the kernel one. Every address space created afterwards copies its
+/// entries.
+pub(super) fn initialize() {
+    KERNEL_LEVEL_4_TABLE.store(Cr3::read().as_u64(), Ordering::Relaxed);
+}
+
+/// Returns a mutable reference to the page table stored in the given frame.
+///
+/// # Arguments
+/// * `address` - Physical address of the page table.
+///
+/// # Safety
+/// The frame must hold a page table and nothing else can be referencing it.
+unsafe fn page_table(
+    address: PhysicalMemoryAddress,
+) -> Result<&'static mut PageTable, AddressSpaceError> {
+    let offset = physical_memory_offset().ok_or(AddressSpaceError::NotInitialized)?;
+    Ok(&mut *(offset + address).as_mut_ptr())
+}
+
+/// Allocates a frame and fills it with zeros.
+fn allocate_zeroed_frame() -> Result<PhysicalMemoryAddress, AddressSpaceError> {
+    let offset = physical_memory_offset().ok_or(AddressSpaceError::NotInitialized)?;
+    let frame = allocate_frame().ok_or(AddressSpaceError::OutOfMemory)?;
+    unsafe { core::ptr::write_bytes((offset + frame).as_mut_ptr::<u8>(), 0, PAGE_SIZE as usize) };
+    Ok(frame)
+}
+
+/// Returns the table indexes of a virtual address, from the level 4 to the level 1.
+///
+/// # Arguments
+/// * `address` - Virtual address.
+fn table_indexes(address: u64) -> [usize; 4] {
+    let address = VirtualMemoryAddress::new(address);
+    [
+        PageTableLevel::Level4,
+        PageTableLevel::Level3,
+        PageTableLevel::Level2,
+        PageTableLevel::Level1,
+    ]
+    .map(|level| address.get_page_table_index(level))
+}
+
+/// An address space for user code.
+pub struct AddressSpace {
+    /// Physical address of the PML4.
+    level_4_table: PhysicalMemoryAddress,
+}
+
+impl AddressSpace {
+    /// Creates an address space with only the kernel mapped.
+    pub fn new() -> Result<Self, AddressSpaceError> {
+        let kernel_table = match KERNEL_LEVEL_4_TABLE.load(Ordering::Relaxed) {
+            0 => return Err(AddressSpaceError::NotInitialized),
+            address => PhysicalMemoryAddress::new(address),
+        };
+        let level_4_table = allocate_zeroed_frame()?;
+
+        unsafe {
+            let kernel_table = page_table(kernel_table)?;
+            let table = page_table(level_4_table)?;
+            for (index, entry) in kernel_table.iter().enumerate() {
+                table[index] = PageTableEntry::new(**entry & !ADDRESS_MASK, entry.address());
+            }
+        }
+
+        Ok(Self { level_4_table })
+    }
+
+    /// Returns the physical address of the PML4.
+    pub fn level_4_table(&self) -> PhysicalMemoryAddress {
+        self.level_4_table
+    }
+
+    /// Maps a user page to a new frame filled with zeros. If the page was already mapped by this
+    /// address space, the frame is kept and the flags are merged: the page is writable if any of
+    /// the mappings is writable, and executable if any of them is executable.
+    ///
+    /// The user accessible and present flags are always added.
+    ///
+    /// # Arguments
+    /// * `page` - Start address of the page.
+    /// * `flags` - Flags of the page.
+    pub fn map_user_page(&mut self, page: u64, flags: u64) -> Result<(), AddressSpaceError> {
+        if page >= USER_SPACE_END {
+            return Err(AddressSpaceError::OutOfUserSpace);
+        }
+
+        let intermediate_flags = PageTableEntryFlags::PRESENT
+            | PageTableEntryFlags::WRITABLE
+            | PageTableEntryFlags::USER_ACCESSIBLE;
+        let [level_4, level_3, level_2, level_1] = table_indexes(page);
+
+        let mut table = unsafe { page_table(self.level_4_table)? };
+        for index in [level_4, level_3, level_2] {
+            let entry = &mut table[index];
+            if !entry.is_present() {
+                *entry = PageTableEntry::new(intermediate_flags, allocate_zeroed_frame()?);
+            } else if entry.is_huge() {
+                return Err(AddressSpaceError::AlreadyMapped);
+            } else if **entry & PageTableEntryFlags::USER_ACCESSIBLE == 0 {
+                // Shared with the kernel, we must not modify it
+                let clone = allocate_zeroed_frame()?;
+                unsafe {
+                    let source = page_table(entry.address())?;
+                    let destination = page_table(clone)?;
+                    for (index, entry) in source.iter().enumerate() {
+                        destination[index] =
+                            PageTableEntry::new(**entry & !ADDRESS_MASK, entry.address());
+                    }
+                }
+                let entry_flags = (**entry & !ADDRESS_MASK & !PageTableEntryFlags::NO_EXECUTE)
+                    | intermediate_flags;
+                *entry = PageTableEntry::new(entry_flags, clone);
+            }
+            table = unsafe { page_table(entry.address())? };
+        }
+
+        let flags = flags | PageTableEntryFlags::PRESENT | PageTableEntryFlags::USER_ACCESSIBLE;
+        let entry = &mut table[level_1];
+        if !entry.is_present() {
+            *entry = PageTableEntry::new(flags, allocate_zeroed_frame()?);
+        } else if **entry & PageTableEntryFlags::USER_ACCESSIBLE == 0 {
+            return Err(AddressSpaceError::AlreadyMapped);
+        } else {
+            let old_flags = **entry & !ADDRESS_MASK;
+            let no_execute = old_flags & flags & PageTableEntryFlags::NO_EXECUTE;
+            let merged = ((old_flags | flags) & !PageTableEntryFlags::NO_EXECUTE) | no_execute;
+            *entry = PageTableEntry::new(merged, entry.address());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the frame of a page mapped with [map_user_page](Self::map_user_page).
+    ///
+    /// # Arguments
+    /// * `page` - Start address of the page.
+    fn user_frame(&self, page: u64) -> Result<PhysicalMemoryAddress, AddressSpaceError> {
+        if page >= USER_SPACE_END {
+            return Err(AddressSpaceError::OutOfUserSpace);
+        }
+
+        let mut address = self.level_4_table;
+        for index in table_indexes(page) {
+            let entry = &unsafe { page_table(address)? }[index];
+            if !entry.is_present()
+                || entry.is_huge()
+                || **entry & PageTableEntryFlags::USER_ACCESSIBLE == 0
+            {
+                return Err(AddressSpaceError::NotMapped);
+            }
+            address = entry.address();
+        }
+
+        Ok(address)
+    }
+
+    /// Copies bytes to user pages of the address space, even if they are not writable. The
+    /// address space does not need to be active.
+    ///
+    /// # Arguments
+    /// * `address` - User address of the first byte.
+    /// * `bytes` - Bytes to copy.
+    pub fn write(&self, address: u64, bytes: &[u8]) -> Result<(), AddressSpaceError> {
+        self.for_each_chunk(address, bytes.len(), |destination, start| {
+            let source = &bytes[start..start + destination.len()];
+            destination.copy_from_slice(source);
+        })
+    }
+
+    /// Fills a range of user pages of the address space with zeros, even if they are not
+    /// writable.
+    ///
+    /// # Arguments
+    /// * `address` - User address of the first byte.
+    /// * `len` - Number of bytes to fill.
+    pub fn zero(&self, address: u64, len: usize) -> Result<(), AddressSpaceError> {
+        self.for_each_chunk(address, len, |destination, _| destination.fill(0))
+    }
+
+    /// Calls `function` with the part of every page that belongs to the range `[address, address +
+    /// len)`, and the offset of that part in the range.
+    fn for_each_chunk(
+        &self,
+        address: u64,
+        len: usize,
+        mut function: impl FnMut(&mut [u8], usize),
+    ) -> Result<(), AddressSpaceError> {
+        let offset = physical_memory_offset().ok_or(AddressSpaceError::NotInitialized)?;
+        address
+            .checked_add(len as u64)
+            .filter(|&end| end <= USER_SPACE_END)
+            .ok_or(AddressSpaceError::OutOfUserSpace)?;
+
+        let mut done = 0;
+        while done < len {
+            let current = address + done as u64;
+            let page_offset = current % PAGE_SIZE;
+            let chunk_len = (len - done).min((PAGE_SIZE - page_offset) as usize);
+            let frame = self.user_frame(current - page_offset)?;
+
+            let destination = unsafe {
+                core::slice::from_raw_parts_mut(
+                    (offset + frame)
+                        .as_mut_ptr::<u8>()
+                        .add(page_offset as usize),
+                    chunk_len,
+                )
+            };
+            function(destination, done);
+            done += chunk_len;
+        }
+
+        Ok(())
+    }
+
+    /// Switches to this address space.
+    ///
+    /// # Safety
+    /// The caller must be sure that nothing accesses the user pages of the previous address space
+    /// afterwards.
+    pub unsafe fn activate(&self) {
+        Cr3::write(self.level_4_table)
+    }
+}
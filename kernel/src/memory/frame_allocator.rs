@@ -1,13 +1,127 @@
+//! Physical frame allocator
+//!
+//! The bootloader gives us a map of the physical memory, telling which regions are free to use.
+//! Frames are handed out from those regions in increasing address order, keeping only the
+//! address of the next candidate frame. There is no way to free a frame yet, so every allocated
+//! frame is leaked.
+use bootloader::bootinfo::{MemoryRegion, MemoryRegionType};
 use x86_64_custom::memory::{
+    address::PhysicalMemoryAddress,
     frame_allocator::{self, PhysicalFrame},
     paging::page_size::Size4KiB,
 };
 
-/// struct used for implementing the frame allocator
-struct FrameAllocator;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
 
-impl frame_allocator::FrameAllocator<Size4KiB> for FrameAllocator {
+/// Size of a frame.
+const FRAME_SIZE: u64 = 4096;
+
+/// Allocator of the whole physical memory. It has no frames until it is initialized.
+static FRAME_ALLOCATOR: IrqSafeMutex<BootFrameAllocator<'static>> =
+    IrqSafeMutex::new(BootFrameAllocator::new(&[]));
+
+/// Allocator of the usable frames of a memory map.
+struct BootFrameAllocator<'a> {
+    /// Memory map, sorted by address.
+    regions: &'a [MemoryRegion],
+
+    /// Lowest address that can be returned by the next allocation.
+    next: u64,
+
+    /// Number of frames allocated.
+    allocated: u64,
+}
+
+impl<'a> BootFrameAllocator<'a> {
+    /// Creates an allocator for the usable regions of a memory map.
+    ///
+    /// # Arguments
+    /// * `regions` - Memory map, sorted by address.
+    const fn new(regions: &'a [MemoryRegion]) -> Self {
+        Self {
+            regions,
+            // The frame zero is never handed out, a null physical address is usually a bug
+            next: FRAME_SIZE,
+            allocated: 0,
+        }
+    }
+
+    /// Returns the address of a free frame, or `None` if all the usable memory was handed out.
+    fn allocate(&mut self) -> Option<PhysicalMemoryAddress> {
+        let next = self.next;
+        let frame = self
+            .regions
+            .iter()
+            .filter(|region| region.region_type == MemoryRegionType::Usable)
+            .map(|region| (next.max(region.range.start_addr()), region.range.end_addr()))
+            .find(|&(start, end)| start.saturating_add(FRAME_SIZE) <= end)
+            .map(|(start, _)| start)?;
+
+        self.next = frame + FRAME_SIZE;
+        self.allocated += 1;
+        Some(PhysicalMemoryAddress::new(frame))
+    }
+}
+
+/// Gives the memory map to the frame allocator. Until it is called, every allocation fails.
+///
+/// # Arguments
+/// * `regions` - Memory map given by the bootloader, sorted by address.
+pub fn initialize(regions: &'static [MemoryRegion]) {
+    *FRAME_ALLOCATOR.lock() = BootFrameAllocator::new(regions);
+}
+
+/// Allocates a 4KiB physical frame. Its content is undefined.
+pub fn allocate_frame() -> Option<PhysicalMemoryAddress> {
+    FRAME_ALLOCATOR.lock().allocate()
+}
+
+/// Returns the number of frames allocated so far.
+pub fn allocated_frames() -> u64 {
+    FRAME_ALLOCATOR.lock().allocated
+}
+
+/// Frame allocator that hands out frames from the global allocator.
+pub struct GlobalFrameAllocator;
+
+impl frame_allocator::FrameAllocator<Size4KiB> for GlobalFrameAllocator {
     unsafe fn allocate(&self) -> Option<PhysicalFrame<Size4KiB>> {
-        None
+        allocate_frame().map(PhysicalFrame::new)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bootloader::bootinfo::FrameRange;
+
+    fn region(start: u64, end: u64, region_type: MemoryRegionType) -> MemoryRegion {
+        MemoryRegion {
+            range: FrameRange::new(start, end),
+            region_type,
+        }
+    }
+
+    #[test_case]
+    fn only_usable_frames_are_allocated() {
+        let regions = [
+            region(0x0000, 0x2000, MemoryRegionType::Usable),
+            region(0x2000, 0x4000, MemoryRegionType::Kernel),
+            region(0x4000, 0x5000, MemoryRegionType::Usable),
+            region(0x5000, 0x6000, MemoryRegionType::Reserved),
+        ];
+        let mut allocator = BootFrameAllocator::new(&regions);
+
+        // The frame zero is skipped
+        assert_eq!(
+            Some(0x1000),
+            allocator.allocate().map(|frame| frame.as_u64())
+        );
+        assert_eq!(
+            Some(0x4000),
+            allocator.allocate().map(|frame| frame.as_u64())
+        );
+        assert_eq!(None, allocator.allocate().map(|frame| frame.as_u64()));
+        assert_eq!(2, allocator.allocated);
     }
 }
@@ -1,7 +1,20 @@
+pub mod address_space;
 pub mod allocator;
 pub mod frame_allocator;
 pub mod volatile;
 
+use bootloader::bootinfo::MemoryRegion;
+
+/// Initializes the physical memory management: the frame allocator gets the usable regions of
+/// the memory map and the current address space is saved as the kernel one.
+///
+/// # Arguments
+/// * `regions` - Memory map given by the bootloader.
+pub fn initialize(regions: &'static [MemoryRegion]) {
+    frame_allocator::initialize(regions);
+    address_space::initialize();
+}
+
 /*
 // TODO: BELOW IS JUST FOR DEBUGGING, REMOVE!
 use x86_64_custom::{
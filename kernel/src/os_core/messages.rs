@@ -1,11 +1,29 @@
 // TODO: Maybe this module should not be named like this
+use core::fmt::Display;
+
 use crate::{print, println, PrintColor};
 
+/// Value returned by an initialization step. Steps that have something to tell (like what they
+/// found) return it, and it is printed after the "OK".
+pub trait InitDetails {
+    /// Prints the details of the step, if any.
+    fn print_details(&self) {}
+}
+
+impl InitDetails for () {}
+
+impl<T: Display + ?Sized> InitDetails for &T {
+    fn print_details(&self) {
+        println!("  {}", self);
+    }
+}
+
 // TODO:
 // init_fn should be FnOnce() -> Result<...>
 // handle error cases!
-pub fn init_with_message(what: &str, init_fn: impl FnOnce()) {
+pub fn init_with_message<R: InitDetails>(what: &str, init_fn: impl FnOnce() -> R) {
     print!("Initializing {what}...");
-    init_fn();
+    let details = init_fn();
     println!([PrintColor::Green], " OK");
+    details.print_details();
 }
@@ -0,0 +1,128 @@
+//! This module implements a condition variable
+//!
+//! Condition variables represent the ability to block a process such that it consumes no CPU time
+//! while waiting for an event to occur. They are always used together with a mutex that protects
+//! the state the event depends on: the waiter releases the mutex while it sleeps and acquires it
+//! again before checking the state.
+//!
+//! Both [Mutex](super::spinlock::Mutex) and [IrqSafeMutex](super::irq_safe_mutex::IrqSafeMutex)
+//! guards can be used. Data shared with interrupt handlers must be protected by an
+//! `IrqSafeMutex`; the handler can then change it and notify the condition variable.
+//!
+//! P.S: Most documentation comments are taken from
+//! https://doc.rust-lang.org/std/sync/struct.Condvar.html
+use core::ops::DerefMut;
+
+use super::wait_queue::WaitQueue;
+
+/// A lock guard that a condition variable can release while waiting.
+pub trait CondvarGuard: DerefMut + Sized {
+    /// Releases the lock, calls `wait` and acquires the lock again.
+    ///
+    /// # Arguments
+    /// * `wait` - Function that blocks until the condition variable is notified.
+    fn unlock_and_wait(self, wait: impl FnOnce()) -> Self;
+}
+
+/// A Condition Variable.
+pub struct Condvar {
+    waiters: WaitQueue,
+}
+
+impl Condvar {
+    /// Creates a new condition variable which is ready to be waited on and notified.
+    pub const fn new() -> Self {
+        Self {
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Blocks the current process until this condition variable receives a notification.
+    ///
+    /// This function will atomically unlock the mutex specified (represented by `guard`) and block
+    /// the current process. This means that any calls to notify which happen logically after the
+    /// mutex is unlocked are candidates to wake this process up. When this function call returns,
+    /// the lock specified will have been re-acquired.
+    ///
+    /// Note that this function is susceptible to spurious wakeups. Condition variables normally
+    /// have a boolean predicate associated with them, and the predicate must always be checked
+    /// each time this function returns to protect against spurious wakeups.
+    ///
+    /// # Arguments
+    /// * `guard` - Guard of the mutex that protects the waited state.
+    pub fn wait<G: CondvarGuard>(&self, guard: G) -> G {
+        // Read before unlocking, so a notification sent right after the unlock is not lost
+        let seen = self.waiters.notifications();
+        guard.unlock_and_wait(|| self.waiters.wait_for_notification(seen))
+    }
+
+    /// Blocks the current process while `condition` returns true.
+    ///
+    /// The condition is checked with the mutex locked, before blocking and every time this
+    /// condition variable is notified.
+    ///
+    /// # Arguments
+    /// * `guard` - Guard of the mutex that protects the waited state.
+    /// * `condition` - Condition that must be false to stop waiting.
+    pub fn wait_while<G: CondvarGuard>(
+        &self,
+        mut guard: G,
+        mut condition: impl FnMut(&mut G::Target) -> bool,
+    ) -> G {
+        while condition(&mut guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Wakes up one blocked process on this condvar.
+    ///
+    /// Calls to `notify_one` are not buffered in any way.
+    pub fn notify_one(&self) {
+        self.waiters.notify_one();
+    }
+
+    /// Wakes up all blocked processes on this condvar.
+    pub fn notify_all(&self) {
+        self.waiters.notify_all();
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+    use crate::synchronization::spinlock::Mutex;
+
+    #[test_case]
+    fn wait_while_false_condition_does_not_wait() {
+        let mutex = Mutex::new(1);
+        let condvar = Condvar::new();
+
+        let guard = condvar.wait_while(mutex.lock(), |value| *value == 0);
+        assert_eq!(1, *guard);
+    }
+
+    #[test_case]
+    fn wait_releases_and_reacquires_the_lock() {
+        let mutex = IrqSafeMutex::new(0);
+        let condvar = Condvar::new();
+
+        // The "notifier" changes the state between the first check and the wait
+        let guard = condvar.wait_while(mutex.lock(), |value| {
+            *value += 1;
+            condvar.notify_one();
+            *value < 2
+        });
+        assert_eq!(2, *guard);
+        drop(guard);
+
+        assert!(mutex.try_lock().is_ok());
+    }
+}
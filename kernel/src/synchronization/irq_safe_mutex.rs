@@ -0,0 +1,192 @@
+//! This module implements a spinlock that disables the interrupts while it is held
+//!
+//! A regular spinlock shared between the kernel and an interrupt handler can deadlock: if the
+//! interrupt fires while the kernel holds the lock, the handler spins forever waiting for a lock
+//! that will never be released, because the kernel code can not continue until the handler
+//! returns.
+//!
+//! `IrqSafeMutex` avoids it by disabling the interrupts before taking the lock. The state of the
+//! interrupt flag (RFLAGS.IF) is saved in the guard and restored when it is dropped, so nested
+//! locks (or locks taken inside interrupt handlers, where the interrupts are already disabled) do
+//! not enable the interrupts too early.
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+// TODO: Disabling the interrupts is specific for x86 at the moment, if we are going to support
+// more architectures, we need to refactor this.
+use x86_64::instructions::interrupts;
+
+use super::condvar::CondvarGuard;
+use super::spinlock::{Mutex, MutexError, MutexGuard};
+
+/// A mutual exclusion primitive that disables the interrupts while the lock is held.
+///
+/// It must be used for all the data shared with interrupt handlers.
+pub struct IrqSafeMutex<T> {
+    mutex: Mutex<T>,
+}
+
+impl<T> IrqSafeMutex<T> {
+    /// Creates a new mutex in an unlocked state ready for use.
+    pub const fn new(data: T) -> Self {
+        Self {
+            mutex: Mutex::new(data),
+        }
+    }
+
+    /// Attempts to acquire this lock.
+    ///
+    /// If the lock could not be acquired at this time, then
+    /// [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is returned and
+    /// the interrupts are left as they were. Otherwise, the interrupts are disabled and an RAII
+    /// guard is returned. The lock will be unlocked and the interrupts restored when the guard is
+    /// dropped.
+    ///
+    /// This function does not block.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn try_lock(&self) -> Result<IrqSafeMutexGuard<'_, T>, MutexError> {
+        let interrupts_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        match self.mutex.try_lock() {
+            Ok(guard) => Ok(IrqSafeMutexGuard {
+                guard: ManuallyDrop::new(guard),
+                interrupts_enabled,
+            }),
+            Err(error) => {
+                if interrupts_enabled {
+                    interrupts::enable();
+                }
+                Err(error)
+            }
+        }
+    }
+
+    /// Disables the interrupts and acquires the mutex, blocking until it is able to do so.
+    ///
+    /// An RAII guard is returned to allow scoped unlock of the lock. When the guard goes out of
+    /// scope, the mutex will be unlocked and the interrupts restored.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
+    pub fn lock(&self) -> IrqSafeMutexGuard<'_, T> {
+        let interrupts_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        IrqSafeMutexGuard {
+            guard: ManuallyDrop::new(self.mutex.lock()),
+            interrupts_enabled,
+        }
+    }
+
+    /// Immediately drops the guard, and consequently unlocks the mutex and restores the
+    /// interrupts.
+    ///
+    /// This function is equivalent to calling drop on the guard but is more self-documenting.
+    /// Alternately, the guard will be automatically dropped when it goes out of scope.
+    pub fn unlock(guard: IrqSafeMutexGuard<'_, T>) {
+        drop(guard)
+    }
+}
+
+/// An RAII implementation of a "scoped lock" of an interrupt safe mutex. When this structure is
+/// dropped (falls out of scope), the lock will be unlocked and the interrupts will be enabled
+/// again if they were enabled when the lock was acquired.
+///
+/// This structure is created by the lock and try_lock methods on
+/// [IrqSafeMutex](IrqSafeMutex).
+pub struct IrqSafeMutexGuard<'mutex, T: 'mutex> {
+    guard: ManuallyDrop<MutexGuard<'mutex, T>>,
+
+    /// Whether the interrupts were enabled before taking the lock.
+    interrupts_enabled: bool,
+}
+
+impl<'mutex, T> Deref for IrqSafeMutexGuard<'mutex, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'mutex, T> DerefMut for IrqSafeMutexGuard<'mutex, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<'mutex, T> Drop for IrqSafeMutexGuard<'mutex, T> {
+    fn drop(&mut self) {
+        // The lock must be released before enabling the interrupts, otherwise a handler could
+        // fire and spin on it
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+        if self.interrupts_enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+impl<'mutex, T> CondvarGuard for IrqSafeMutexGuard<'mutex, T> {
+    fn unlock_and_wait(mut self, wait: impl FnOnce()) -> Self {
+        let interrupts_enabled = self.interrupts_enabled;
+        let guard = unsafe { ManuallyDrop::take(&mut self.guard) };
+        core::mem::forget(self);
+
+        // Waiting enables the interrupts, they must be disabled again before taking the lock
+        let guard = guard.unlock_and_wait(|| {
+            wait();
+            interrupts::disable();
+        });
+
+        Self {
+            guard: ManuallyDrop::new(guard),
+            interrupts_enabled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: The tests run without an IDT, so we can not enable the interrupts here.
+
+    #[test_case]
+    fn write_and_read_ok() {
+        let mutex = IrqSafeMutex::new(42);
+        {
+            let mut value = mutex.lock();
+            *value = 43;
+        }
+
+        assert_eq!(43, *mutex.lock());
+    }
+
+    #[test_case]
+    fn lock_two_times_should_fail() {
+        let mutex = IrqSafeMutex::new(42);
+        let _l1 = mutex.lock();
+
+        assert!(mutex.try_lock().is_err());
+    }
+
+    #[test_case]
+    fn guard_should_release_lock() {
+        let mutex = IrqSafeMutex::new(42);
+        {
+            let _l1 = mutex.lock();
+            assert!(!interrupts::are_enabled());
+        }
+
+        assert!(mutex.try_lock().is_ok());
+    }
+
+    #[test_case]
+    fn guard_should_not_enable_disabled_interrupts() {
+        let mutex = IrqSafeMutex::new(42);
+        interrupts::disable();
+        {
+            let _l1 = mutex.lock();
+        }
+
+        assert!(!interrupts::are_enabled());
+    }
+}
@@ -0,0 +1,93 @@
+//! This module implements a lazily initialized value
+//!
+//! `Lazy` is a value that is initialized with a function the first time it is accessed. It
+//! allows declaring statics that can not be built in a constant context, without macros:
+//!
+//! static VALUE: Lazy<Mutex<Value>> = Lazy::new(|| Mutex::new(Value::new()));
+//!
+//! The initialization is done through a [Once](super::once::Once), so it inherits its
+//! guarantees: it runs only once and an initializer that accesses its own value panics instead of
+//! spinning forever.
+use core::cell::Cell;
+use core::ops::Deref;
+
+use super::once::Once;
+
+/// A value which is initialized on the first access.
+pub struct Lazy<T, F = fn() -> T> {
+    /// The initialized value.
+    cell: Once<T>,
+
+    /// The initializer, taken when it runs.
+    init: Cell<Option<F>>,
+}
+
+impl<T, F: FnOnce() -> T> Lazy<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    pub const fn new(init: F) -> Self {
+        Self {
+            cell: Once::new(),
+            init: Cell::new(Some(init)),
+        }
+    }
+
+    /// Forces the evaluation of this lazy value and returns a reference to the result. This is
+    /// equivalent to the `Deref` implementation, but is explicit.
+    ///
+    /// # Panics
+    ///
+    /// If it is called by the initializer.
+    pub fn force(this: &Self) -> &T {
+        this.cell.call_once(|| match this.init.take() {
+            Some(init) => init(),
+            None => unreachable!("the Once runs the initializer only once"),
+        })
+    }
+
+    /// Returns true if the value was already initialized.
+    pub fn is_initialized(this: &Self) -> bool {
+        this.cell.is_completed()
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for Lazy<T, F> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        Self::force(self)
+    }
+}
+
+// The initializer is only accessed by the process that wins the `Once` initialization
+unsafe impl<T: Send + Sync, F: Send> Sync for Lazy<T, F> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    static VALUE: Lazy<usize> = Lazy::new(|| {
+        CALLS.fetch_add(1, Ordering::Relaxed);
+        42
+    });
+
+    #[test_case]
+    fn lazy_initializes_on_first_access() {
+        assert!(!Lazy::is_initialized(&VALUE));
+
+        assert_eq!(42, *VALUE);
+        assert_eq!(42, *Lazy::force(&VALUE));
+        assert!(Lazy::is_initialized(&VALUE));
+        assert_eq!(1, CALLS.load(Ordering::Relaxed));
+    }
+
+    #[test_case]
+    fn lazy_with_closure_ok() {
+        let base = 40;
+        let lazy = Lazy::new(|| base + 2);
+
+        assert_eq!(42, *lazy);
+    }
+}
@@ -0,0 +1,147 @@
+//! Lock debugging
+//!
+//! When the `lock_debug` feature is enabled, every [Mutex](super::spinlock::Mutex) records who
+//! holds it: the location of the code that acquired it, and the CPU and the thread it was running
+//! on. With that information we can detect two common bugs that otherwise just hang the system:
+//!
+//! - Recursive locking: a lock is requested by the same thread, on the same CPU, that holds it
+//!   (for example, an interrupt handler that prints while the interrupted code was printing). The
+//!   holder can never release it, so this is reported and the processor is halted. It does not
+//!   panic: the panic handler prints, and the lock could be the one of the screen.
+//! - Possible deadlocks: a lock spins for longer than `SPIN_THRESHOLD` iterations. This is
+//!   reported once per lock attempt and the lock keeps spinning.
+//!
+//! The diagnostics are written directly to the serial port, bypassing its lock (that could be
+//! the one causing the problem).
+//!
+//! The holder is identified by the [Location] of the `lock` call, given by `#[track_caller]`,
+//! instead of a return address: Rust has no stable way to read the return address, and a source
+//! location does not need the symbols of the kernel to be read.
+//!
+//! The thread is needed because the mutexes only disable preemption: a thread that blocks or
+//! yields while holding a lock lets the next thread on the same CPU request it, and that one is
+//! not recursive. The CPU is needed because the application processors do not run threads, they
+//! would all look like the thread running on the bootstrap processor.
+use core::panic::Location;
+use core::ptr;
+use core::sync::atomic::{AtomicPtr, AtomicU32, AtomicUsize, Ordering};
+use x86_64_custom::cpuid;
+
+use crate::arch::x86_64::power;
+use crate::drivers::serial::_print_unlocked;
+use crate::thread;
+
+/// Number of spin iterations after which a lock is reported as a possible deadlock.
+const SPIN_THRESHOLD: u64 = 100_000_000;
+
+/// Value of the owner CPU when the lock is free.
+const NO_CPU: u32 = u32::MAX;
+
+/// Information about the holder of a lock.
+pub(crate) struct LockOwner {
+    /// Location of the code that acquired the lock.
+    location: AtomicPtr<Location<'static>>,
+
+    /// CPU (local APIC ID) that acquired the lock.
+    cpu: AtomicU32,
+
+    /// Slot of the thread that acquired the lock.
+    thread: AtomicUsize,
+}
+
+impl LockOwner {
+    pub(crate) const fn new() -> Self {
+        Self {
+            location: AtomicPtr::new(ptr::null_mut()),
+            cpu: AtomicU32::new(NO_CPU),
+            thread: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records the new holder of the lock. Must be called right after acquiring it.
+    ///
+    /// # Arguments
+    /// * `location` - Location of the code that acquired the lock.
+    pub(crate) fn acquired(&self, location: &'static Location<'static>) {
+        self.location
+            .store(location as *const _ as *mut _, Ordering::Relaxed);
+        self.thread.store(thread::running_slot(), Ordering::Relaxed);
+        self.cpu.store(current_cpu(), Ordering::Relaxed);
+    }
+
+    /// Clears the holder of the lock. Must be called right before releasing it.
+    pub(crate) fn released(&self) {
+        self.location.store(ptr::null_mut(), Ordering::Relaxed);
+        self.cpu.store(NO_CPU, Ordering::Relaxed);
+    }
+
+    /// Reports and halts the processor if the lock is held by the current thread on the current
+    /// CPU.
+    ///
+    /// # Arguments
+    /// * `lock` - Address of the lock, to identify it in the diagnostic.
+    /// * `location` - Location of the code that is trying to acquire the lock.
+    pub(crate) fn check_recursion(&self, lock: usize, location: &Location) {
+        if self.cpu.load(Ordering::Relaxed) != current_cpu()
+            || self.thread.load(Ordering::Relaxed) != thread::running_slot()
+        {
+            return;
+        }
+
+        self.report("recursive locking", lock, location);
+        power::halt();
+    }
+
+    /// Prints a diagnostic about the lock over serial.
+    fn report(&self, problem: &str, lock: usize, location: &Location) {
+        _print_unlocked(format_args!(
+            "[lock debug] CPU {}: {} of lock {:#x} at {}\n",
+            current_cpu(),
+            problem,
+            lock,
+            location
+        ));
+
+        let holder = self.location.load(Ordering::Relaxed);
+        // Safety: the pointer was created from a `&'static Location`
+        match unsafe { holder.as_ref() } {
+            Some(holder) => _print_unlocked(format_args!(
+                "[lock debug]   held by CPU {} (thread slot {}) since {}\n",
+                self.cpu.load(Ordering::Relaxed),
+                self.thread.load(Ordering::Relaxed),
+                holder
+            )),
+            None => _print_unlocked(format_args!("[lock debug]   holder unknown\n")),
+        }
+    }
+}
+
+/// Counts the iterations a lock attempt spins and reports it once if it goes past the
+/// threshold.
+pub(crate) struct SpinWatch {
+    spins: u64,
+}
+
+impl SpinWatch {
+    pub(crate) const fn new() -> Self {
+        Self { spins: 0 }
+    }
+
+    /// Counts a spin iteration.
+    ///
+    /// # Arguments
+    /// * `owner` - Holder of the lock.
+    /// * `lock` - Address of the lock, to identify it in the diagnostic.
+    /// * `location` - Location of the code that is trying to acquire the lock.
+    pub(crate) fn spin(&mut self, owner: &LockOwner, lock: usize, location: &Location) {
+        self.spins += 1;
+        if self.spins == SPIN_THRESHOLD {
+            owner.report("possible deadlock", lock, location);
+        }
+    }
+}
+
+/// Returns the ID of the current CPU (its initial local APIC ID).
+fn current_cpu() -> u32 {
+    u32::from(cpuid::initial_apic_id())
+}
@@ -1 +1,11 @@
+pub mod condvar;
+pub mod irq_safe_mutex;
+pub mod lazy;
+#[cfg(feature = "lock_debug")]
+mod lock_debug;
+pub mod once;
+pub mod rwlock;
+pub mod semaphore;
 pub mod spinlock;
+pub mod ticket_lock;
+pub mod wait_queue;
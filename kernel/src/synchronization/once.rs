@@ -0,0 +1,205 @@
+//! This module implements a one-time initialization primitive
+//!
+//! `Once` holds a value that is initialized only once, the first time it is requested, even if
+//! several processes request it at the same time. The ones that arrive while the initialization
+//! is running spin until it finishes.
+//!
+//! An initializer that requests its own `Once` (directly, or from an interrupt handler on the same
+//! CPU and thread) would spin forever waiting for itself, so it is reported as an error instead.
+//! The initializer is identified by the CPU and the thread that run it.
+//!
+//! There is no poisoning: the kernel is built with `panic = "abort"`, so a panicking initializer
+//! never returns to leave the `Once` in a failed state.
+//!
+//! P.S: Most documentation comments are taken from
+//! https://doc.rust-lang.org/std/sync/struct.OnceLock.html
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use x86_64_custom::cpuid;
+
+use crate::thread;
+
+const INCOMPLETE: u8 = 0;
+const RUNNING: u8 = 1;
+const COMPLETE: u8 = 2;
+
+/// Value of the initializer identity until it is recorded.
+const NO_INITIALIZER: u64 = u64::MAX;
+
+/// Represents all the possible errors that can happen when using a `Once`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OnceError {
+    /// The value is not initialized yet.
+    Incomplete,
+
+    /// The value was requested by its own initializer.
+    Recursive,
+}
+
+/// A synchronization primitive which can be written to only once.
+pub struct Once<T> {
+    /// Initialization state.
+    state: AtomicU8,
+
+    /// CPU and thread running the initializer, see [identity].
+    initializer: AtomicU64,
+
+    /// The value, only initialized when the state is `COMPLETE`.
+    data: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Once<T> {
+    /// Creates a new uninitialized `Once`.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU8::new(INCOMPLETE),
+            initializer: AtomicU64::new(NO_INITIALIZER),
+            data: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Creates a new `Once` already initialized with the given value.
+    pub const fn initialized(value: T) -> Self {
+        Self {
+            state: AtomicU8::new(COMPLETE),
+            initializer: AtomicU64::new(NO_INITIALIZER),
+            data: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was uninitialized.
+    ///
+    /// Many processes may call `call_once` concurrently with different initializing functions,
+    /// but it is guaranteed that only one function will be executed.
+    ///
+    /// # Panics
+    ///
+    /// If it is called by the initializer of the cell.
+    pub fn call_once(&self, f: impl FnOnce() -> T) -> &T {
+        match self.try_call_once(f) {
+            Ok(value) => value,
+            Err(error) => panic!("Once initialization failed: {:?}", error),
+        }
+    }
+
+    /// Gets the contents of the cell, initializing it with `f` if the cell was uninitialized.
+    /// Returns `OnceError::Recursive` if it is called by the initializer of the cell.
+    pub fn try_call_once(&self, f: impl FnOnce() -> T) -> Result<&T, OnceError> {
+        if self
+            .state
+            .compare_exchange(INCOMPLETE, RUNNING, Ordering::Acquire, Ordering::Acquire)
+            .is_ok()
+        {
+            self.initializer.store(identity(), Ordering::Relaxed);
+            let value = f();
+            unsafe { (*self.data.get()).write(value) };
+            self.state.store(COMPLETE, Ordering::Release);
+        }
+
+        self.wait()
+    }
+
+    /// Gets the reference to the underlying value, blocking if it is being initialized.
+    ///
+    /// Returns `OnceError::Incomplete` if the initialization did not start and
+    /// `OnceError::Recursive` if it is called by the initializer of the cell.
+    pub fn wait(&self) -> Result<&T, OnceError> {
+        // Only needed if the initialization is running, it is slow to get
+        let mut current = None;
+        loop {
+            match self.state.load(Ordering::Acquire) {
+                RUNNING => {
+                    let current = *current.get_or_insert_with(identity);
+                    if self.initializer.load(Ordering::Relaxed) == current {
+                        return Err(OnceError::Recursive);
+                    }
+                    core::hint::spin_loop()
+                }
+                state => return self.get_for_state(state),
+            }
+        }
+    }
+
+    /// Gets the reference to the underlying value, without blocking.
+    ///
+    /// Returns `None` if the cell is uninitialized or being initialized.
+    pub fn get(&self) -> Option<&T> {
+        self.get_for_state(self.state.load(Ordering::Acquire)).ok()
+    }
+
+    /// Returns true if the value is initialized.
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Returns the value if the given state is `COMPLETE`.
+    fn get_for_state(&self, state: u8) -> Result<&T, OnceError> {
+        match state {
+            COMPLETE => Ok(unsafe { (*self.data.get()).assume_init_ref() }),
+            _ => Err(OnceError::Incomplete),
+        }
+    }
+}
+
+impl<T> Default for Once<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for Once<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == COMPLETE {
+            unsafe { self.data.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+unsafe impl<T: Send> Send for Once<T> {}
+unsafe impl<T: Send + Sync> Sync for Once<T> {}
+
+/// Returns a value that identifies the code running: the CPU (its initial local APIC ID) and the
+/// slot of the running thread. The application processors do not run threads, they all see the
+/// thread running on the bootstrap processor, so the CPU is needed too.
+fn identity() -> u64 {
+    (u64::from(cpuid::initial_apic_id()) << 32) | thread::running_slot() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn call_once_initializes_once() {
+        let once = Once::new();
+        assert_eq!(None, once.get());
+        assert_eq!(Err(OnceError::Incomplete), once.wait());
+
+        assert_eq!(42, *once.call_once(|| 42));
+        assert_eq!(42, *once.call_once(|| 43));
+        assert_eq!(Some(&42), once.get());
+        assert!(once.is_completed());
+    }
+
+    #[test_case]
+    fn initialized_once_ok() {
+        let once = Once::initialized(42);
+
+        assert!(once.is_completed());
+        assert_eq!(42, *once.call_once(|| 43));
+    }
+
+    #[test_case]
+    fn recursive_initialization_should_fail() {
+        let once = Once::new();
+        let value = once.call_once(|| {
+            assert_eq!(Err(OnceError::Recursive), once.try_call_once(|| 43));
+            assert_eq!(Err(OnceError::Recursive), once.wait());
+            assert_eq!(None, once.get());
+            42
+        });
+
+        assert_eq!(42, *value);
+    }
+}
@@ -0,0 +1,423 @@
+//! This module implements a reader-writer spinlock
+//!
+//! A reader-writer lock allows any number of readers or a single writer at the same time. It is
+//! useful for data that is read a lot more than it is written.
+//!
+//! Besides the read and write guards, there is an upgradeable read guard: it can read the data
+//! concurrently with the regular readers and later be upgraded to a write guard without
+//! releasing the lock. Only one upgradeable guard can exist at a time, and while it exists no new
+//! readers are admitted, so the upgrade only needs to wait for the current readers to finish.
+//!
+//! The whole state of the lock is kept in a single atomic word:
+//!
+//! bits   63 .. 2  |     1     |   0
+//!        readers    upgraded   writer
+//!
+//! P.S: Most documentation comments are taken from
+//! https://doc.rust-lang.org/std/sync/struct.RwLock.html and the design is based on the `spin`
+//! crate: https://docs.rs/spin/latest/spin/rwlock/struct.RwLock.html
+use core::cell::UnsafeCell;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::spinlock::MutexError;
+
+const WRITER: usize = 1;
+const UPGRADED: usize = 1 << 1;
+const READER: usize = 1 << 2;
+
+/// A reader-writer lock.
+///
+/// This type of lock allows a number of readers or at most one writer at any point in time. The
+/// generic parameter T is the type of the data that the lock is protecting. This lock protects
+/// data busy-waiting for the lock.
+pub struct RwLock<T> {
+    /// Lock state (readers count, upgraded and writer flags).
+    state: AtomicUsize,
+
+    /// Protected data.
+    data: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    /// Creates a new reader-writer lock in an unlocked state ready for use.
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Attempts to acquire this lock with shared read access.
+    ///
+    /// If the lock could not be acquired at this time (there is a writer or an upgradeable
+    /// reader), then [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is
+    /// returned. Otherwise, an RAII guard is returned which will release the shared access when
+    /// it is dropped.
+    ///
+    /// This function does not block.
+    pub fn try_read(&self) -> Result<RwLockReadGuard<'_, T>, MutexError> {
+        let state = self.state.fetch_add(READER, Ordering::Acquire);
+        if state & (WRITER | UPGRADED) != 0 {
+            // Undo the reader increment
+            self.state.fetch_sub(READER, Ordering::Release);
+            Err(MutexError::AlreadyLocked)
+        } else {
+            Ok(RwLockReadGuard { lock: self })
+        }
+    }
+
+    /// Locks this lock with shared read access, blocking until it can be acquired.
+    ///
+    /// There may be other readers currently inside the lock when this method returns. Returns an
+    /// RAII guard which will release the shared access when it is dropped.
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        loop {
+            if let Ok(guard) = self.try_read() {
+                return guard;
+            }
+
+            while self.state.load(Ordering::Relaxed) & (WRITER | UPGRADED) != 0 {
+                core::hint::spin_loop()
+            }
+        }
+    }
+
+    /// Attempts to lock this lock with exclusive write access.
+    ///
+    /// If the lock could not be acquired at this time, then
+    /// [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is returned.
+    /// Otherwise, an RAII guard is returned which will release the lock when it is dropped.
+    ///
+    /// This function does not block.
+    pub fn try_write(&self) -> Result<RwLockWriteGuard<'_, T>, MutexError> {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| RwLockWriteGuard { lock: self })
+            .map_err(|_| MutexError::AlreadyLocked)
+    }
+
+    /// Locks this lock with exclusive write access, blocking until it can be acquired.
+    ///
+    /// This function will not return while other writers or other readers currently have access
+    /// to the lock. Returns an RAII guard which will drop the write access of this lock when
+    /// dropped.
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        loop {
+            if let Ok(guard) = self.try_write() {
+                return guard;
+            }
+
+            while self.state.load(Ordering::Relaxed) != 0 {
+                core::hint::spin_loop()
+            }
+        }
+    }
+
+    /// Attempts to obtain an upgradeable lock.
+    ///
+    /// If the lock could not be acquired at this time (there is a writer or another upgradeable
+    /// reader), then [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is
+    /// returned. Otherwise, an RAII guard is returned which will release the lock when it is
+    /// dropped.
+    ///
+    /// This function does not block.
+    pub fn try_upgradeable_read(&self) -> Result<RwLockUpgradeableGuard<'_, T>, MutexError> {
+        // If there is a writer, the flag we set is cleared when it releases the lock. If there
+        // is another upgradeable reader, the flag was already set.
+        if self.state.fetch_or(UPGRADED, Ordering::Acquire) & (WRITER | UPGRADED) == 0 {
+            Ok(RwLockUpgradeableGuard { lock: self })
+        } else {
+            Err(MutexError::AlreadyLocked)
+        }
+    }
+
+    /// Obtains an upgradeable lock, blocking until it can be acquired.
+    ///
+    /// The upgradeable lock can read the data at the same time as the regular readers, and can be
+    /// upgraded to a write lock later. Returns an RAII guard which will release the lock when it
+    /// is dropped.
+    pub fn upgradeable_read(&self) -> RwLockUpgradeableGuard<'_, T> {
+        loop {
+            if let Ok(guard) = self.try_upgradeable_read() {
+                return guard;
+            }
+
+            while self.state.load(Ordering::Relaxed) & (WRITER | UPGRADED) != 0 {
+                core::hint::spin_loop()
+            }
+        }
+    }
+
+    /// Returns the number of readers that currently hold the lock (not including the upgradeable
+    /// one).
+    pub fn reader_count(&self) -> usize {
+        self.state.load(Ordering::Relaxed) / READER
+    }
+
+    /// Returns true if the lock is held by a writer.
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+}
+
+unsafe impl<T: Send> Send for RwLock<T> {}
+unsafe impl<T: Send + Sync> Sync for RwLock<T> {}
+
+/// RAII structure used to release the shared read access of a lock when dropped.
+///
+/// This structure is created by the read and try_read methods on [RwLock](RwLock).
+pub struct RwLockReadGuard<'lock, T: 'lock> {
+    lock: &'lock RwLock<T>,
+}
+
+impl<'lock, T> Deref for RwLockReadGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> Drop for RwLockReadGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(READER, Ordering::Release);
+    }
+}
+
+/// RAII structure used to release the exclusive write access of a lock when dropped.
+///
+/// This structure is created by the write and try_write methods on [RwLock](RwLock).
+pub struct RwLockWriteGuard<'lock, T: 'lock> {
+    lock: &'lock RwLock<T>,
+}
+
+impl<'lock, T> RwLockWriteGuard<'lock, T> {
+    /// Downgrades the write lock to a read lock without allowing other writers in between.
+    pub fn downgrade(self) -> RwLockReadGuard<'lock, T> {
+        let lock = self.lock;
+        // The reader must be added before the writer flag is cleared
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        drop(self);
+
+        RwLockReadGuard { lock }
+    }
+
+    /// Downgrades the write lock to an upgradeable lock without allowing other writers in
+    /// between.
+    pub fn downgrade_to_upgradeable(self) -> RwLockUpgradeableGuard<'lock, T> {
+        let lock = ManuallyDrop::new(self).lock;
+        // Readers that are failing to get the lock could be transiently counted in the state, so
+        // we can not just overwrite it
+        let mut state = lock.state.load(Ordering::Relaxed);
+        while let Err(current) = lock.state.compare_exchange_weak(
+            state,
+            (state & !WRITER) | UPGRADED,
+            Ordering::Release,
+            Ordering::Relaxed,
+        ) {
+            state = current;
+        }
+
+        RwLockUpgradeableGuard { lock }
+    }
+}
+
+impl<'lock, T> Deref for RwLockWriteGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> DerefMut for RwLockWriteGuard<'lock, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut (*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> Drop for RwLockWriteGuard<'lock, T> {
+    fn drop(&mut self) {
+        // An upgradeable reader that failed while we held the lock could have set the upgraded
+        // flag, it is cleared too
+        self.lock
+            .state
+            .fetch_and(!(WRITER | UPGRADED), Ordering::Release);
+    }
+}
+
+/// RAII structure used to release the upgradeable read access of a lock when dropped.
+///
+/// This structure is created by the upgradeable_read and try_upgradeable_read methods on
+/// [RwLock](RwLock).
+pub struct RwLockUpgradeableGuard<'lock, T: 'lock> {
+    lock: &'lock RwLock<T>,
+}
+
+impl<'lock, T> RwLockUpgradeableGuard<'lock, T> {
+    /// Upgrades the lock to a write lock, blocking until the current readers release it.
+    pub fn upgrade(self) -> RwLockWriteGuard<'lock, T> {
+        let mut guard = self;
+        loop {
+            match guard.try_upgrade() {
+                Ok(write_guard) => return write_guard,
+                Err(upgradeable_guard) => guard = upgradeable_guard,
+            }
+
+            core::hint::spin_loop()
+        }
+    }
+
+    /// Tries to upgrade the lock to a write lock. If there are readers holding the lock, the
+    /// upgradeable guard is given back.
+    ///
+    /// This function does not block.
+    pub fn try_upgrade(self) -> Result<RwLockWriteGuard<'lock, T>, Self> {
+        match self.lock.state.compare_exchange(
+            UPGRADED,
+            WRITER,
+            Ordering::Acquire,
+            Ordering::Relaxed,
+        ) {
+            Ok(_) => {
+                let lock = ManuallyDrop::new(self).lock;
+                Ok(RwLockWriteGuard { lock })
+            }
+            Err(_) => Err(self),
+        }
+    }
+
+    /// Downgrades the upgradeable lock to a read lock, allowing another upgradeable reader in.
+    pub fn downgrade(self) -> RwLockReadGuard<'lock, T> {
+        let lock = self.lock;
+        // The reader must be added before the upgraded flag is cleared
+        lock.state.fetch_add(READER, Ordering::Acquire);
+        drop(self);
+
+        RwLockReadGuard { lock }
+    }
+}
+
+impl<'lock, T> Deref for RwLockUpgradeableGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> Drop for RwLockUpgradeableGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(UPGRADED, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn multiple_readers_ok() {
+        let lock = RwLock::new(42);
+        let r1 = lock.read();
+        let r2 = lock.try_read();
+
+        assert!(r2.is_ok());
+        assert_eq!(2, lock.reader_count());
+        assert_eq!(42, *r1);
+    }
+
+    #[test_case]
+    fn write_and_read_ok() {
+        let lock = RwLock::new(42);
+        {
+            let mut value = lock.write();
+            *value = 43;
+        }
+
+        assert_eq!(43, *lock.read());
+    }
+
+    #[test_case]
+    fn write_with_readers_should_fail() {
+        let lock = RwLock::new(42);
+        let _r1 = lock.read();
+
+        assert!(lock.try_write().is_err());
+    }
+
+    #[test_case]
+    fn read_with_writer_should_fail() {
+        let lock = RwLock::new(42);
+        let _w1 = lock.write();
+
+        assert!(lock.is_write_locked());
+        assert!(lock.try_read().is_err());
+        assert!(lock.try_upgradeable_read().is_err());
+    }
+
+    #[test_case]
+    fn guards_should_release_lock() {
+        let lock = RwLock::new(42);
+        {
+            let _r1 = lock.read();
+        }
+        {
+            let _w1 = lock.write();
+        }
+        {
+            let _u1 = lock.upgradeable_read();
+        }
+
+        assert!(lock.try_write().is_ok());
+    }
+
+    #[test_case]
+    fn only_one_upgradeable_reader() {
+        let lock = RwLock::new(42);
+        let _u1 = lock.upgradeable_read();
+
+        assert!(lock.try_upgradeable_read().is_err());
+        assert!(lock.try_read().is_err());
+    }
+
+    #[test_case]
+    fn upgrade_waits_for_readers() {
+        let lock = RwLock::new(42);
+        let upgradeable = lock.upgradeable_read();
+        let reader = lock.read();
+
+        let upgradeable = match upgradeable.try_upgrade() {
+            Ok(_) => panic!("Upgrade should fail while there are readers"),
+            Err(upgradeable) => upgradeable,
+        };
+
+        drop(reader);
+        let mut writer = upgradeable.upgrade();
+        *writer = 43;
+        drop(writer);
+
+        assert_eq!(43, *lock.read());
+    }
+
+    #[test_case]
+    fn downgrade_ok() {
+        let lock = RwLock::new(42);
+        let reader = lock.write().downgrade();
+
+        assert!(!lock.is_write_locked());
+        assert!(lock.try_read().is_ok());
+        assert!(lock.try_write().is_err());
+        drop(reader);
+
+        let upgradeable = lock.write().downgrade_to_upgradeable();
+        assert!(lock.try_upgradeable_read().is_err());
+        let reader = upgradeable.downgrade();
+        assert!(lock.try_upgradeable_read().is_ok());
+        drop(reader);
+
+        assert!(lock.try_write().is_ok());
+    }
+}
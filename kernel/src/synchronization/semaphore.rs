@@ -0,0 +1,112 @@
+//! This module implements a counting semaphore
+//!
+//! A semaphore holds a number of permits. Acquiring a permit decrements the count, blocking while
+//! there are none available, and releasing one increments it and wakes up a waiter.
+//!
+//! Releasing never blocks, so it is safe to do it from interrupt handlers. For example, the
+//! keyboard handler can release a permit for every key pressed while a reader acquires one for
+//! every key it consumes.
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::wait_queue::WaitQueue;
+
+/// Represents all the possible errors that can happen when using a semaphore.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SemaphoreError {
+    NoPermitsAvailable,
+}
+
+/// A counting semaphore.
+pub struct Semaphore {
+    /// Number of available permits.
+    permits: AtomicUsize,
+
+    /// Processes waiting for a permit.
+    waiters: WaitQueue,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the given number of available permits.
+    ///
+    /// # Arguments
+    /// * `permits` - Initial number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new(),
+        }
+    }
+
+    /// Attempts to acquire a permit.
+    ///
+    /// If there are no permits available, then
+    /// [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is returned.
+    ///
+    /// This function does not block.
+    pub fn try_acquire(&self) -> Result<(), SemaphoreError> {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+        loop {
+            if permits == 0 {
+                return Err(SemaphoreError::NoPermitsAvailable);
+            }
+
+            match self.permits.compare_exchange_weak(
+                permits,
+                permits - 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(current) => permits = current,
+            }
+        }
+    }
+
+    /// Acquires a permit, blocking until one is available.
+    pub fn acquire(&self) {
+        self.waiters.wait_until(|| self.try_acquire().is_ok());
+    }
+
+    /// Releases a permit and wakes up a waiter.
+    ///
+    /// This function does not block.
+    pub fn release(&self) {
+        self.permits.fetch_add(1, Ordering::Release);
+        self.waiters.notify_one();
+    }
+
+    /// Returns the number of permits currently available.
+    pub fn available_permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn acquire_and_release_ok() {
+        let semaphore = Semaphore::new(2);
+
+        semaphore.acquire();
+        assert!(semaphore.try_acquire().is_ok());
+        assert_eq!(0, semaphore.available_permits());
+
+        semaphore.release();
+        assert_eq!(1, semaphore.available_permits());
+    }
+
+    #[test_case]
+    fn acquire_without_permits_should_fail() {
+        let semaphore = Semaphore::new(0);
+
+        assert_eq!(
+            Err(SemaphoreError::NoPermitsAvailable),
+            semaphore.try_acquire()
+        );
+
+        semaphore.release();
+        assert!(semaphore.try_acquire().is_ok());
+    }
+}
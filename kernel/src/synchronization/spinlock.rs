@@ -11,6 +11,14 @@ use core::cell::UnsafeCell;
 use core::ops::{Deref, DerefMut};
 use core::sync::atomic::{AtomicBool, Ordering};
 
+use super::condvar::CondvarGuard;
+use crate::thread::PreemptionGuard;
+
+#[cfg(feature = "lock_debug")]
+use super::lock_debug::{LockOwner, SpinWatch};
+#[cfg(feature = "lock_debug")]
+use core::panic::Location;
+
 const LOCKED: bool = true;
 const UNLOCKED: bool = false;
 
@@ -24,10 +32,20 @@ pub enum MutexError {
 /// This mutex will block other processes waiting for the lock to become available. The generic
 /// parameter T is the type of the data that the mutex is protecting. This mutex protects data
 /// busy-waiting for the lock.
+///
+/// The running thread is not preempted while it holds the lock, see
+/// [schedule](crate::thread::schedule).
+///
+/// With the `lock_debug` feature enabled, the mutex records its holder and detects recursive
+/// locking and possible deadlocks (see [lock_debug](super::lock_debug)).
 pub struct Mutex<T> {
     /// Wether the data is locked or not.
     locked: AtomicBool,
 
+    /// Holder of the lock.
+    #[cfg(feature = "lock_debug")]
+    owner: LockOwner,
+
     /// Protected data.
     data: UnsafeCell<T>,
 }
@@ -37,6 +55,8 @@ impl<T> Mutex<T> {
     pub const fn new(data: T) -> Self {
         Self {
             locked: AtomicBool::new(false),
+            #[cfg(feature = "lock_debug")]
+            owner: LockOwner::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -48,7 +68,11 @@ impl<T> Mutex<T> {
     /// Otherwise, an RAII guard is returned. The lock will be unlocked when the guard is dropped.
     ///
     /// This function does not block.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
     pub fn try_lock(&self) -> Result<MutexGuard<T>, MutexError> {
+        // Taken before the lock, so the thread can not be preempted between both
+        let preemption = PreemptionGuard::new();
+
         // Check if it is locked
         if self
             .locked
@@ -57,7 +81,13 @@ impl<T> Mutex<T> {
         {
             Err(MutexError::AlreadyLocked)
         } else {
-            Ok(MutexGuard { mutex: self })
+            #[cfg(feature = "lock_debug")]
+            self.owner.acquired(Location::caller());
+
+            Ok(MutexGuard {
+                mutex: self,
+                _preemption: preemption,
+            })
         }
     }
 
@@ -66,7 +96,11 @@ impl<T> Mutex<T> {
     /// This function will block the until another process is available to acquire the mutex.
     /// An RAII guard is returned to allow scoped unlock of the lock. When the guard goes out
     /// of scope, the mutex will be unlocked.
+    #[cfg_attr(feature = "lock_debug", track_caller)]
     pub fn lock(&self) -> MutexGuard<T> {
+        #[cfg(feature = "lock_debug")]
+        let mut spin_watch = SpinWatch::new();
+
         // Loop until the lock is released...
         loop {
             if let Ok(mutex_guard) = self.try_lock() {
@@ -81,7 +115,14 @@ impl<T> Mutex<T> {
                 // protocol), avoiding all the coordination efforts and not asking for exclusive
                 // access
                 // For more information at hardware level of this check out the MESI protocol
+                #[cfg(feature = "lock_debug")]
+                self.owner
+                    .check_recursion(self as *const _ as usize, Location::caller());
+
                 while self.locked.load(Ordering::Relaxed) {
+                    #[cfg(feature = "lock_debug")]
+                    spin_watch.spin(&self.owner, self as *const _ as usize, Location::caller());
+
                     core::hint::spin_loop()
                 }
             }
@@ -98,6 +139,9 @@ impl<T> Mutex<T> {
 
     /// Releases the lock.
     fn release(&self) {
+        #[cfg(feature = "lock_debug")]
+        self.owner.released();
+
         self.locked.store(UNLOCKED, Ordering::Release);
     }
 }
@@ -116,6 +160,9 @@ unsafe impl<T: Sync> Sync for Mutex<T> {}
 /// [Mutex](Mutex).
 pub struct MutexGuard<'mutex, T: 'mutex> {
     mutex: &'mutex Mutex<T>,
+
+    /// Dropped after releasing the lock.
+    _preemption: PreemptionGuard,
 }
 
 impl<'mutex, T> Deref for MutexGuard<'mutex, T> {
@@ -138,6 +185,15 @@ impl<'mutex, T> Drop for MutexGuard<'mutex, T> {
     }
 }
 
+impl<'mutex, T> CondvarGuard for MutexGuard<'mutex, T> {
+    fn unlock_and_wait(self, wait: impl FnOnce()) -> Self {
+        let mutex = self.mutex;
+        drop(self);
+        wait();
+        mutex.lock()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,174 @@
+//! This module implements a ticket lock
+//!
+//! The test-and-set [Mutex](super::spinlock::Mutex) is not fair: when the lock is released, any
+//! of the waiters can get it, so one of them could wait forever. A ticket lock works like the
+//! queue of a bakery: every process that wants the lock takes a ticket (a number that is
+//! incremented each time) and waits until its number is served. The lock is acquired in the same
+//! order it was requested.
+//!
+//! For more info:
+//! https://en.wikipedia.org/wiki/Ticket_lock
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use super::spinlock::MutexError;
+
+/// A fair mutual exclusion primitive useful for protecting shared data.
+///
+/// The processes waiting for the lock acquire it in the same order they requested it. The generic
+/// parameter T is the type of the data that the lock is protecting. This lock protects data
+/// busy-waiting for the lock.
+pub struct TicketLock<T> {
+    /// Next ticket to give.
+    next_ticket: AtomicUsize,
+
+    /// Ticket that currently holds the lock.
+    now_serving: AtomicUsize,
+
+    /// Protected data.
+    data: UnsafeCell<T>,
+}
+
+impl<T> TicketLock<T> {
+    /// Creates a new ticket lock in an unlocked state ready for use.
+    pub const fn new(data: T) -> Self {
+        Self {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Attempts to acquire this lock.
+    ///
+    /// If the lock could not be acquired at this time (it is held or there are other processes
+    /// waiting for it), then
+    /// [Err](https://doc.rust-lang.org/std/result/enum.Result.html#variant.Err) is returned.
+    /// Otherwise, an RAII guard is returned. The lock will be unlocked when the guard is dropped.
+    ///
+    /// This function does not block.
+    pub fn try_lock(&self) -> Result<TicketLockGuard<'_, T>, MutexError> {
+        // We only take a ticket if it is the one being served
+        let ticket = self.now_serving.load(Ordering::Relaxed);
+        self.next_ticket
+            .compare_exchange(
+                ticket,
+                ticket.wrapping_add(1),
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            )
+            .map(|_| TicketLockGuard { lock: self })
+            .map_err(|_| MutexError::AlreadyLocked)
+    }
+
+    /// Acquires the lock, blocking until it is able to do so.
+    ///
+    /// This function will block until all the processes that requested the lock before are done
+    /// with it. An RAII guard is returned to allow scoped unlock of the lock. When the guard goes
+    /// out of scope, the lock will be unlocked.
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            core::hint::spin_loop()
+        }
+
+        TicketLockGuard { lock: self }
+    }
+
+    /// Immediately drops the guard, and consequently unlocks the lock.
+    ///
+    /// This function is equivalent to calling drop on the guard but is more self-documenting.
+    /// Alternately, the guard will be automatically dropped when it goes out of scope.
+    pub fn unlock(guard: TicketLockGuard<'_, T>) {
+        drop(guard)
+    }
+
+    /// Returns true if the lock is held.
+    pub fn is_locked(&self) -> bool {
+        self.next_ticket.load(Ordering::Relaxed) != self.now_serving.load(Ordering::Relaxed)
+    }
+
+    /// Releases the lock, serving the next ticket.
+    fn release(&self) {
+        self.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
+
+unsafe impl<T: Send> Send for TicketLock<T> {}
+unsafe impl<T: Send> Sync for TicketLock<T> {}
+
+/// An RAII implementation of a "scoped lock" of a ticket lock. When this structure is dropped
+/// (falls out of scope), the lock will be unlocked.
+///
+/// This structure is created by the lock and try_lock methods on [TicketLock](TicketLock).
+pub struct TicketLockGuard<'lock, T: 'lock> {
+    lock: &'lock TicketLock<T>,
+}
+
+impl<'lock, T> Deref for TicketLockGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &(*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> DerefMut for TicketLockGuard<'lock, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe { &mut (*self.lock.data.get()) }
+    }
+}
+
+impl<'lock, T> Drop for TicketLockGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.release();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn write_and_read_ok() {
+        let lock = TicketLock::new(42);
+        {
+            let mut value = lock.lock();
+            *value = 43;
+        }
+
+        assert_eq!(43, *lock.lock());
+    }
+
+    #[test_case]
+    fn lock_two_times_should_fail() {
+        let lock = TicketLock::new(42);
+        let _l1 = lock.lock();
+
+        assert!(lock.is_locked());
+        assert!(lock.try_lock().is_err());
+    }
+
+    #[test_case]
+    fn guard_should_release_lock() {
+        let lock = TicketLock::new(42);
+        {
+            let _l1 = lock.lock();
+        }
+
+        assert!(!lock.is_locked());
+        assert!(lock.try_lock().is_ok());
+    }
+
+    #[test_case]
+    fn tickets_are_served_in_order() {
+        let lock = TicketLock::new(42);
+        for _ in 0..10 {
+            let _guard = lock.lock();
+        }
+
+        assert_eq!(10, lock.next_ticket.load(Ordering::Relaxed));
+        assert_eq!(10, lock.now_serving.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,132 @@
+//! This module implements a queue of processes waiting for an event
+//!
+//! A `WaitQueue` is the building block of the blocking primitives ([Semaphore], [Condvar]): the
+//! waiting side checks a condition and, if it is not met, sleeps until somebody notifies the
+//! queue; the notifying side changes the state the condition depends on and notifies the queue.
+//!
+//! Every notification increments a counter. A waiter reads the counter before checking its
+//! condition and only sleeps if the counter did not change since then, so a notification that
+//! arrives between the check and the sleep is never lost.
+//!
+//! Waiting blocks the current [thread](crate::thread) on the queue, so other threads can use the
+//! CPU. The counter is checked with the thread table locked and the interrupts disabled, so the
+//! queue can be safely notified from interrupt handlers.
+//!
+//! Waiters must be prepared for spurious wake ups: they re-check their condition after every
+//! notification, even if it was meant for another waiter.
+//!
+//! [Semaphore]: super::semaphore::Semaphore
+//! [Condvar]: super::condvar::Condvar
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::thread;
+
+/// A queue of processes waiting for an event.
+pub struct WaitQueue {
+    /// Number of notifications received.
+    notifications: AtomicU64,
+}
+
+impl WaitQueue {
+    /// Creates a new wait queue without waiters.
+    pub const fn new() -> Self {
+        Self {
+            notifications: AtomicU64::new(0),
+        }
+    }
+
+    /// Blocks until `condition` returns true. The condition is checked before blocking and every
+    /// time the queue is notified.
+    ///
+    /// If nothing else can run, the CPU is halted with the interrupts enabled, since the
+    /// notifications usually come from interrupt handlers.
+    ///
+    /// # Arguments
+    /// * `condition` - Condition to wait for.
+    pub fn wait_until(&self, mut condition: impl FnMut() -> bool) {
+        loop {
+            let seen = self.notifications();
+            if condition() {
+                return;
+            }
+
+            self.wait_for_notification(seen);
+        }
+    }
+
+    /// Wakes up one of the waiters.
+    pub fn notify_one(&self) {
+        self.notifications.fetch_add(1, Ordering::Release);
+        thread::wake_one(self.address());
+    }
+
+    /// Wakes up all the waiters.
+    pub fn notify_all(&self) {
+        self.notifications.fetch_add(1, Ordering::Release);
+        thread::wake_all(self.address());
+    }
+
+    /// Returns the address of the queue, which identifies it among the blocked threads.
+    fn address(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Returns the number of notifications received. It must be read before checking the
+    /// condition being waited for and then passed to
+    /// [wait_for_notification](Self::wait_for_notification).
+    pub(super) fn notifications(&self) -> u64 {
+        self.notifications.load(Ordering::Acquire)
+    }
+
+    /// Blocks until the queue is notified, unless it was already notified after `seen` was read.
+    ///
+    /// # Arguments
+    /// * `seen` - Number of notifications read before checking the condition.
+    pub(super) fn wait_for_notification(&self, seen: u64) {
+        thread::block_on(self.address(), || self.notifications() == seen);
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NOTE: The tests run without an IDT, so they must never actually wait.
+
+    #[test_case]
+    fn met_condition_does_not_wait() {
+        let queue = WaitQueue::new();
+        let mut checks = 0;
+
+        queue.wait_until(|| {
+            checks += 1;
+            true
+        });
+
+        assert_eq!(1, checks);
+    }
+
+    #[test_case]
+    fn notification_is_not_lost() {
+        let queue = WaitQueue::new();
+        let mut checks = 0;
+
+        // Notified between the condition check and the wait (as an interrupt handler would do),
+        // the waiter must not sleep and check the condition again
+        queue.wait_until(|| {
+            checks += 1;
+            if checks == 1 {
+                queue.notify_one();
+            }
+            checks == 2
+        });
+
+        assert_eq!(2, checks);
+    }
+}
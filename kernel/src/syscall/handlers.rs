@@ -0,0 +1,70 @@
+//! System call handlers
+//!
+//! The arguments were already decoded by the [syscall table](super), user memory is accessed only
+//! through [UserPointer] and [UserSlice].
+use core::time::Duration;
+
+use super::{SyscallResult, UserPointer, UserSlice};
+use crate::print;
+use crate::thread;
+use crate::time;
+
+/// Maximum number of bytes written by a single `write` call.
+const MAX_WRITE_LEN: usize = 1024;
+
+/// Finishes the current thread.
+///
+/// # Arguments
+/// * `code` - Exit code of the thread.
+pub(super) fn exit(code: usize) -> SyscallResult {
+    thread::exit(code)
+}
+
+/// Prints a UTF-8 string on the screen. Invalid sequences are replaced with `U+FFFD`. Returns the
+/// number of bytes written, at most `MAX_WRITE_LEN`.
+///
+/// # Arguments
+/// * `buffer` - Bytes to print.
+pub(super) fn write(buffer: UserSlice<u8>) -> SyscallResult {
+    let mut text = [0; MAX_WRITE_LEN];
+    let len = buffer.read(0, &mut text)?;
+
+    for chunk in text[..len].utf8_chunks() {
+        print!("{}", chunk.valid());
+        if !chunk.invalid().is_empty() {
+            print!("{}", char::REPLACEMENT_CHARACTER);
+        }
+    }
+
+    Ok(len as u64)
+}
+
+/// Blocks the current thread.
+///
+/// # Arguments
+/// * `milliseconds` - Time to sleep.
+pub(super) fn sleep(milliseconds: u64) -> SyscallResult {
+    thread::sleep(Duration::from_millis(milliseconds));
+    Ok(0)
+}
+
+/// Gives the CPU to the next ready thread.
+pub(super) fn yield_now() -> SyscallResult {
+    thread::yield_now();
+    Ok(0)
+}
+
+/// Returns the identifier of the current thread.
+pub(super) fn thread_id() -> SyscallResult {
+    Ok(thread::current().as_u64())
+}
+
+/// Copies the time elapsed since boot, in nanoseconds, to user memory.
+///
+/// # Arguments
+/// * `destination` - Where to write the uptime.
+pub(super) fn uptime(destination: UserPointer<u64>) -> SyscallResult {
+    let uptime = u64::try_from(time::uptime().as_nanos()).unwrap_or(u64::MAX);
+    destination.write(uptime)?;
+    Ok(0)
+}
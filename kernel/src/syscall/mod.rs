@@ -0,0 +1,139 @@
+//! System calls
+//!
+//! The [arch entry points](crate::arch::x86_64::SYSCALL_VECTOR) save the registers of the user
+//! code and call [dispatch] with the system call number (RAX) and the six raw arguments. The
+//! table below decodes the raw arguments into the types of the handler parameters (see
+//! [SyscallArgument]) and calls it.
+//!
+//! A handler returns a [SyscallResult]. Success values are returned as is, errors as the negated
+//! error code (like Linux does), so user code checks for a result between -4095 and -1.
+//!
+//! User memory is never accessed directly: pointers are wrapped in [UserPointer] and [UserSlice],
+//! that validate them against the current address space before copying from or to them.
+mod handlers;
+pub mod user_pointer;
+
+pub use user_pointer::{UserPointer, UserSlice};
+
+/// Result of a system call handler.
+pub type SyscallResult = Result<u64, SyscallError>;
+
+/// Represents all the possible errors that a system call can return.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyscallError {
+    /// A pointer argument is not mapped, not accessible from user mode or out of the user address
+    /// space.
+    BadAddress = 14,
+
+    /// An argument has an invalid value.
+    InvalidArgument = 22,
+
+    /// There is no system call with the requested number.
+    UnknownSyscall = 38,
+}
+
+impl SyscallError {
+    /// Returns the error as it is returned to user code: the negated error code.
+    pub fn as_u64(self) -> u64 {
+        (-(self as i64)) as u64
+    }
+}
+
+/// A type that can be decoded from the raw arguments of a system call.
+pub trait SyscallArgument: Sized {
+    /// Decodes the argument, consuming as many raw arguments as it needs.
+    ///
+    /// # Arguments
+    /// * `arguments` - Raw arguments not consumed yet.
+    fn from_raw(arguments: &mut impl Iterator<Item = u64>) -> Result<Self, SyscallError>;
+}
+
+impl SyscallArgument for u64 {
+    fn from_raw(arguments: &mut impl Iterator<Item = u64>) -> Result<Self, SyscallError> {
+        arguments.next().ok_or(SyscallError::InvalidArgument)
+    }
+}
+
+impl SyscallArgument for usize {
+    fn from_raw(arguments: &mut impl Iterator<Item = u64>) -> Result<Self, SyscallError> {
+        let argument = u64::from_raw(arguments)?;
+        usize::try_from(argument).map_err(|_| SyscallError::InvalidArgument)
+    }
+}
+
+/// Defines the system call numbers (in the `number` module) and the `call` function that decodes
+/// the arguments and calls the handler of a system call.
+macro_rules! syscall_table {
+    ($($constant: ident = $number: literal => $handler: ident($($argument: ident: $type: ty),*)),* $(,)?) => {
+        /// System call numbers.
+        pub mod number {
+            $(pub const $constant: u64 = $number;)*
+        }
+
+        /// Calls the handler of a system call.
+        ///
+        /// # Arguments
+        /// * `number` - System call number.
+        /// * `arguments` - Raw arguments.
+        fn call(number: u64, arguments: [u64; 6]) -> SyscallResult {
+            #[allow(unused_variables, unused_mut)]
+            let mut arguments = arguments.into_iter();
+            match number {
+                $(number::$constant => {
+                    $(let $argument = <$type as SyscallArgument>::from_raw(&mut arguments)?;)*
+                    handlers::$handler($($argument),*)
+                })*
+                _ => Err(SyscallError::UnknownSyscall),
+            }
+        }
+    };
+}
+
+syscall_table! {
+    EXIT = 0 => exit(code: usize),
+    WRITE = 1 => write(buffer: UserSlice<u8>),
+    SLEEP = 2 => sleep(milliseconds: u64),
+    YIELD = 3 => yield_now(),
+    THREAD_ID = 4 => thread_id(),
+    UPTIME = 5 => uptime(destination: UserPointer<u64>),
+}
+
+/// Runs a system call and returns the value for the RAX register of the user code.
+///
+/// # Arguments
+/// * `number` - System call number.
+/// * `arguments` - Raw arguments.
+pub fn dispatch(number: u64, arguments: [u64; 6]) -> u64 {
+    match call(number, arguments) {
+        Ok(value) => value,
+        Err(error) => error.as_u64(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn unknown_syscall_should_fail() {
+        assert_eq!(
+            SyscallError::UnknownSyscall.as_u64(),
+            dispatch(u64::MAX, [0; 6])
+        );
+        assert_eq!(-38, dispatch(u64::MAX, [0; 6]) as i64);
+    }
+
+    #[test_case]
+    fn handlers_get_typed_arguments() {
+        assert_eq!(
+            crate::thread::current().as_u64(),
+            dispatch(number::THREAD_ID, [0; 6])
+        );
+
+        // The kernel half of the address space is never accessible
+        assert_eq!(
+            SyscallError::BadAddress.as_u64(),
+            dispatch(number::UPTIME, [0xffff_8000_0000_0000, 0, 0, 0, 0, 0])
+        );
+    }
+}
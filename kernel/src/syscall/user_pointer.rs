@@ -0,0 +1,233 @@
+//! Access to user memory from system calls
+//!
+//! A pointer received from user code can point anywhere: to unmapped memory, to kernel memory or
+//! to memory the user code can read but not write. Before copying from (copy-in) or to (copy-out)
+//! user memory, every page of the range is looked up in the current address space and it must be
+//! present and accessible from user mode (and writable, for copy-out).
+use core::marker::PhantomData;
+use core::mem::{size_of, MaybeUninit};
+
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::memory::paging::page_table::PageTableEntryFlags;
+
+use super::{SyscallArgument, SyscallError};
+use crate::arch::x86_64::page_flags;
+use crate::memory::address_space::{PAGE_SIZE, USER_SPACE_END};
+
+/// Checks that the range `[address, address + len)` belongs to the user half of the address space
+/// and every page of it is mapped and accessible from user mode.
+///
+/// # Arguments
+/// * `address` - Start of the range.
+/// * `len` - Length of the range in bytes.
+/// * `writable` - Whether the range must also be writable.
+fn validate(address: u64, len: usize, writable: bool) -> Result<(), SyscallError> {
+    let end = address
+        .checked_add(len as u64)
+        .filter(|&end| end <= USER_SPACE_END)
+        .ok_or(SyscallError::BadAddress)?;
+    if len == 0 {
+        return Ok(());
+    }
+    if address == 0 {
+        return Err(SyscallError::BadAddress);
+    }
+
+    let mut required = PageTableEntryFlags::PRESENT | PageTableEntryFlags::USER_ACCESSIBLE;
+    if writable {
+        required |= PageTableEntryFlags::WRITABLE;
+    }
+
+    let mut page = address & !(PAGE_SIZE - 1);
+    while page < end {
+        let flags = page_flags(VirtualMemoryAddress::new(page)).ok_or(SyscallError::BadAddress)?;
+        if flags & required != required {
+            return Err(SyscallError::BadAddress);
+        }
+        page += PAGE_SIZE;
+    }
+
+    Ok(())
+}
+
+/// Copies `destination.len()` bytes from user memory.
+///
+/// # Arguments
+/// * `destination` - Kernel buffer.
+/// * `source` - User address.
+pub fn copy_from_user(destination: &mut [u8], source: u64) -> Result<(), SyscallError> {
+    validate(source, destination.len(), false)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(
+            source as *const u8,
+            destination.as_mut_ptr(),
+            destination.len(),
+        )
+    };
+    Ok(())
+}
+
+/// Copies `source.len()` bytes to user memory.
+///
+/// # Arguments
+/// * `destination` - User address.
+/// * `source` - Kernel buffer.
+pub fn copy_to_user(destination: u64, source: &[u8]) -> Result<(), SyscallError> {
+    validate(destination, source.len(), true)?;
+    unsafe {
+        core::ptr::copy_nonoverlapping(source.as_ptr(), destination as *mut u8, source.len())
+    };
+    Ok(())
+}
+
+/// A pointer to a `T` in user memory.
+pub struct UserPointer<T> {
+    address: u64,
+    phantom: PhantomData<T>,
+}
+
+impl<T: Copy> UserPointer<T> {
+    /// Creates a new user pointer. It is not validated until it is used.
+    ///
+    /// # Arguments
+    /// * `address` - User address.
+    pub const fn new(address: u64) -> Self {
+        Self {
+            address,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Copies the value from user memory.
+    ///
+    /// `T` must be valid for any bit pattern, since user code can write anything there.
+    pub fn read(&self) -> Result<T, SyscallError> {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes =
+            unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr().cast(), size_of::<T>()) };
+        copy_from_user(bytes, self.address)?;
+        Ok(unsafe { value.assume_init() })
+    }
+
+    /// Copies a value to user memory.
+    ///
+    /// # Arguments
+    /// * `value` - Value to copy.
+    pub fn write(&self, value: T) -> Result<(), SyscallError> {
+        let bytes =
+            unsafe { core::slice::from_raw_parts((&value as *const T).cast(), size_of::<T>()) };
+        copy_to_user(self.address, bytes)
+    }
+}
+
+impl<T: Copy> SyscallArgument for UserPointer<T> {
+    fn from_raw(arguments: &mut impl Iterator<Item = u64>) -> Result<Self, SyscallError> {
+        Ok(Self::new(u64::from_raw(arguments)?))
+    }
+}
+
+/// A slice of `T` in user memory. It takes two system call arguments: address and length.
+pub struct UserSlice<T> {
+    address: u64,
+    len: usize,
+    phantom: PhantomData<T>,
+}
+
+impl<T> UserSlice<T> {
+    /// Creates a new user slice. It is not validated until it is used.
+    ///
+    /// # Arguments
+    /// * `address` - User address of the first element.
+    /// * `len` - Number of elements.
+    pub const fn new(address: u64, len: usize) -> Self {
+        Self {
+            address,
+            len,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of elements of the slice.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns if the slice has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl UserSlice<u8> {
+    /// Copies bytes from the slice, starting at `offset`, into `destination`. Returns the number
+    /// of bytes copied, less than the destination length if the end of the slice is reached.
+    ///
+    /// # Arguments
+    /// * `offset` - Offset of the first byte to copy.
+    /// * `destination` - Kernel buffer.
+    pub fn read(&self, offset: usize, destination: &mut [u8]) -> Result<usize, SyscallError> {
+        let count = self.len.saturating_sub(offset).min(destination.len());
+        let address = self.address.checked_add(offset as u64);
+        copy_from_user(
+            &mut destination[..count],
+            address.ok_or(SyscallError::BadAddress)?,
+        )?;
+        Ok(count)
+    }
+
+    /// Copies bytes from `source` into the slice, starting at `offset`. Returns the number of
+    /// bytes copied, less than the source length if the end of the slice is reached.
+    ///
+    /// # Arguments
+    /// * `offset` - Offset of the first byte to write.
+    /// * `source` - Kernel buffer.
+    pub fn write(&self, offset: usize, source: &[u8]) -> Result<usize, SyscallError> {
+        let count = self.len.saturating_sub(offset).min(source.len());
+        let address = self.address.checked_add(offset as u64);
+        copy_to_user(address.ok_or(SyscallError::BadAddress)?, &source[..count])?;
+        Ok(count)
+    }
+}
+
+impl<T> SyscallArgument for UserSlice<T> {
+    fn from_raw(arguments: &mut impl Iterator<Item = u64>) -> Result<Self, SyscallError> {
+        let address = u64::from_raw(arguments)?;
+        let len = usize::from_raw(arguments)?;
+        Ok(Self::new(address, len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn ranges_out_of_user_space_are_rejected() {
+        assert_eq!(Err(SyscallError::BadAddress), validate(0, 8, false));
+        assert_eq!(
+            Err(SyscallError::BadAddress),
+            validate(USER_SPACE_END - 4, 8, false)
+        );
+        assert_eq!(
+            Err(SyscallError::BadAddress),
+            validate(u64::MAX - 4, 8, true)
+        );
+        assert_eq!(
+            Err(SyscallError::BadAddress),
+            validate(0xffff_8000_0000_0000, 1, false)
+        );
+
+        // Nothing is accessed
+        assert_eq!(Ok(()), validate(0x1000, 0, true));
+    }
+
+    #[test_case]
+    fn slice_reads_stop_at_the_end() {
+        let slice = UserSlice::<u8>::new(0x1000, 4);
+        let mut buffer = [0u8; 8];
+
+        // Nothing is left to copy, so the address is not even checked
+        assert_eq!(Ok(0), slice.read(4, &mut buffer));
+        assert_eq!(Ok(0), slice.write(8, &buffer));
+    }
+}
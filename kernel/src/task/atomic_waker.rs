@@ -0,0 +1,108 @@
+//! Waker shared with interrupt handlers
+//!
+//! A task that waits for an event registers its waker in an [AtomicWaker], and whoever produces
+//! the event (usually an interrupt handler) wakes it up. The waker is kept behind an
+//! [IrqSafeMutex], so it can be woken from a handler that interrupted the task while it was
+//! registering it.
+use core::task::Waker;
+
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+
+/// Holds the waker of the task waiting for an event.
+pub struct AtomicWaker {
+    waker: IrqSafeMutex<Option<Waker>>,
+}
+
+impl AtomicWaker {
+    /// Creates an empty waker.
+    pub const fn new() -> Self {
+        Self {
+            waker: IrqSafeMutex::new(None),
+        }
+    }
+
+    /// Registers the waker woken up by the next [wake](Self::wake). It replaces the previous one.
+    ///
+    /// # Arguments
+    /// * `waker` - Waker of the task waiting for the event.
+    pub fn register(&self, waker: &Waker) {
+        let mut registered = self.waker.lock();
+        match registered.as_ref() {
+            Some(current) if current.will_wake(waker) => {}
+            _ => *registered = Some(waker.clone()),
+        }
+    }
+
+    /// Wakes up the registered waker, if any, and removes it.
+    pub fn wake(&self) {
+        // The lock is released before waking, the waker could register itself again
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Removes the registered waker and returns it.
+    pub fn take(&self) -> Option<Waker> {
+        self.waker.lock().take()
+    }
+}
+
+impl Default for AtomicWaker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::ptr;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+    use core::task::{RawWaker, RawWakerVTable};
+
+    static WAKES: AtomicUsize = AtomicUsize::new(0);
+
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {
+            WAKES.fetch_add(1, Ordering::Relaxed);
+        },
+        |_| {},
+    );
+
+    /// Returns a waker that counts how many times it is woken up in [WAKES].
+    fn counting_waker() -> Waker {
+        unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+    }
+
+    #[test_case]
+    fn wake_should_wake_the_registered_waker_once() {
+        let atomic_waker = AtomicWaker::new();
+        let waker = counting_waker();
+        let wakes = WAKES.load(Ordering::Relaxed);
+
+        atomic_waker.wake();
+        assert_eq!(wakes, WAKES.load(Ordering::Relaxed));
+
+        atomic_waker.register(&waker);
+        atomic_waker.wake();
+        atomic_waker.wake();
+        assert_eq!(wakes + 1, WAKES.load(Ordering::Relaxed));
+    }
+
+    #[test_case]
+    fn take_should_remove_the_registered_waker() {
+        let atomic_waker = AtomicWaker::new();
+        let wakes = WAKES.load(Ordering::Relaxed);
+
+        atomic_waker.register(&counting_waker());
+        assert!(atomic_waker.take().is_some());
+        atomic_waker.wake();
+
+        assert!(atomic_waker.take().is_none());
+        assert_eq!(wakes, WAKES.load(Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,313 @@
+//! Cooperative executor for `async` tasks
+//!
+//! The tasks are stored in a fixed size table (we do not have a heap yet): every slot has room
+//! for a future of up to `TASK_SIZE` bytes. The future is moved into its slot when the task is
+//! spawned and never moves again until it finishes, so it can be safely pinned there.
+//!
+//! Every slot has a `woken` flag. The wakers handed to the futures just point to their slot and
+//! set that flag, so they can be used from interrupt handlers. The run loop polls the woken
+//! tasks and, when there is nothing left to do, gives the CPU to other threads or halts it until
+//! the next interrupt.
+//!
+//! For more information:
+//! https://os.phil-opp.com/async-await/
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::mem::{align_of, size_of, MaybeUninit};
+use core::pin::Pin;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use x86_64::instructions::interrupts;
+
+use crate::interrupts::deferred::{has_deferred_work, run_deferred_work};
+use crate::thread;
+
+/// Maximum number of tasks that can exist at the same time.
+const MAX_TASKS: usize = 32;
+
+/// Maximum size (in bytes) of the future of a task.
+const TASK_SIZE: usize = 1024;
+
+/// Maximum alignment of the future of a task.
+const TASK_ALIGN: usize = 16;
+
+// Slot states
+const FREE: u8 = 0;
+const RESERVED: u8 = 1;
+const OCCUPIED: u8 = 2;
+
+/// Global executor, driven by [run].
+static EXECUTOR: Executor = Executor::new();
+
+/// Represents all the possible errors that can happen when spawning a task.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ExecutorError {
+    /// There are no free slots for a new task.
+    TooManyTasks,
+
+    /// The future does not fit in a task slot.
+    TaskTooLarge,
+}
+
+/// Storage for the future of a task.
+#[repr(C, align(16))]
+struct TaskStorage([MaybeUninit<u8>; TASK_SIZE]);
+
+/// Type erased operations over the future stored in a slot.
+#[derive(Clone, Copy)]
+struct TaskVTable {
+    poll: unsafe fn(*mut u8, &mut Context) -> Poll<()>,
+    drop: unsafe fn(*mut u8),
+}
+
+/// A task slot.
+struct TaskSlot {
+    /// Whether the slot is free, being filled or holds a task.
+    state: AtomicU8,
+
+    /// Whether the task must be polled.
+    woken: AtomicBool,
+
+    /// Operations over the stored future, only valid when the slot is occupied.
+    vtable: UnsafeCell<MaybeUninit<TaskVTable>>,
+
+    /// The future, only initialized when the slot is occupied.
+    storage: UnsafeCell<TaskStorage>,
+}
+
+impl TaskSlot {
+    /// Returns a pointer to the future stored in the slot.
+    fn future(&self) -> *mut u8 {
+        self.storage.get().cast()
+    }
+}
+
+/// A cooperative task executor.
+pub struct Executor {
+    slots: [TaskSlot; MAX_TASKS],
+}
+
+impl Executor {
+    /// Creates a new executor without tasks.
+    pub const fn new() -> Self {
+        Self {
+            slots: [const {
+                TaskSlot {
+                    state: AtomicU8::new(FREE),
+                    woken: AtomicBool::new(false),
+                    vtable: UnsafeCell::new(MaybeUninit::uninit()),
+                    storage: UnsafeCell::new(TaskStorage([MaybeUninit::uninit(); TASK_SIZE])),
+                }
+            }; MAX_TASKS],
+        }
+    }
+
+    /// Adds a new task. It is polled for the first time in the next round of the executor.
+    ///
+    /// This function does not block, tasks can be spawned from other tasks.
+    ///
+    /// # Arguments
+    /// * `future` - Future driven by the task.
+    pub fn spawn<F>(&self, future: F) -> Result<(), ExecutorError>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        if size_of::<F>() > TASK_SIZE || align_of::<F>() > TASK_ALIGN {
+            return Err(ExecutorError::TaskTooLarge);
+        }
+
+        let slot = self
+            .slots
+            .iter()
+            .find(|slot| {
+                slot.state
+                    .compare_exchange(FREE, RESERVED, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .ok_or(ExecutorError::TooManyTasks)?;
+
+        unsafe {
+            slot.future().cast::<F>().write(future);
+            (*slot.vtable.get()).write(TaskVTable {
+                poll: poll_future::<F>,
+                drop: drop_future::<F>,
+            });
+        }
+        slot.state.store(OCCUPIED, Ordering::Release);
+        slot.woken.store(true, Ordering::Release);
+
+        Ok(())
+    }
+
+    /// Polls every woken task once. Returns the number of tasks polled.
+    ///
+    /// Only one process can run the tasks of an executor at the same time.
+    pub fn run_ready_tasks(&'static self) -> usize {
+        let mut polled = 0;
+        for slot in self.slots.iter() {
+            if slot.state.load(Ordering::Acquire) != OCCUPIED
+                || !slot.woken.swap(false, Ordering::Acquire)
+            {
+                continue;
+            }
+
+            let waker = unsafe { Waker::from_raw(raw_waker(slot)) };
+            let mut context = Context::from_waker(&waker);
+            let vtable = unsafe { (*slot.vtable.get()).assume_init() };
+            let future = slot.future();
+
+            if let Poll::Ready(()) = unsafe { (vtable.poll)(future, &mut context) } {
+                unsafe { (vtable.drop)(future) };
+                slot.woken.store(false, Ordering::Relaxed);
+                slot.state.store(FREE, Ordering::Release);
+            }
+            polled += 1;
+        }
+        polled
+    }
+
+    /// Returns if there are tasks waiting to be polled.
+    pub fn has_ready_tasks(&self) -> bool {
+        self.slots.iter().any(|slot| {
+            slot.state.load(Ordering::Acquire) == OCCUPIED && slot.woken.load(Ordering::Acquire)
+        })
+    }
+
+    /// Runs the tasks and the deferred interrupt work forever. When there is nothing to do the
+    /// CPU is given to other threads (see [thread::idle]).
+    pub fn run(&'static self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            run_deferred_work();
+
+            // Checking and halting is done with interrupts disabled, so a task woken by an
+            // interrupt right after the check still wakes up the CPU
+            interrupts::disable();
+            if self.has_ready_tasks() || has_deferred_work() {
+                interrupts::enable();
+            } else {
+                thread::idle();
+            }
+        }
+    }
+}
+
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+unsafe impl Sync for Executor {}
+
+/// Spawns a task in the global executor. See [Executor::spawn].
+///
+/// # Arguments
+/// * `future` - Future driven by the task.
+pub fn spawn<F>(future: F) -> Result<(), ExecutorError>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    EXECUTOR.spawn(future)
+}
+
+/// Runs the global executor forever. See [Executor::run].
+pub fn run() -> ! {
+    EXECUTOR.run()
+}
+
+/// Polls the future of type `F` stored at `future`.
+unsafe fn poll_future<F: Future<Output = ()>>(future: *mut u8, context: &mut Context) -> Poll<()> {
+    // The future never moves out of its slot
+    Pin::new_unchecked(&mut *future.cast::<F>()).poll(context)
+}
+
+/// Drops the future of type `F` stored at `future`.
+unsafe fn drop_future<F>(future: *mut u8) {
+    ptr::drop_in_place(future.cast::<F>());
+}
+
+// Waker implementation. The data pointer is the task slot.
+const WAKER_VTABLE: RawWakerVTable = RawWakerVTable::new(clone_waker, wake, wake, drop_waker);
+
+fn raw_waker(slot: &'static TaskSlot) -> RawWaker {
+    RawWaker::new(slot as *const TaskSlot as *const (), &WAKER_VTABLE)
+}
+
+unsafe fn clone_waker(slot: *const ()) -> RawWaker {
+    raw_waker(&*slot.cast::<TaskSlot>())
+}
+
+unsafe fn wake(slot: *const ()) {
+    // If the task already finished and the slot was reused, the new task gets a spurious wake up
+    (*slot.cast::<TaskSlot>())
+        .woken
+        .store(true, Ordering::Release);
+}
+
+unsafe fn drop_waker(_: *const ()) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::yield_now;
+    use core::sync::atomic::AtomicUsize;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    #[test_case]
+    fn spawned_task_runs_to_completion() {
+        static TEST_EXECUTOR: Executor = Executor::new();
+        COUNTER.store(0, Ordering::Relaxed);
+
+        assert!(TEST_EXECUTOR
+            .spawn(async {
+                COUNTER.fetch_add(1, Ordering::Relaxed);
+                yield_now().await;
+                COUNTER.fetch_add(1, Ordering::Relaxed);
+            })
+            .is_ok());
+
+        assert_eq!(1, TEST_EXECUTOR.run_ready_tasks());
+        assert_eq!(1, COUNTER.load(Ordering::Relaxed));
+        // The task woke itself before yielding
+        assert!(TEST_EXECUTOR.has_ready_tasks());
+
+        assert_eq!(1, TEST_EXECUTOR.run_ready_tasks());
+        assert_eq!(2, COUNTER.load(Ordering::Relaxed));
+        assert!(!TEST_EXECUTOR.has_ready_tasks());
+        assert_eq!(0, TEST_EXECUTOR.run_ready_tasks());
+    }
+
+    #[test_case]
+    fn large_task_should_fail() {
+        static TEST_EXECUTOR: Executor = Executor::new();
+        let buffer = [0u8; TASK_SIZE + 1];
+
+        assert_eq!(
+            Err(ExecutorError::TaskTooLarge),
+            TEST_EXECUTOR.spawn(async move {
+                core::hint::black_box(&buffer);
+            })
+        );
+    }
+
+    #[test_case]
+    fn full_executor_should_fail() {
+        static TEST_EXECUTOR: Executor = Executor::new();
+        for _ in 0..MAX_TASKS {
+            assert!(TEST_EXECUTOR.spawn(async {}).is_ok());
+        }
+
+        assert_eq!(
+            Err(ExecutorError::TooManyTasks),
+            TEST_EXECUTOR.spawn(async {})
+        );
+
+        // Finished tasks free their slots
+        assert_eq!(MAX_TASKS, TEST_EXECUTOR.run_ready_tasks());
+        assert!(TEST_EXECUTOR.spawn(async {}).is_ok());
+    }
+}
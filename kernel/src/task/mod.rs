@@ -0,0 +1,41 @@
+//! Kernel `async` tasks
+//!
+//! Tasks are futures driven by a cooperative [executor]: each one runs until it has to wait for
+//! something (an `.await` that returns `Poll::Pending`) and then gives the CPU back to the next
+//! one. Long running tasks must [yield_now] from time to time, otherwise they starve the rest.
+pub mod atomic_waker;
+pub mod executor;
+pub mod stream;
+
+pub use atomic_waker::AtomicWaker;
+pub use executor::{run, spawn, ExecutorError};
+pub use stream::Stream;
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// Gives the CPU to the other ready tasks. The current task is polled again in the next round of
+/// the executor.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// Future returned by [yield_now].
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+
+        self.yielded = true;
+        context.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,97 @@
+//! Asynchronous streams
+//!
+//! A stream is the asynchronous version of an iterator: a source of values that may not be ready
+//! yet. [Stream] is a minimal version of the trait of the `futures` crates, with just what the
+//! kernel uses: polling for the next value and awaiting it with [next](Stream::next).
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+/// A source of values produced asynchronously.
+pub trait Stream {
+    /// Type of the values.
+    type Item;
+
+    /// Tries to get the next value. Returns `Poll::Pending` if it is not ready yet, the waker of
+    /// the context is woken up when it is. `Poll::Ready(None)` means that the stream finished.
+    ///
+    /// # Arguments
+    /// * `context` - Context of the task polling the stream.
+    fn poll_next(self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<Self::Item>>;
+
+    /// Returns a future that resolves to the next value, or to `None` if the stream finished.
+    fn next(&mut self) -> Next<'_, Self>
+    where
+        Self: Unpin,
+    {
+        Next { stream: self }
+    }
+}
+
+/// Future returned by [Stream::next].
+pub struct Next<'a, S: ?Sized> {
+    stream: &'a mut S,
+}
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(context)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::task::Waker;
+
+    /// Stream of the numbers from 1 to `last`, that is pending once before every number.
+    struct Countdown {
+        next: u8,
+        last: u8,
+        ready: bool,
+    }
+
+    impl Stream for Countdown {
+        type Item = u8;
+
+        fn poll_next(mut self: Pin<&mut Self>, context: &mut Context) -> Poll<Option<u8>> {
+            if self.next > self.last {
+                return Poll::Ready(None);
+            }
+            if !self.ready {
+                self.ready = true;
+                context.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            self.ready = false;
+            self.next += 1;
+            Poll::Ready(Some(self.next - 1))
+        }
+    }
+
+    #[test_case]
+    fn next_should_return_the_values_in_order() {
+        let mut stream = Countdown {
+            next: 1,
+            last: 2,
+            ready: false,
+        };
+        let mut context = Context::from_waker(Waker::noop());
+
+        for expected in [None, Some(Some(1)), None, Some(Some(2)), Some(None)] {
+            let poll = Pin::new(&mut stream.next()).poll(&mut context);
+            assert_eq!(expected, poll_value(poll));
+        }
+    }
+
+    /// Returns the value of a poll, or `None` if it is pending.
+    fn poll_value<T>(poll: Poll<T>) -> Option<T> {
+        match poll {
+            Poll::Ready(value) => Some(value),
+            Poll::Pending => None,
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Thread context switching
+//!
+//! A thread that is not running keeps its callee-saved registers (RBP, RBX, R12 - R15) pushed on
+//! its own stack. The caller-saved registers do not need to be stored: the compiler already saved
+//! the ones it cares about before calling [switch], as in any other function call. So the context
+//! of a thread is just its stack pointer.
+//!
+//! Switching pushes the callee-saved registers of the current thread, saves its stack pointer,
+//! loads the stack pointer of the next thread, pops its registers and returns to wherever the next
+//! thread called [switch] from.
+//!
+//! A new thread never called [switch], so its stack is prepared to look like it did:
+//!
+//!  stack top -> | return address (0)   | <- RSP when `entry` starts executing
+//!               | SS                   |
+//!               | RSP                  |
+//!               | RFLAGS               |  Interrupt stack frame popped by `iretq`
+//!               | CS                   |
+//!               | RIP (`entry`)        |
+//!               | thread_trampoline    | <- Return address of `switch`
+//!               | RBP, RBX, R12 - R15  | <- Saved stack pointer
+//!
+//! The trampoline moves the function and argument (stored in R12 and R13) to the registers used to
+//! pass the first two arguments and executes `iretq`, that loads the code segment, enables the
+//! interrupts and jumps to `entry`.
+//!
+//! For more information:
+//! https://wiki.osdev.org/Context_Switching
+//! https://wiki.osdev.org/Kernel_Multitasking
+use core::arch::naked_asm;
+use core::mem::size_of;
+
+/// RFLAGS of a new thread: interrupts enabled (bit 9) and the reserved bit 1, that is always set.
+const INITIAL_RFLAGS: u64 = 1 << 9 | 1 << 1;
+
+/// Saved context of a thread that is not running.
+#[derive(Debug)]
+#[repr(C)]
+pub(super) struct Context {
+    /// Stack pointer, pointing to the saved callee-saved registers.
+    rsp: u64,
+}
+
+/// Initial stack of a new thread, from the lowest to the highest address.
+#[repr(C)]
+struct InitialFrame {
+    r15: u64,
+    r14: u64,
+    r13: u64,
+    r12: u64,
+    rbx: u64,
+    rbp: u64,
+    trampoline: u64,
+    rip: u64,
+    cs: u64,
+    rflags: u64,
+    rsp: u64,
+    ss: u64,
+    return_address: u64,
+}
+
+impl Context {
+    /// Creates the context of the thread that is already running (its registers are saved the
+    /// first time it switches to other thread).
+    pub(super) const fn running() -> Self {
+        Self { rsp: 0 }
+    }
+
+    /// Prepares the stack of a new thread, so switching to it calls `entry(function, argument)`
+    /// with the interrupts enabled.
+    ///
+    /// # Arguments
+    /// * `stack_top` - Highest address of the stack, aligned to 16 bytes.
+    /// * `entry` - Function executed by the thread. It must never return.
+    /// * `function` - First argument of `entry`.
+    /// * `argument` - Second argument of `entry`.
+    /// * `code_selector` - Code segment selector loaded when the thread starts.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the stack is valid, unused and
+    /// aligned.
+    pub(super) unsafe fn new(
+        stack_top: u64,
+        entry: extern "C" fn(usize, usize) -> !,
+        function: usize,
+        argument: usize,
+        code_selector: u16,
+    ) -> Self {
+        let frame_address = stack_top - size_of::<InitialFrame>() as u64;
+        (frame_address as *mut InitialFrame).write(InitialFrame {
+            r15: 0,
+            r14: 0,
+            r13: argument as u64,
+            r12: function as u64,
+            rbx: 0,
+            rbp: 0,
+            trampoline: thread_trampoline as unsafe extern "C" fn() as usize as u64,
+            rip: entry as usize as u64,
+            cs: u64::from(code_selector),
+            rflags: INITIAL_RFLAGS,
+            // As if `entry` had been called: the return address is on top of the stack
+            rsp: stack_top - size_of::<u64>() as u64,
+            // A null stack segment is valid in 64 bit mode for the ring 0
+            ss: 0,
+            return_address: 0,
+        });
+
+        Self { rsp: frame_address }
+    }
+}
+
+/// Saves the context of the current thread in `current` and restores the context in `next`. It
+/// returns when another thread switches back to `current`.
+///
+/// # Arguments
+/// * `current` - Where the context of the current thread is saved.
+/// * `next` - Context of the thread to run.
+///
+/// # Safety
+///
+/// This is unsafe because:
+/// - The interrupts must be disabled.
+/// - `next` must be a context saved by this function or created with [Context::new], whose stack
+///   is still valid.
+pub(super) unsafe fn switch(current: *mut Context, next: *const Context) {
+    switch_context(current.cast::<u64>(), (*next).rsp);
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn switch_context(current_rsp: *mut u64, next_rsp: u64) {
+    naked_asm!(
+        "push rbp",
+        "push rbx",
+        "push r12",
+        "push r13",
+        "push r14",
+        "push r15",
+        "mov [rdi], rsp",
+        "mov rsp, rsi",
+        "pop r15",
+        "pop r14",
+        "pop r13",
+        "pop r12",
+        "pop rbx",
+        "pop rbp",
+        "ret",
+    )
+}
+
+#[unsafe(naked)]
+unsafe extern "C" fn thread_trampoline() {
+    naked_asm!("mov rdi, r12", "mov rsi, r13", "iretq")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" fn entry(_: usize, _: usize) -> ! {
+        unreachable!()
+    }
+
+    #[test_case]
+    fn new_thread_frame_is_aligned_for_entry() {
+        #[repr(C, align(16))]
+        struct TestStack([u64; 32]);
+
+        let mut stack = TestStack([0; 32]);
+        let stack_top = stack.0.as_mut_ptr() as u64 + size_of::<TestStack>() as u64;
+        let entry: extern "C" fn(usize, usize) -> ! = entry;
+        let context = unsafe { Context::new(stack_top, entry, 1, 2, 8) };
+
+        let frame = unsafe { &*(context.rsp as *const InitialFrame) };
+        assert_eq!(1, frame.r12);
+        assert_eq!(2, frame.r13);
+        assert_eq!(8, frame.cs);
+        assert_eq!(entry as usize as u64, frame.rip);
+        // On function entry, RSP + 8 must be aligned to 16 bytes
+        assert_eq!(8, frame.rsp % 16);
+        assert_eq!(stack_top, frame.rsp + 8);
+    }
+}
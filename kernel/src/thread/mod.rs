@@ -0,0 +1,648 @@
+//! Kernel threads
+//!
+//! A kernel thread is a function running on its own stack, with its own saved registers. Unlike
+//! the [async tasks](crate::task), threads can block anywhere: the CPU is given to other thread by
+//! [switching the context](context), and taken back later exactly where it was left.
+//!
+//! The threads are kept in a fixed size table (we do not have a heap yet). The first slot belongs
+//! to the boot thread, the one that was running the kernel initialization when the table was
+//! created. The other slots get a statically reserved [stack] with a guard page.
+//!
+//! Threads are [scheduled](scheduler) by priority, in round robin order among the ones with the
+//! same priority. The timer interrupt preempts the running thread when its time slice is over.
+//!
+//! Every thread has a save area for the x87, SSE and AVX registers, that are switched lazily (see
+//! [claim_fpu]).
+mod context;
+mod run_queue;
+mod scheduler;
+mod stack;
+
+pub(crate) use scheduler::{block_on, tick, wake_all, wake_one, PreemptionGuard};
+pub use scheduler::{idle, next_wake_up, schedule};
+
+use core::mem::ManuallyDrop;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use x86_64::instructions::interrupts;
+use x86_64_custom::fpu::ExtendedState;
+use x86_64_custom::memory::address::PhysicalMemoryAddress;
+use x86_64_custom::memory::paging::paging_error::PagingError;
+
+use crate::arch::x86_64::{self as arch, selectors};
+use crate::println;
+use crate::synchronization::irq_safe_mutex::IrqSafeMutex;
+use crate::time::{self, duration_to_ticks, ticks_to_duration};
+use context::Context;
+use run_queue::RunQueue;
+use scheduler::{reschedule, TIME_SLICE_TICKS};
+
+/// Maximum number of threads that can exist at the same time, including the boot thread.
+const MAX_THREADS: usize = 16;
+
+/// Slot of the boot thread.
+const BOOT_THREAD: usize = 0;
+
+/// Global table of threads.
+static THREADS: IrqSafeMutex<ThreadTable> = IrqSafeMutex::new(ThreadTable::new());
+
+/// Slot of the running thread. It is a copy of `ThreadTable::current` that can be read without
+/// taking the lock of the table, see [running_slot].
+static RUNNING_SLOT: AtomicUsize = AtomicUsize::new(BOOT_THREAD);
+
+/// Identifier of the next spawned thread.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Represents all the possible errors that can happen when spawning a thread.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ThreadError {
+    /// There are no free slots for a new thread.
+    TooManyThreads,
+
+    /// The guard page of the stack could not be unmapped.
+    GuardPage(PagingError),
+}
+
+/// Unique identifier of a thread.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ThreadId(u64);
+
+impl ThreadId {
+    /// Returns the identifier as a number.
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Priority of a thread. Ready threads with a higher priority always run first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Low = 0,
+    Normal = 1,
+    High = 2,
+}
+
+impl Priority {
+    /// Number of priorities.
+    const COUNT: usize = 3;
+
+    /// All the priorities, from the lowest to the highest.
+    const ALL: [Priority; Self::COUNT] = [Priority::Low, Priority::Normal, Priority::High];
+}
+
+/// State of a thread slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ThreadState {
+    /// The slot is not used.
+    Free,
+
+    /// Waiting for the CPU.
+    Ready,
+
+    /// Using the CPU.
+    Running,
+
+    /// Waiting for some time to elapse.
+    Sleeping,
+
+    /// Waiting for an event (for example, another thread to finish).
+    Blocked,
+
+    /// The thread exited, the exit code is waiting to be collected by `join`.
+    Finished,
+}
+
+/// A thread slot.
+struct Thread {
+    id: ThreadId,
+    state: ThreadState,
+    priority: Priority,
+    context: Context,
+
+    /// Value returned by the thread function.
+    exit_code: usize,
+
+    /// Slot of the thread waiting for this one to finish.
+    joiner: Option<usize>,
+
+    /// Whether nobody is going to join this thread, so its slot is freed when it finishes.
+    detached: bool,
+
+    /// Whether the guard page of the slot's stack is already unmapped.
+    guarded: bool,
+
+    /// Ticks left until the thread can be preempted by another one with the same priority.
+    time_slice: u64,
+
+    /// Ticks the thread spent running.
+    cpu_ticks: u64,
+
+    /// Tick in which a sleeping thread must be woken up.
+    wake_up_tick: u64,
+
+    /// Address of the wait queue a blocked thread is waiting on, zero if none.
+    wait_queue: usize,
+
+    /// Physical address of the PML4 of the thread's user address space, zero if it only runs
+    /// kernel code (it can use any address space then).
+    level_4_table: u64,
+
+    /// x87, SSE and AVX registers, saved when another thread uses them.
+    fpu_state: ExtendedState,
+}
+
+impl Thread {
+    const fn new(state: ThreadState) -> Self {
+        Self {
+            id: ThreadId(0),
+            state,
+            priority: Priority::Normal,
+            context: Context::running(),
+            exit_code: 0,
+            joiner: None,
+            detached: false,
+            guarded: false,
+            time_slice: TIME_SLICE_TICKS,
+            cpu_ticks: 0,
+            wake_up_tick: 0,
+            wait_queue: 0,
+            level_4_table: 0,
+            fpu_state: ExtendedState::new(),
+        }
+    }
+
+    /// Returns if the slot can be used for a new thread.
+    fn is_reusable(&self) -> bool {
+        self.state == ThreadState::Free || (self.state == ThreadState::Finished && self.detached)
+    }
+}
+
+/// Fixed size table of threads.
+struct ThreadTable {
+    threads: [Thread; MAX_THREADS],
+
+    /// Slot of the running thread.
+    current: usize,
+
+    /// Ready threads.
+    run_queue: RunQueue,
+
+    /// Slot of the thread whose state is loaded in the x87, SSE and AVX registers. The registers
+    /// are per processor, so there is a single owner because the threads only run on the
+    /// bootstrap processor (see [claim_fpu]).
+    fpu_owner: Option<usize>,
+}
+
+impl ThreadTable {
+    /// Creates a table with the boot thread running.
+    const fn new() -> Self {
+        let mut threads = [const { Thread::new(ThreadState::Free) }; MAX_THREADS];
+        threads[BOOT_THREAD] = Thread::new(ThreadState::Running);
+
+        Self {
+            threads,
+            current: BOOT_THREAD,
+            run_queue: RunQueue::new(),
+            fpu_owner: None,
+        }
+    }
+
+    /// Returns a slot that can be used for a new thread.
+    fn free_slot(&self) -> Option<usize> {
+        (0..MAX_THREADS)
+            .filter(|&index| index != BOOT_THREAD && index != self.current)
+            .find(|&index| self.threads[index].is_reusable())
+    }
+
+    /// Marks a thread as ready and adds it to the run queue. If it has a higher priority than
+    /// the running thread, the running thread is preempted.
+    ///
+    /// # Arguments
+    /// * `index` - Slot of the thread.
+    fn make_ready(&mut self, index: usize) {
+        let thread = &mut self.threads[index];
+        thread.state = ThreadState::Ready;
+        let priority = thread.priority;
+        self.run_queue.push(index, priority);
+
+        let current = &self.threads[self.current];
+        if current.state == ThreadState::Running && priority > current.priority {
+            scheduler::request_reschedule();
+        }
+    }
+
+    /// Wakes up the sleeping threads that are due. Returns the tick of the nearest wake up of the
+    /// ones that keep sleeping.
+    ///
+    /// # Arguments
+    /// * `now` - Current tick.
+    fn wake_sleepers(&mut self, now: u64) -> Option<u64> {
+        let mut next_wake_up = None;
+        for index in 0..MAX_THREADS {
+            let thread = &self.threads[index];
+            if thread.state != ThreadState::Sleeping {
+                continue;
+            }
+
+            if thread.wake_up_tick <= now {
+                self.make_ready(index);
+            } else {
+                let wake_up_tick = thread.wake_up_tick;
+                next_wake_up =
+                    Some(next_wake_up.map_or(wake_up_tick, |next: u64| next.min(wake_up_tick)));
+            }
+        }
+        next_wake_up
+    }
+
+    /// Wakes up the threads blocked on a wait queue.
+    ///
+    /// # Arguments
+    /// * `wait_queue` - Address of the wait queue.
+    /// * `all` - Whether to wake up all the threads or only the first one found.
+    fn wake_waiters(&mut self, wait_queue: usize, all: bool) {
+        for index in 0..MAX_THREADS {
+            let thread = &mut self.threads[index];
+            if thread.state != ThreadState::Blocked || thread.wait_queue != wait_queue {
+                continue;
+            }
+
+            thread.wait_queue = 0;
+            self.make_ready(index);
+            if !all {
+                return;
+            }
+        }
+    }
+}
+
+/// Entry point of every spawned thread.
+///
+/// # Arguments
+/// * `function` - Thread function, a `fn(usize) -> usize`.
+/// * `argument` - Argument passed to `function`.
+extern "C" fn thread_start(function: usize, argument: usize) -> ! {
+    let function: fn(usize) -> usize = unsafe { core::mem::transmute(function) };
+    exit(function(argument))
+}
+
+/// Spawns a new thread with [Normal](Priority::Normal) priority that executes
+/// `function(argument)`. See [spawn_with_priority].
+///
+/// # Arguments
+/// * `function` - Function to execute. Its return value is the exit code of the thread.
+/// * `argument` - Argument passed to `function`.
+pub fn spawn(function: fn(usize) -> usize, argument: usize) -> Result<JoinHandle, ThreadError> {
+    spawn_with_priority(function, argument, Priority::Normal)
+}
+
+/// Spawns a new thread that executes `function(argument)`. If its priority is higher than the
+/// current thread's one, it preempts the current thread at the next [schedule] point. Otherwise
+/// it waits for its turn in the run queue.
+///
+/// # Arguments
+/// * `function` - Function to execute. Its return value is the exit code of the thread.
+/// * `argument` - Argument passed to `function`.
+/// * `priority` - Priority of the thread.
+pub fn spawn_with_priority(
+    function: fn(usize) -> usize,
+    argument: usize,
+    priority: Priority,
+) -> Result<JoinHandle, ThreadError> {
+    let mut threads = THREADS.lock();
+    let index = threads.free_slot().ok_or(ThreadError::TooManyThreads)?;
+    let stack_index = index - 1;
+
+    let thread = &mut threads.threads[index];
+    if !thread.guarded {
+        unsafe { stack::install_guard_page(stack_index) }.map_err(ThreadError::GuardPage)?;
+        thread.guarded = true;
+    }
+
+    let id = ThreadId(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    *thread = Thread {
+        id,
+        state: ThreadState::Free,
+        priority,
+        context: unsafe {
+            Context::new(
+                stack::top(stack_index),
+                thread_start,
+                function as usize,
+                argument,
+                *selectors().cs,
+            )
+        },
+        exit_code: 0,
+        joiner: None,
+        detached: false,
+        guarded: true,
+        time_slice: TIME_SLICE_TICKS,
+        cpu_ticks: 0,
+        wake_up_tick: 0,
+        wait_queue: 0,
+        level_4_table: 0,
+        fpu_state: ExtendedState::new(),
+    };
+    threads.make_ready(index);
+
+    Ok(JoinHandle { index, id })
+}
+
+/// Gives the CPU to the next ready thread, if there is any. The current thread goes to the end of
+/// the run queue, so only threads with, at least, its priority run before it.
+pub fn yield_now() {
+    interrupts::without_interrupts(|| {
+        let mut threads = THREADS.lock();
+        let current = threads.current;
+        threads.make_ready(current);
+        drop(threads);
+
+        reschedule();
+    });
+}
+
+/// Blocks the current thread for, at least, the given duration. Other threads use the CPU in the
+/// meantime.
+///
+/// # Arguments
+/// * `duration` - Time to sleep.
+pub fn sleep(duration: Duration) {
+    let ticks = duration_to_ticks(duration);
+    if ticks == 0 {
+        yield_now();
+        return;
+    }
+
+    interrupts::without_interrupts(|| scheduler::sleep_until(wake_up_tick(time::ticks(), ticks)));
+}
+
+/// Returns the tick a thread that sleeps the given number of ticks is woken up at. It saturates,
+/// the duration comes from user programs too.
+///
+/// # Arguments
+/// * `now` - Current tick.
+/// * `ticks` - Number of ticks to sleep.
+fn wake_up_tick(now: u64, ticks: u64) -> u64 {
+    // We add one tick because we might be in the middle of the current one
+    now.saturating_add(ticks).saturating_add(1)
+}
+
+/// Changes the priority of the current thread.
+///
+/// # Arguments
+/// * `priority` - New priority.
+pub fn set_priority(priority: Priority) {
+    let mut threads = THREADS.lock();
+    let current = threads.current;
+    threads.threads[current].priority = priority;
+    if threads.run_queue.highest_priority() > Some(priority) {
+        scheduler::request_reschedule();
+    }
+}
+
+/// Makes the current thread run in a user address space from now on, and switches to it.
+///
+/// # Arguments
+/// * `level_4_table` - Physical address of the PML4 of the address space.
+///
+/// # Safety
+/// The address space must map the kernel, and it must never be freed while the thread exists.
+pub(crate) unsafe fn set_address_space(level_4_table: PhysicalMemoryAddress) {
+    interrupts::without_interrupts(|| {
+        let mut threads = THREADS.lock();
+        let current = threads.current;
+        threads.threads[current].level_4_table = level_4_table.as_u64();
+        arch::switch_address_space(level_4_table);
+    });
+}
+
+/// Gives the x87, SSE and AVX registers to the current thread: the state of the thread that used
+/// them last is saved and the state of the current thread is restored. Called by the device not
+/// available (#NM) handler, raised by the first use of the registers after a context switch.
+///
+/// The kernel does not use those registers, so the handler never interrupts code that holds the
+/// thread table lock.
+///
+/// The owner is only valid for the registers of the bootstrap processor, the only one that runs
+/// threads. The application processors must never use the registers.
+pub(crate) fn claim_fpu() {
+    assert!(
+        arch::smp::per_cpu::current().is_none_or(|cpu| cpu.index() == 0),
+        "x87, SSE or AVX instruction on an application processor"
+    );
+    let mut threads = THREADS.lock();
+    let current = threads.current;
+    arch::fpu::set_available(true);
+    if threads.fpu_owner == Some(current) {
+        return;
+    }
+
+    unsafe {
+        if let Some(owner) = threads.fpu_owner {
+            arch::fpu::save(&mut threads.threads[owner].fpu_state);
+        }
+        arch::fpu::restore(&threads.threads[current].fpu_state);
+    }
+    threads.fpu_owner = Some(current);
+}
+
+/// Finishes the current thread with the given exit code, that is returned to the thread joining
+/// it.
+///
+/// # Arguments
+/// * `exit_code` - Exit code of the thread.
+pub fn exit(exit_code: usize) -> ! {
+    interrupts::disable();
+
+    let mut threads = THREADS.lock();
+    let current = threads.current;
+    let thread = &mut threads.threads[current];
+    thread.state = ThreadState::Finished;
+    thread.exit_code = exit_code;
+    if let Some(joiner) = thread.joiner.take() {
+        threads.make_ready(joiner);
+    }
+    // The registers are not saved anymore, the slot can be reused
+    if threads.fpu_owner == Some(current) {
+        threads.fpu_owner = None;
+    }
+    drop(threads);
+
+    reschedule();
+    unreachable!("a finished thread was scheduled again");
+}
+
+/// Returns the identifier of the running thread.
+pub fn current() -> ThreadId {
+    let threads = THREADS.lock();
+    threads.threads[threads.current].id
+}
+
+/// Returns the table slot of the running thread. Unlike [current], it does not take any lock, so
+/// it can be used by the locks themselves.
+pub(crate) fn running_slot() -> usize {
+    RUNNING_SLOT.load(Ordering::Relaxed)
+}
+
+/// Returns the CPU time used by a thread, or `None` if the thread does not exist anymore.
+///
+/// # Arguments
+/// * `id` - Identifier of the thread.
+pub fn cpu_time(id: ThreadId) -> Option<Duration> {
+    let threads = THREADS.lock();
+    threads
+        .threads
+        .iter()
+        .find(|thread| thread.id == id && thread.state != ThreadState::Free)
+        .map(|thread| ticks_to_duration(thread.cpu_ticks))
+}
+
+/// Returns the time the CPU spent without running any thread.
+pub fn idle_time() -> Duration {
+    ticks_to_duration(scheduler::idle_ticks())
+}
+
+/// Prints the state, priority and CPU time of every thread.
+pub fn print_thread_statistics() {
+    // Copied so the table is not locked while printing
+    let threads: [(ThreadId, ThreadState, Priority, u64); MAX_THREADS] = {
+        let threads = THREADS.lock();
+        core::array::from_fn(|index| {
+            let thread = &threads.threads[index];
+            (thread.id, thread.state, thread.priority, thread.cpu_ticks)
+        })
+    };
+
+    for (id, state, priority, cpu_ticks) in threads {
+        if state == ThreadState::Free {
+            continue;
+        }
+
+        println!(
+            "Thread {}: {:?}, {:?} priority, CPU time {:?}",
+            id.0,
+            state,
+            priority,
+            ticks_to_duration(cpu_ticks)
+        );
+    }
+    println!("Idle time: {:?}", idle_time());
+}
+
+/// An owned permission to join a thread (block until it finishes and get its exit code).
+///
+/// Dropping the handle detaches the thread: its slot is freed as soon as it finishes.
+#[must_use = "dropping the handle detaches the thread"]
+pub struct JoinHandle {
+    /// Slot of the thread. It can not be reused until the thread is joined or detached.
+    index: usize,
+    id: ThreadId,
+}
+
+impl JoinHandle {
+    /// Returns the identifier of the thread.
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    /// Blocks until the thread finishes and returns its exit code.
+    pub fn join(self) -> usize {
+        let handle = ManuallyDrop::new(self);
+
+        interrupts::without_interrupts(|| loop {
+            let mut threads = THREADS.lock();
+            let current = threads.current;
+            let thread = &mut threads.threads[handle.index];
+            if thread.state == ThreadState::Finished {
+                thread.state = ThreadState::Free;
+                return thread.exit_code;
+            }
+
+            thread.joiner = Some(current);
+            threads.threads[current].state = ThreadState::Blocked;
+            drop(threads);
+
+            reschedule();
+        })
+    }
+}
+
+impl Drop for JoinHandle {
+    fn drop(&mut self) {
+        let mut threads = THREADS.lock();
+        let thread = &mut threads.threads[self.index];
+        if thread.state == ThreadState::Finished {
+            thread.state = ThreadState::Free;
+        } else {
+            thread.detached = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn wake_up_tick_saturates() {
+        assert_eq!(11, wake_up_tick(5, 5));
+        assert_eq!(u64::MAX, wake_up_tick(5, u64::MAX - 5));
+        assert_eq!(
+            u64::MAX,
+            wake_up_tick(5, time::duration_to_ticks(Duration::MAX))
+        );
+    }
+
+    #[test_case]
+    fn due_sleepers_are_woken_up() {
+        let mut table = ThreadTable::new();
+        for (index, wake_up_tick) in [(3, 10), (5, 20), (7, 15)] {
+            table.threads[index].state = ThreadState::Sleeping;
+            table.threads[index].wake_up_tick = wake_up_tick;
+        }
+
+        assert_eq!(Some(10), table.wake_sleepers(9));
+        assert_eq!(None, table.run_queue.pop());
+
+        assert_eq!(Some(20), table.wake_sleepers(15));
+        assert_eq!(Some(3), table.run_queue.pop());
+        assert_eq!(Some(7), table.run_queue.pop());
+        assert_eq!(ThreadState::Sleeping, table.threads[5].state);
+    }
+
+    #[test_case]
+    fn only_waiters_of_the_notified_queue_are_woken_up() {
+        let mut table = ThreadTable::new();
+        for (index, wait_queue) in [(2, 0x1000), (4, 0x2000), (6, 0x1000), (8, 0x1000)] {
+            table.threads[index].state = ThreadState::Blocked;
+            table.threads[index].wait_queue = wait_queue;
+        }
+
+        table.wake_waiters(0x1000, false);
+        assert_eq!(Some(2), table.run_queue.pop());
+        assert_eq!(None, table.run_queue.pop());
+
+        table.wake_waiters(0x1000, true);
+        assert_eq!(Some(6), table.run_queue.pop());
+        assert_eq!(Some(8), table.run_queue.pop());
+        assert_eq!(ThreadState::Blocked, table.threads[4].state);
+    }
+
+    #[test_case]
+    fn finished_slots_are_reused_when_detached() {
+        let mut table = ThreadTable::new();
+        for thread in table.threads.iter_mut().skip(1) {
+            thread.state = ThreadState::Finished;
+        }
+        assert_eq!(None, table.free_slot());
+
+        table.threads[2].detached = true;
+        assert_eq!(Some(2), table.free_slot());
+
+        // The running thread stack is still in use, even if it already finished
+        table.current = 2;
+        assert_eq!(None, table.free_slot());
+    }
+}
@@ -0,0 +1,104 @@
+//! Queue of the threads waiting for the CPU
+//!
+//! There is one FIFO queue per priority. The next thread to run is the oldest one of the highest
+//! priority queue that is not empty, so threads with the same priority share the CPU in round
+//! robin order.
+use super::{Priority, MAX_THREADS};
+
+/// A fixed size FIFO queue of thread slots.
+struct SlotQueue {
+    slots: [usize; MAX_THREADS],
+
+    /// Position of the oldest slot.
+    head: usize,
+
+    /// Number of slots in the queue.
+    len: usize,
+}
+
+impl SlotQueue {
+    const fn new() -> Self {
+        Self {
+            slots: [0; MAX_THREADS],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, index: usize) {
+        // A thread is queued at most once, so there is always room for it
+        debug_assert!(self.len < MAX_THREADS);
+        self.slots[(self.head + self.len) % MAX_THREADS] = index;
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let index = self.slots[self.head];
+        self.head = (self.head + 1) % MAX_THREADS;
+        self.len -= 1;
+        Some(index)
+    }
+}
+
+/// Ready threads, ordered by priority.
+pub(super) struct RunQueue {
+    queues: [SlotQueue; Priority::COUNT],
+}
+
+impl RunQueue {
+    /// Creates an empty run queue.
+    pub(super) const fn new() -> Self {
+        Self {
+            queues: [const { SlotQueue::new() }; Priority::COUNT],
+        }
+    }
+
+    /// Adds a thread at the end of the queue of its priority.
+    ///
+    /// # Arguments
+    /// * `index` - Slot of the thread.
+    /// * `priority` - Priority of the thread.
+    pub(super) fn push(&mut self, index: usize, priority: Priority) {
+        self.queues[priority as usize].push(index);
+    }
+
+    /// Removes and returns the next thread to run.
+    pub(super) fn pop(&mut self) -> Option<usize> {
+        self.queues.iter_mut().rev().find_map(SlotQueue::pop)
+    }
+
+    /// Returns the priority of the next thread to run, if there is any.
+    pub(super) fn highest_priority(&self) -> Option<Priority> {
+        Priority::ALL
+            .into_iter()
+            .rev()
+            .find(|&priority| self.queues[priority as usize].len > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn higher_priorities_run_first() {
+        let mut queue = RunQueue::new();
+        assert_eq!(None, queue.highest_priority());
+
+        queue.push(1, Priority::Low);
+        queue.push(2, Priority::High);
+        queue.push(3, Priority::Normal);
+        queue.push(4, Priority::High);
+        assert_eq!(Some(Priority::High), queue.highest_priority());
+
+        // Same priority threads keep their arrival order
+        for index in [2, 4, 3, 1] {
+            assert_eq!(Some(index), queue.pop());
+        }
+        assert_eq!(None, queue.pop());
+    }
+}
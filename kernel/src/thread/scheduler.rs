@@ -0,0 +1,270 @@
+//! Preemptive scheduler
+//!
+//! The ready threads wait in a [run queue](super::run_queue) ordered by priority. The running
+//! thread keeps the CPU until it blocks, sleeps, yields or its time slice is over and there is
+//! another ready thread with, at least, the same priority. A thread that becomes ready with a
+//! higher priority than the running one preempts it right away.
+//!
+//! The timer interrupt [accounts](tick) the elapsed ticks to the running thread, wakes up the
+//! sleeping ones and, when the running thread must be preempted, raises a flag. The switch is
+//! done by [schedule], at the end of the interrupt handlers, once the interrupt was acknowledged.
+//!
+//! A thread is never preempted while it holds a [spinlock](crate::synchronization::spinlock):
+//! a thread with a higher priority spinning on the lock would keep the holder from running
+//! forever. The switch waits for the next interrupt after the last lock is released.
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use x86_64::instructions::interrupts;
+use x86_64_custom::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+
+use super::context::{self, Context};
+use super::{stack, ThreadState, BOOT_THREAD, RUNNING_SLOT, THREADS};
+use crate::arch::x86_64 as arch;
+use crate::arch::x86_64::smp::per_cpu::{self, PerCpu};
+use crate::time::{self, Instant};
+
+/// Number of ticks a thread can run before being preempted by another one with the same
+/// priority.
+pub(super) const TIME_SLICE_TICKS: u64 = 10;
+
+/// Value of `NEXT_WAKE_UP` when there are no sleeping threads.
+const NO_WAKE_UP: u64 = u64::MAX;
+
+/// Whether the running thread must be preempted.
+static NEED_RESCHEDULE: AtomicBool = AtomicBool::new(false);
+
+/// Tick of the nearest wake up of a sleeping thread.
+static NEXT_WAKE_UP: AtomicU64 = AtomicU64::new(NO_WAKE_UP);
+
+/// Ticks elapsed without any thread running.
+static IDLE_TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Keeps the running thread from being preempted while it is alive. Taken by the spinlocks while
+/// they are locked.
+pub(crate) struct PreemptionGuard(Option<&'static PerCpu>);
+
+impl PreemptionGuard {
+    pub(crate) fn new() -> Self {
+        // Before the per-CPU data is set up the interrupts are not enabled yet
+        let cpu = per_cpu::current();
+        if let Some(cpu) = cpu {
+            cpu.disable_preemption();
+        }
+        Self(cpu)
+    }
+}
+
+impl Drop for PreemptionGuard {
+    fn drop(&mut self) {
+        if let Some(cpu) = self.0 {
+            cpu.enable_preemption();
+        }
+    }
+}
+
+/// Returns true if the running thread can be preempted, that is, it holds no spinlock.
+fn is_preemptible() -> bool {
+    per_cpu::current().is_none_or(PerCpu::is_preemptible)
+}
+
+/// Asks [schedule] to preempt the running thread.
+pub(super) fn request_reschedule() {
+    NEED_RESCHEDULE.store(true, Ordering::Relaxed);
+}
+
+/// Gives the CPU to the next thread of the run queue. The state of the current thread must be
+/// already updated: `Ready` (and queued) to keep running later, `Sleeping`, `Blocked` or
+/// `Finished` otherwise. Returns when the current thread is scheduled again.
+///
+/// If there are no ready threads, the CPU is halted until an interrupt makes one ready.
+///
+/// Must be called with the interrupts disabled.
+pub(super) fn reschedule() {
+    loop {
+        let mut threads = THREADS.lock();
+        let Some(next) = threads.run_queue.pop() else {
+            drop(threads);
+            arch::idle();
+            interrupts::disable();
+            continue;
+        };
+
+        NEED_RESCHEDULE.store(false, Ordering::Relaxed);
+        let current = threads.current;
+        let thread = &mut threads.threads[next];
+        thread.state = ThreadState::Running;
+        thread.time_slice = TIME_SLICE_TICKS;
+        if next == current {
+            return;
+        }
+
+        threads.current = next;
+        RUNNING_SLOT.store(next, Ordering::Relaxed);
+        // Only the owner finds its state in the x87, SSE and AVX registers, the rest get it on
+        // their first use of them
+        arch::fpu::set_available(threads.fpu_owner == Some(next));
+        if next != BOOT_THREAD {
+            // Interrupts from user mode must land on the kernel stack of the thread
+            unsafe { arch::set_kernel_stack(VirtualMemoryAddress::new(stack::top(next - 1))) };
+        }
+        // Threads that only run kernel code keep the current address space
+        let level_4_table = threads.threads[next].level_4_table;
+        if level_4_table != 0 {
+            unsafe { arch::switch_address_space(PhysicalMemoryAddress::new(level_4_table)) };
+        }
+        let current_context: *mut Context = &mut threads.threads[current].context;
+        let next_context: *const Context = &threads.threads[next].context;
+        drop(threads);
+
+        // The table is static, so the contexts stay valid after releasing the lock
+        unsafe { context::switch(current_context, next_context) };
+        return;
+    }
+}
+
+/// Preempts the running thread if its time slice is over or a thread with a higher priority is
+/// ready. Otherwise, or while the running thread holds a spinlock, it does nothing.
+///
+/// It is safe to call it at the exit of an interrupt handler, after acknowledging the interrupt:
+/// the interrupted thread goes back to the run queue and finishes the handler when it is
+/// scheduled again.
+pub fn schedule() {
+    // The request is kept, the next interrupt after releasing the locks does the switch
+    if !NEED_RESCHEDULE.load(Ordering::Relaxed) || !is_preemptible() {
+        return;
+    }
+
+    interrupts::without_interrupts(|| {
+        let mut threads = THREADS.lock();
+        let current = threads.current;
+        // The CPU is idle inside `reschedule`, it already picks the next thread
+        if threads.threads[current].state != ThreadState::Running {
+            return;
+        }
+
+        threads.make_ready(current);
+        drop(threads);
+
+        reschedule();
+    });
+}
+
+/// Gives the CPU to the ready threads or, if there are none, halts it until the next interrupt.
+/// This is what loops waiting for an event must call instead of halting the CPU.
+///
+/// Must be called with the interrupts disabled, so an event that arrives after checking for it
+/// still wakes up the CPU. The interrupts are enabled when it returns.
+pub fn idle() {
+    let mut threads = THREADS.lock();
+    if threads.run_queue.highest_priority().is_none() {
+        drop(threads);
+        arch::idle();
+        return;
+    }
+
+    let current = threads.current;
+    threads.make_ready(current);
+    drop(threads);
+
+    reschedule();
+    interrupts::enable();
+}
+
+/// Accounts the elapsed ticks to the running thread, wakes up the sleeping threads that are due
+/// and decides if the running thread must be preempted. Called by the timer interrupt handler,
+/// with the interrupts disabled.
+///
+/// # Arguments
+/// * `ticks` - Number of ticks elapsed.
+pub(crate) fn tick(ticks: u64) {
+    let mut threads = THREADS.lock();
+    let highest_ready = threads.run_queue.highest_priority();
+    let current = threads.current;
+    let thread = &mut threads.threads[current];
+
+    if thread.state == ThreadState::Running {
+        thread.cpu_ticks += ticks;
+        thread.time_slice = thread.time_slice.saturating_sub(ticks);
+        if thread.time_slice == 0 && highest_ready >= Some(thread.priority) {
+            request_reschedule();
+        }
+    } else {
+        IDLE_TICKS.fetch_add(ticks, Ordering::Relaxed);
+    }
+
+    let now = time::ticks();
+    if now >= NEXT_WAKE_UP.load(Ordering::Relaxed) {
+        let next_wake_up = threads.wake_sleepers(now);
+        NEXT_WAKE_UP.store(next_wake_up.unwrap_or(NO_WAKE_UP), Ordering::Relaxed);
+    }
+}
+
+/// Puts the current thread to sleep until the given tick. Must be called with the interrupts
+/// disabled.
+///
+/// # Arguments
+/// * `wake_up_tick` - Tick in which the thread must be woken up.
+pub(super) fn sleep_until(wake_up_tick: u64) {
+    let mut threads = THREADS.lock();
+    let current = threads.current;
+    let thread = &mut threads.threads[current];
+    thread.state = ThreadState::Sleeping;
+    thread.wake_up_tick = wake_up_tick;
+    NEXT_WAKE_UP.fetch_min(wake_up_tick, Ordering::Relaxed);
+    drop(threads);
+
+    reschedule();
+}
+
+/// Returns the instant when the nearest sleeping thread must be woken up, if there is any.
+pub fn next_wake_up() -> Option<Instant> {
+    match NEXT_WAKE_UP.load(Ordering::Relaxed) {
+        NO_WAKE_UP => None,
+        wake_up_tick => Some(Instant::from_ticks(wake_up_tick)),
+    }
+}
+
+/// Returns the number of ticks the CPU spent without running any thread.
+pub(super) fn idle_ticks() -> u64 {
+    IDLE_TICKS.load(Ordering::Relaxed)
+}
+
+/// Blocks the current thread on a wait queue if `condition` returns true. The condition is
+/// checked with the thread table locked, so a [wake_one] or [wake_all] on the same queue can not
+/// happen between the check and the block.
+///
+/// # Arguments
+/// * `wait_queue` - Address of the wait queue. It must not be zero.
+/// * `condition` - Whether the thread must block.
+pub(crate) fn block_on(wait_queue: usize, condition: impl FnOnce() -> bool) {
+    interrupts::without_interrupts(|| {
+        let mut threads = THREADS.lock();
+        if !condition() {
+            return;
+        }
+
+        let current = threads.current;
+        let thread = &mut threads.threads[current];
+        thread.state = ThreadState::Blocked;
+        thread.wait_queue = wait_queue;
+        drop(threads);
+
+        reschedule();
+    });
+}
+
+/// Wakes up one of the threads blocked on a wait queue, if there is any.
+///
+/// # Arguments
+/// * `wait_queue` - Address of the wait queue.
+pub(crate) fn wake_one(wait_queue: usize) {
+    THREADS.lock().wake_waiters(wait_queue, false);
+}
+
+/// Wakes up all the threads blocked on a wait queue.
+///
+/// # Arguments
+/// * `wait_queue` - Address of the wait queue.
+pub(crate) fn wake_all(wait_queue: usize) {
+    THREADS.lock().wake_waiters(wait_queue, true);
+}
@@ -0,0 +1,61 @@
+//! Kernel thread stacks
+//!
+//! The stacks are reserved statically (we do not have a heap yet). The lowest page of every stack
+//! is a guard page: it is unmapped, so a thread that overflows its stack page faults instead of
+//! silently overwriting the stack below it.
+use core::cell::UnsafeCell;
+
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::memory::paging::paging_error::PagingError;
+
+use crate::arch::x86_64::unmap_page;
+
+const PAGE_SIZE: usize = 4096;
+
+/// Number of usable pages of a stack.
+const STACK_PAGES: usize = 4;
+
+/// Size of a stack, including its guard page.
+const STACK_SIZE: usize = (STACK_PAGES + 1) * PAGE_SIZE;
+
+/// A stack, the first page is the guard page.
+#[repr(C, align(4096))]
+struct Stack([u8; STACK_SIZE]);
+
+/// Statically reserved stacks.
+struct Stacks<const COUNT: usize>(UnsafeCell<[Stack; COUNT]>);
+
+unsafe impl<const COUNT: usize> Sync for Stacks<COUNT> {}
+
+/// Stacks of the spawned threads (the boot thread uses the stack given by the bootloader).
+static STACKS: Stacks<{ super::MAX_THREADS - 1 }> = Stacks(UnsafeCell::new(
+    [const { Stack([0; STACK_SIZE]) }; super::MAX_THREADS - 1],
+));
+
+/// Returns the lowest address of the stack, where its guard page starts.
+///
+/// # Arguments
+/// * `index` - Index of the stack.
+fn bottom(index: usize) -> u64 {
+    STACKS.0.get().cast::<Stack>().wrapping_add(index) as u64
+}
+
+/// Returns the highest address of the stack (the stack grows down from there).
+///
+/// # Arguments
+/// * `index` - Index of the stack.
+pub(super) fn top(index: usize) -> u64 {
+    bottom(index) + STACK_SIZE as u64
+}
+
+/// Unmaps the guard page of the stack.
+///
+/// # Arguments
+/// * `index` - Index of the stack.
+///
+/// # Safety
+///
+/// This is unsafe because the stack must not be in use.
+pub(super) unsafe fn install_guard_page(index: usize) -> Result<(), PagingError> {
+    unmap_page(VirtualMemoryAddress::new(bottom(index)))
+}
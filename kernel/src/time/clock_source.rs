@@ -0,0 +1,300 @@
+//! Clock sources
+//!
+//! The tick counter has a resolution of one millisecond, which is not enough to profile code or
+//! to sleep for a few microseconds. A clock source is a free running hardware counter with a known
+//! frequency that can be read at any time (even with the interrupts disabled). The available ones
+//! are, from best to worst:
+//!
+//! - TSC: Read with a single instruction, but only usable if it is invariant (it runs at a
+//!   constant rate). Its frequency is not reported by all the processors, so it is calibrated
+//!   against the HPET or the PIT.
+//! - HPET: Memory mapped counter with a frequency of at least 10 MHz. Reading it is slow compared
+//!   with the TSC. Only used if its counter is 64 bits wide: a 32 bits one wraps around every few
+//!   minutes (about 5 at 14.3 MHz), and the timestamps would go backwards.
+//! - Ticks: The kernel tick counter, always available.
+//!
+//! The best available source is selected at boot.
+use core::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use core::time::Duration;
+use x86_64_custom::cpuid;
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::registers::tsc::Tsc;
+use x86_64_custom::timers::{Hpet, PIT_BASE_FREQUENCY};
+
+use super::{ticks, TICKS_PER_SECOND};
+use crate::arch::x86_64::PIT;
+
+const NANOSECONDS_PER_SECOND: u64 = 1_000_000_000;
+
+/// Duration of each calibration measure, in PIT oscillator ticks (10 ms).
+const CALIBRATION_PIT_TICKS: u16 = (PIT_BASE_FREQUENCY / 100) as u16;
+
+/// Number of calibration measures. The shortest one is used, the others were probably disturbed
+/// by something else (an SMI, the host scheduler if we are virtualized, etc).
+const CALIBRATION_ROUNDS: usize = 3;
+
+/// A free running counter with a known frequency.
+pub trait ClockSource: Sync {
+    /// Name of the clock source.
+    fn name(&self) -> &'static str;
+
+    /// Returns the current value of the counter.
+    fn counter(&self) -> u64;
+
+    /// Returns the frequency of the counter in Hz.
+    fn frequency(&self) -> u64;
+
+    /// Returns the value of the counter converted to nanoseconds.
+    fn nanoseconds(&self) -> u64 {
+        counter_to_nanoseconds(self.counter(), self.frequency())
+    }
+}
+
+/// Available clock sources, from worst to best.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum ClockSourceKind {
+    Ticks = 0,
+    Hpet = 1,
+    Tsc = 2,
+}
+
+/// The kernel tick counter.
+struct TicksClockSource;
+
+impl ClockSource for TicksClockSource {
+    fn name(&self) -> &'static str {
+        "ticks"
+    }
+
+    fn counter(&self) -> u64 {
+        ticks()
+    }
+
+    fn frequency(&self) -> u64 {
+        TICKS_PER_SECOND
+    }
+}
+
+/// The Time Stamp Counter.
+struct TscClockSource {
+    /// Calibrated frequency, zero if the TSC is not usable.
+    frequency: AtomicU64,
+}
+
+impl ClockSource for TscClockSource {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn counter(&self) -> u64 {
+        Tsc::read()
+    }
+
+    fn frequency(&self) -> u64 {
+        self.frequency.load(Ordering::Relaxed)
+    }
+}
+
+/// The HPET main counter.
+struct HpetClockSource {
+    /// Virtual address of the HPET registers, zero if there is no HPET.
+    base: AtomicU64,
+
+    /// Frequency of the main counter.
+    frequency: AtomicU64,
+}
+
+impl HpetClockSource {
+    /// Returns the HPET, if present.
+    fn hpet(&self) -> Option<Hpet> {
+        match self.base.load(Ordering::Relaxed) {
+            0 => None,
+            // Safety: the address was given by `initialize`, that requires it to be valid
+            base => Some(unsafe { Hpet::new(VirtualMemoryAddress::new(base)) }),
+        }
+    }
+}
+
+impl ClockSource for HpetClockSource {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn counter(&self) -> u64 {
+        self.hpet().map_or(0, |hpet| hpet.main_counter())
+    }
+
+    fn frequency(&self) -> u64 {
+        self.frequency.load(Ordering::Relaxed)
+    }
+}
+
+static TICKS_CLOCK_SOURCE: TicksClockSource = TicksClockSource;
+
+static TSC_CLOCK_SOURCE: TscClockSource = TscClockSource {
+    frequency: AtomicU64::new(0),
+};
+
+static HPET_CLOCK_SOURCE: HpetClockSource = HpetClockSource {
+    base: AtomicU64::new(0),
+    frequency: AtomicU64::new(0),
+};
+
+/// Selected clock source.
+static CURRENT_CLOCK_SOURCE: AtomicU8 = AtomicU8::new(ClockSourceKind::Ticks as u8);
+
+/// Detects the available clock sources and selects the best one. The TSC is calibrated here, so
+/// this should be called with the interrupts disabled to get an accurate measure.
+///
+/// # Arguments
+/// * `hpet` - The HPET, if the ACPI tables report one.
+pub fn initialize(hpet: Option<Hpet>) {
+    if let Some(mut hpet) = hpet {
+        unsafe { hpet.enable() };
+        HPET_CLOCK_SOURCE
+            .frequency
+            .store(hpet.frequency(), Ordering::Relaxed);
+        HPET_CLOCK_SOURCE
+            .base
+            .store(hpet.base().as_u64(), Ordering::Relaxed);
+    }
+
+    if cpuid::has_invariant_tsc() {
+        let frequency = calibrate(Tsc::read);
+        TSC_CLOCK_SOURCE
+            .frequency
+            .store(frequency, Ordering::Relaxed);
+    }
+
+    let best = if TSC_CLOCK_SOURCE.frequency() != 0 {
+        ClockSourceKind::Tsc
+    } else if HPET_CLOCK_SOURCE
+        .hpet()
+        .is_some_and(|hpet| hpet.is_64_bits() && hpet.frequency() != 0)
+    {
+        ClockSourceKind::Hpet
+    } else {
+        ClockSourceKind::Ticks
+    };
+    CURRENT_CLOCK_SOURCE.store(best as u8, Ordering::Relaxed);
+}
+
+/// Returns the kind of the selected clock source.
+pub fn clock_source_kind() -> ClockSourceKind {
+    match CURRENT_CLOCK_SOURCE.load(Ordering::Relaxed) {
+        2 => ClockSourceKind::Tsc,
+        1 => ClockSourceKind::Hpet,
+        _ => ClockSourceKind::Ticks,
+    }
+}
+
+/// Returns the selected clock source.
+pub fn clock_source() -> &'static dyn ClockSource {
+    match clock_source_kind() {
+        ClockSourceKind::Tsc => &TSC_CLOCK_SOURCE,
+        ClockSourceKind::Hpet => &HPET_CLOCK_SOURCE,
+        ClockSourceKind::Ticks => &TICKS_CLOCK_SOURCE,
+    }
+}
+
+/// Returns a timestamp in nanoseconds read from the selected clock source. Only useful to be
+/// compared with other timestamps, the origin depends on the clock source.
+pub fn nanoseconds() -> u64 {
+    clock_source().nanoseconds()
+}
+
+/// Busy-waits for, at least, the given duration using the selected clock source.
+///
+/// Unlike [sleep](super::sleep), the precision is the one of the clock source and, if the clock
+/// source is not the tick counter, it works with the interrupts disabled.
+///
+/// # Arguments
+/// * `duration` - Time to wait.
+pub fn precise_sleep(duration: Duration) {
+    let source = clock_source();
+    let start = source.nanoseconds();
+    let duration = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+    while source.nanoseconds().wrapping_sub(start) < duration {
+        core::hint::spin_loop();
+    }
+}
+
+/// Measures the frequency of a counter against a reference timer (the HPET if it is available,
+/// the PIT otherwise). Returns the frequency in Hz.
+///
+/// The counter must increment monotonically while it is measured.
+///
+/// # Arguments
+/// * `counter` - Function that reads the counter.
+pub fn calibrate(mut counter: impl FnMut() -> u64) -> u64 {
+    let hpet = HPET_CLOCK_SOURCE.hpet();
+
+    (0..CALIBRATION_ROUNDS)
+        .map(|_| match hpet {
+            Some(hpet) => {
+                // Wait the same time the PIT measure takes
+                let reference_ticks = hpet.frequency() * u64::from(CALIBRATION_PIT_TICKS)
+                    / u64::from(PIT_BASE_FREQUENCY);
+                // A 32 bits counter can wrap around in the middle of the measure
+                let mask = if hpet.is_64_bits() {
+                    u64::MAX
+                } else {
+                    u64::from(u32::MAX)
+                };
+                let reference_start = hpet.main_counter();
+                let start = counter();
+                while hpet.main_counter().wrapping_sub(reference_start) & mask < reference_ticks {
+                    core::hint::spin_loop();
+                }
+                counter().wrapping_sub(start)
+            }
+            None => {
+                let start = counter();
+                unsafe { PIT.lock().wait_ticks(CALIBRATION_PIT_TICKS) };
+                counter().wrapping_sub(start)
+            }
+        })
+        .min()
+        .map_or(0, |elapsed| {
+            elapsed * u64::from(PIT_BASE_FREQUENCY) / u64::from(CALIBRATION_PIT_TICKS)
+        })
+}
+
+/// Converts a counter value into nanoseconds.
+///
+/// # Arguments
+/// * `counter` - Counter value.
+/// * `frequency` - Frequency of the counter in Hz.
+pub fn counter_to_nanoseconds(counter: u64, frequency: u64) -> u64 {
+    if frequency == 0 {
+        return 0;
+    }
+
+    let nanoseconds =
+        u128::from(counter) * u128::from(NANOSECONDS_PER_SECOND) / u128::from(frequency);
+    u64::try_from(nanoseconds).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn counter_to_nanoseconds_ok() {
+        assert_eq!(0, counter_to_nanoseconds(1000, 0));
+        assert_eq!(1_000_000_000, counter_to_nanoseconds(1000, 1000));
+        assert_eq!(100, counter_to_nanoseconds(1, 10_000_000));
+        // Does not overflow with big counters
+        assert_eq!(
+            u64::MAX / 3,
+            counter_to_nanoseconds(u64::MAX, 3 * NANOSECONDS_PER_SECOND)
+        );
+    }
+
+    #[test_case]
+    fn clock_source_kinds_are_ordered_by_quality() {
+        assert!(ClockSourceKind::Ticks < ClockSourceKind::Hpet);
+        assert!(ClockSourceKind::Hpet < ClockSourceKind::Tsc);
+    }
+}
@@ -0,0 +1,207 @@
+//! Calendar date and time
+//!
+//! All the dates are in the proleptic Gregorian calendar and there is no time zone support, the
+//! RTC is assumed to be in UTC (or whatever local time the BIOS is set to, as DOS does).
+//!
+//! The conversion between days and dates is based on Howard Hinnant's algorithms:
+//! https://howardhinnant.github.io/date_algorithms.html
+use core::fmt::{Display, Formatter, Result};
+use x86_64_custom::timers::RtcTime;
+
+const SECONDS_PER_MINUTE: u64 = 60;
+const SECONDS_PER_HOUR: u64 = 60 * SECONDS_PER_MINUTE;
+const SECONDS_PER_DAY: u64 = 24 * SECONDS_PER_HOUR;
+
+/// Days between 0000-03-01 and 1970-01-01.
+const DAYS_FROM_CIVIL_EPOCH_TO_UNIX_EPOCH: i64 = 719_468;
+
+/// Days in a 400 years cycle.
+const DAYS_PER_ERA: i64 = 146_097;
+
+/// A calendar date and a time of the day, with a precision of one second.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime {
+    pub year: u16,
+    /// Month of the year (1 - 12)
+    pub month: u8,
+    /// Day of the month (1 - 31)
+    pub day: u8,
+    /// Hour of the day (0 - 23)
+    pub hour: u8,
+    /// Minute of the hour (0 - 59)
+    pub minute: u8,
+    /// Second of the minute (0 - 59)
+    pub second: u8,
+}
+
+impl DateTime {
+    /// 1970-01-01 00:00:00
+    pub const UNIX_EPOCH: DateTime = DateTime {
+        year: 1970,
+        month: 1,
+        day: 1,
+        hour: 0,
+        minute: 0,
+        second: 0,
+    };
+
+    /// Creates a date and time from the number of seconds since 1970-01-01 00:00:00.
+    ///
+    /// # Arguments
+    /// * `timestamp` - Seconds since the unix epoch.
+    pub fn from_unix_timestamp(timestamp: u64) -> Self {
+        let days = (timestamp / SECONDS_PER_DAY) as i64;
+        let seconds_of_day = timestamp % SECONDS_PER_DAY;
+
+        // Shift the epoch to 0000-03-01, so the leap day is the last day of the year
+        let days = days + DAYS_FROM_CIVIL_EPOCH_TO_UNIX_EPOCH;
+        let era = days.div_euclid(DAYS_PER_ERA);
+        let day_of_era = days.rem_euclid(DAYS_PER_ERA);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Month starting from March (0) to February (11)
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (seconds_of_day / SECONDS_PER_HOUR) as u8,
+            minute: (seconds_of_day % SECONDS_PER_HOUR / SECONDS_PER_MINUTE) as u8,
+            second: (seconds_of_day % SECONDS_PER_MINUTE) as u8,
+        }
+    }
+
+    /// Returns the number of seconds since 1970-01-01 00:00:00. Dates before the epoch return
+    /// zero.
+    pub fn unix_timestamp(&self) -> u64 {
+        let days = self.days_since_unix_epoch().max(0) as u64;
+
+        days * SECONDS_PER_DAY
+            + u64::from(self.hour) * SECONDS_PER_HOUR
+            + u64::from(self.minute) * SECONDS_PER_MINUTE
+            + u64::from(self.second)
+    }
+
+    /// Returns the day of the week, from 0 (Sunday) to 6 (Saturday), as DOS does.
+    pub fn weekday(&self) -> u8 {
+        // 1970-01-01 was a Thursday
+        (self.days_since_unix_epoch() + 4).rem_euclid(7) as u8
+    }
+
+    /// Returns the date in the DOS/FAT packed format:
+    ///
+    /// bits  15 .. 9  |  8 .. 5  |  4 .. 0
+    ///       year - 1980   month      day
+    pub fn dos_date(&self) -> u16 {
+        let year = self.year.saturating_sub(1980).min(127);
+        year << 9 | u16::from(self.month) << 5 | u16::from(self.day)
+    }
+
+    /// Returns the time in the DOS/FAT packed format (with a precision of two seconds):
+    ///
+    /// bits  15 .. 11  |  10 .. 5  |  4 .. 0
+    ///         hour        minute     second / 2
+    pub fn dos_time(&self) -> u16 {
+        u16::from(self.hour) << 11 | u16::from(self.minute) << 5 | u16::from(self.second / 2)
+    }
+
+    /// Returns the number of days between 1970-01-01 and this date (negative for earlier dates).
+    fn days_since_unix_epoch(&self) -> i64 {
+        let month = i64::from(self.month);
+        let day = i64::from(self.day);
+        // Years start in March, so January and February belong to the previous one
+        let year = i64::from(self.year) - i64::from(month <= 2);
+
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = if month > 2 { month - 3 } else { month + 9 };
+        let day_of_year = (153 * shifted_month + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+        era * DAYS_PER_ERA + day_of_era - DAYS_FROM_CIVIL_EPOCH_TO_UNIX_EPOCH
+    }
+}
+
+impl From<RtcTime> for DateTime {
+    fn from(time: RtcTime) -> Self {
+        Self {
+            year: time.year,
+            month: time.month,
+            day: time.day,
+            hour: time.hour,
+            minute: time.minute,
+            second: time.second,
+        }
+    }
+}
+
+/// Formats the date as `YYYY-MM-DD HH:MM:SS`.
+impl Display for DateTime {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAP_DAY: DateTime = DateTime {
+        year: 2024,
+        month: 2,
+        day: 29,
+        hour: 23,
+        minute: 59,
+        second: 58,
+    };
+
+    #[test_case]
+    fn unix_epoch_is_zero() {
+        assert_eq!(0, DateTime::UNIX_EPOCH.unix_timestamp());
+        assert_eq!(DateTime::UNIX_EPOCH, DateTime::from_unix_timestamp(0));
+    }
+
+    #[test_case]
+    fn unix_timestamp_round_trip() {
+        assert_eq!(1_709_251_198, LEAP_DAY.unix_timestamp());
+        assert_eq!(LEAP_DAY, DateTime::from_unix_timestamp(1_709_251_198));
+        assert_eq!(
+            DateTime {
+                year: 2024,
+                month: 3,
+                day: 1,
+                hour: 0,
+                minute: 0,
+                second: 0,
+            },
+            DateTime::from_unix_timestamp(LEAP_DAY.unix_timestamp() + 2)
+        );
+    }
+
+    #[test_case]
+    fn weekday_ok() {
+        // Thursday
+        assert_eq!(4, DateTime::UNIX_EPOCH.weekday());
+        // Thursday
+        assert_eq!(4, LEAP_DAY.weekday());
+    }
+
+    #[test_case]
+    fn dos_date_and_time_ok() {
+        assert_eq!((44 << 9) | (2 << 5) | 29, LEAP_DAY.dos_date());
+        assert_eq!((23 << 11) | (59 << 5) | 29, LEAP_DAY.dos_time());
+    }
+}
@@ -0,0 +1,184 @@
+//! Kernel time keeping
+//!
+//! The timer interrupt is programmed to fire `TICKS_PER_SECOND` times per second. Every time it
+//! fires, a global monotonic counter of ticks is incremented. All the time related functionality
+//! of the kernel (uptime, sleeps, timeouts, etc) is built on top of that counter.
+//!
+//! The counter starts when the timer interrupt is enabled and never goes backwards. The calendar
+//! time is kept by the [wall clock](wall_clock), synchronized with the RTC.
+//!
+//! For higher resolution timestamps, the [clock source](clock_source) module selects the best
+//! available hardware counter.
+pub mod clock_source;
+mod date_time;
+pub mod wall_clock;
+
+pub use clock_source::{nanoseconds, precise_sleep};
+pub use date_time::DateTime;
+
+use core::ops::{Add, Sub};
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+/// Number of timer interrupts per second.
+pub const TICKS_PER_SECOND: u64 = 1000;
+
+/// Duration of a single tick in nanoseconds.
+const NANOSECONDS_PER_TICK: u64 = 1_000_000_000 / TICKS_PER_SECOND;
+
+/// Number of ticks since the timer interrupt was enabled.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Advances the tick counter. Must only be called by the timer interrupt handler (or the idle
+/// path, after sleeping with the periodic tick stopped).
+///
+/// # Arguments
+/// * `ticks` - Number of ticks elapsed.
+pub(crate) fn advance(ticks: u64) {
+    TICKS.fetch_add(ticks, Ordering::Relaxed);
+}
+
+/// Returns the number of ticks since the timer interrupt was enabled.
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Returns the time elapsed since the timer interrupt was enabled.
+pub fn uptime() -> Duration {
+    ticks_to_duration(ticks())
+}
+
+/// Converts a number of ticks into a duration.
+///
+/// # Arguments
+/// * `ticks` - Number of ticks.
+pub fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos(ticks * NANOSECONDS_PER_TICK)
+}
+
+/// Converts a duration into a number of ticks, rounding up. This way, waiting for the returned
+/// number of ticks always waits at least `duration`.
+///
+/// # Arguments
+/// * `duration` - Duration to convert.
+pub fn duration_to_ticks(duration: Duration) -> u64 {
+    let ticks = duration
+        .as_nanos()
+        .div_ceil(u128::from(NANOSECONDS_PER_TICK));
+    u64::try_from(ticks).unwrap_or(u64::MAX)
+}
+
+/// Busy-waits for, at least, the given duration.
+///
+/// The precision is one tick. Interrupts must be enabled, otherwise the tick counter does not
+/// advance and this function never returns.
+///
+/// # Arguments
+/// * `duration` - Time to wait.
+pub fn sleep(duration: Duration) {
+    // We add one tick because we might be in the middle of the current one
+    let deadline = Instant::now() + duration + ticks_to_duration(1);
+    while Instant::now() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+/// A measurement of the monotonic tick clock.
+///
+/// It is opaque and only useful to be compared with other instants or to measure durations, as
+/// `std::time::Instant`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant(u64);
+
+impl Instant {
+    /// Returns an instant corresponding to "now".
+    pub fn now() -> Self {
+        Self(ticks())
+    }
+
+    /// Creates an instant from a number of ticks since the timer was enabled.
+    ///
+    /// # Arguments
+    /// * `ticks` - Number of ticks since the timer was enabled.
+    pub const fn from_ticks(ticks: u64) -> Self {
+        Self(ticks)
+    }
+
+    /// Returns the number of ticks since the timer was enabled at this instant.
+    pub const fn as_ticks(&self) -> u64 {
+        self.0
+    }
+
+    /// Returns the amount of time elapsed from another instant to this one, or zero duration if
+    /// that instant is later than this one.
+    ///
+    /// # Arguments
+    /// * `earlier` - Instant to compare with.
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        ticks_to_duration(self.0.saturating_sub(earlier.0))
+    }
+
+    /// Returns the amount of time elapsed since this instant was created.
+    pub fn elapsed(&self) -> Duration {
+        Instant::now().duration_since(*self)
+    }
+
+    /// Returns `Some(t)` where `t` is the time `self + duration` if `t` can be represented,
+    /// `None` otherwise.
+    ///
+    /// # Arguments
+    /// * `duration` - Duration to add.
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.0.checked_add(duration_to_ticks(duration)).map(Self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    /// # Panics
+    ///
+    /// This function may panic if the resulting point in time cannot be represented.
+    fn add(self, duration: Duration) -> Self::Output {
+        self.checked_add(duration)
+            .expect("overflow when adding duration to instant")
+    }
+}
+
+impl Sub<Instant> for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Self::Output {
+        self.duration_since(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn duration_to_ticks_rounds_up() {
+        assert_eq!(0, duration_to_ticks(Duration::ZERO));
+        assert_eq!(1, duration_to_ticks(Duration::from_nanos(1)));
+        assert_eq!(1, duration_to_ticks(ticks_to_duration(1)));
+        assert_eq!(TICKS_PER_SECOND, duration_to_ticks(Duration::from_secs(1)));
+    }
+
+    #[test_case]
+    fn instant_arithmetic() {
+        let earlier = Instant::from_ticks(10);
+        let later = earlier + ticks_to_duration(5);
+
+        assert_eq!(15, later.as_ticks());
+        assert_eq!(ticks_to_duration(5), later - earlier);
+        assert_eq!(Duration::ZERO, earlier - later);
+    }
+
+    #[test_case]
+    fn instant_add_overflow_should_fail() {
+        assert!(Instant::from_ticks(u64::MAX)
+            .checked_add(ticks_to_duration(1))
+            .is_none());
+    }
+}
@@ -0,0 +1,49 @@
+//! Wall clock
+//!
+//! Reading the RTC is slow, so it is only read at boot (and when the RTC update interrupt is
+//! enabled, once per second to correct the drift). The wall clock time is computed as the unix
+//! timestamp of the boot plus the uptime given by the tick counter.
+//!
+//! The boot timestamp is kept in nanoseconds, so the wall clock advances with the tick counter
+//! instead of in whole seconds, and a synchronization does not shift it by the fraction of second
+//! elapsed since the last one. The RTC update interrupt fires right when a new second starts, so
+//! the synchronizations done by it are exact.
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::time::Duration;
+
+use super::{uptime, DateTime};
+
+/// Unix timestamp of the moment the tick counter started, in nanoseconds.
+static BOOT_TIMESTAMP_NANOSECONDS: AtomicU64 = AtomicU64::new(0);
+
+/// Synchronizes the wall clock with the given date and time, usually read from the RTC.
+///
+/// # Arguments
+/// * `now` - Current date and time.
+pub fn synchronize(now: DateTime) {
+    let boot_timestamp = Duration::from_secs(now.unix_timestamp()).saturating_sub(uptime());
+    BOOT_TIMESTAMP_NANOSECONDS.store(
+        u64::try_from(boot_timestamp.as_nanos()).unwrap_or(u64::MAX),
+        Ordering::Relaxed,
+    );
+}
+
+/// Returns the unix timestamp of the moment the tick counter started.
+fn boot_timestamp() -> Duration {
+    Duration::from_nanos(BOOT_TIMESTAMP_NANOSECONDS.load(Ordering::Relaxed))
+}
+
+/// Returns the number of seconds since 1970-01-01 00:00:00.
+pub fn unix_timestamp() -> u64 {
+    (boot_timestamp() + uptime()).as_secs()
+}
+
+/// Returns the current date and time.
+pub fn now() -> DateTime {
+    DateTime::from_unix_timestamp(unix_timestamp())
+}
+
+/// Returns the date and time when the tick counter started.
+pub fn boot_time() -> DateTime {
+    DateTime::from_unix_timestamp(boot_timestamp().as_secs())
+}
@@ -0,0 +1,33 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::empty_loop)]
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use lil_os::arch::x86_64::{acpi, initialize_x86_64_arch};
+use lil_os::tests::{exit_qemu, test_panic_handler, QemuExitCode};
+use lil_os::{serial_print, serial_println};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+
+#[no_mangle]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    serial_print!("acpi::tables...\t");
+    initialize_x86_64_arch(VirtualMemoryAddress::new(boot_info.physical_memory_offset));
+
+    let acpi = acpi::get().expect("the ACPI tables were not found");
+    assert!(acpi.madt().is_some());
+    assert!(acpi.fadt().is_some());
+    assert!(acpi.hpet().is_some());
+
+    // The power management needs the soft off state to turn the machine off
+    let dsdt = acpi.tables().dsdt().expect("the DSDT was not found");
+    assert!(dsdt.sleep_types(5).is_some());
+
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
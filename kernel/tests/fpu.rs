@@ -0,0 +1,197 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::empty_loop)]
+use bootloader::BootInfo;
+use core::arch::asm;
+use core::panic::PanicInfo;
+use lil_os::arch::x86_64::initialize_x86_64_arch;
+use lil_os::loader::{self, elf};
+use lil_os::tests::{exit_qemu, test_panic_handler, QemuExitCode};
+use lil_os::thread;
+use lil_os::{serial_print, serial_println};
+use x86_64_custom::cpuid::{self, Feature};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::registers::control::Xcr0;
+
+/// Exit code of the threads finished by a floating point exception.
+const FLOATING_POINT_EXCEPTION_EXIT_CODE: usize = usize::MAX;
+
+/// Address where the test programs are loaded.
+const PROGRAM_ADDRESS: u64 = 0x40_0000;
+
+/// Size of the ELF file header.
+const HEADER_SIZE: usize = 64;
+
+/// Room for the code of the test programs.
+const CODE_SIZE: usize = 64;
+
+/// Size of the test programs.
+const PROGRAM_SIZE: usize = HEADER_SIZE + elf::PROGRAM_HEADER_SIZE + CODE_SIZE;
+
+/// Exits with code 0 (`xor edi, edi; xor eax, eax; syscall`), if the exception did not finish
+/// the program before.
+const EXIT: [u8; 6] = [0x31, 0xff, 0x31, 0xc0, 0x0f, 0x05];
+
+/// Unmasks the SSE divide by zero exception and divides 1 by 0.
+const SIMD_DIVIDE_BY_ZERO: [u8; 25] = [
+    0x68, 0x80, 0x1d, 0x00, 0x00, // push 0x1d80 (default MXCSR without ZM)
+    0x0f, 0xae, 0x14, 0x24, // ldmxcsr [rsp]
+    0x0f, 0x57, 0xc0, // xorps xmm0, xmm0
+    0xb8, 0x01, 0x00, 0x00, 0x00, // mov eax, 1
+    0xf3, 0x0f, 0x2a, 0xc8, // cvtsi2ss xmm1, eax
+    0xf3, 0x0f, 0x5e, 0xc8, // divss xmm1, xmm0
+];
+
+/// Unmasks the x87 divide by zero exception and divides 1 by 0. The exception is reported by the
+/// next waiting instruction.
+const X87_DIVIDE_BY_ZERO: [u8; 15] = [
+    0x68, 0x7b, 0x03, 0x00, 0x00, // push 0x37b (default control word without ZM)
+    0xd9, 0x2c, 0x24, // fldcw [rsp]
+    0xd9, 0xe8, // fld1
+    0xd9, 0xee, // fldz
+    0xde, 0xf9, // fdivp st(1), st
+    0x9b, // fwait
+];
+
+/// Loads a value in XMM0. The kernel is built without SSE, so nothing else uses the register.
+fn write_xmm0(value: u64) {
+    unsafe { asm!("movq xmm0, {}", in(reg) value) };
+}
+
+/// Reads the low half of XMM0.
+fn read_xmm0() -> u64 {
+    let value: u64;
+    unsafe { asm!("movq {}, xmm0", out(reg) value) };
+    value
+}
+
+/// Loads a value in the whole YMM0 register. AVX must be enabled.
+fn write_ymm0(value: &[u64; 4]) {
+    unsafe { asm!("vmovdqu ymm0, [{}]", in(reg) value.as_ptr()) };
+}
+
+/// Reads the whole YMM0 register. AVX must be enabled.
+fn read_ymm0() -> [u64; 4] {
+    let mut value = [0; 4];
+    unsafe { asm!("vmovdqu [{}], ymm0", in(reg) value.as_mut_ptr()) };
+    value
+}
+
+/// Returns true if the kernel enabled AVX, so its state is switched too.
+fn avx_enabled() -> bool {
+    cpuid::has(Feature::OsXsave) && Xcr0::read() & Xcr0::AVX != 0
+}
+
+/// Uses XMM0 while the boot thread keeps its own value in it.
+fn other_thread(value: usize) -> usize {
+    write_xmm0(value as u64);
+    thread::yield_now();
+    read_xmm0() as usize
+}
+
+/// Uses YMM0 while the boot thread keeps its own value in it. Returns 1 if the value survived.
+fn other_avx_thread(value: usize) -> usize {
+    let value = [value as u64; 4];
+    write_ymm0(&value);
+    thread::yield_now();
+    usize::from(read_ymm0() == value)
+}
+
+/// Builds an executable with a single segment holding the whole file. The entry point is the
+/// given code, followed by [EXIT].
+///
+/// # Arguments
+/// * `code` - Code of the program.
+fn program(code: &[u8]) -> [u8; PROGRAM_SIZE] {
+    let mut data = [0u8; PROGRAM_SIZE];
+    let mut write =
+        |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+    // File header: 64 bit, little endian, executable for x86_64
+    write(0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    write(16, &2u16.to_le_bytes());
+    write(18, &0x3eu16.to_le_bytes());
+    write(20, &1u32.to_le_bytes());
+    let code_offset = HEADER_SIZE + elf::PROGRAM_HEADER_SIZE;
+    write(24, &(PROGRAM_ADDRESS + code_offset as u64).to_le_bytes());
+    write(32, &(HEADER_SIZE as u64).to_le_bytes());
+    write(52, &(HEADER_SIZE as u16).to_le_bytes());
+    write(54, &(elf::PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    write(56, &1u16.to_le_bytes());
+
+    // Readable and executable segment
+    write(HEADER_SIZE, &elf::PT_LOAD.to_le_bytes());
+    write(HEADER_SIZE + 4, &(elf::PF_X | 4).to_le_bytes());
+    write(HEADER_SIZE + 16, &PROGRAM_ADDRESS.to_le_bytes());
+    write(HEADER_SIZE + 32, &(PROGRAM_SIZE as u64).to_le_bytes());
+    write(HEADER_SIZE + 40, &(PROGRAM_SIZE as u64).to_le_bytes());
+    write(HEADER_SIZE + 48, &0x1000u64.to_le_bytes());
+
+    write(code_offset, code);
+    write(code_offset + code.len(), &EXIT);
+    data
+}
+
+/// Runs a program in user mode and returns its exit code.
+///
+/// # Arguments
+/// * `code` - Code of the program.
+fn run_program(code: &[u8]) -> usize {
+    loader::spawn(&program(code), &["fpu"], &[])
+        .expect("failed to spawn the program")
+        .join()
+}
+
+#[no_mangle]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    initialize_x86_64_arch(VirtualMemoryAddress::new(boot_info.physical_memory_offset));
+    lil_os::memory::initialize(&boot_info.memory_map);
+
+    serial_print!("fpu::lazy_state_switch...\t");
+    write_xmm0(1);
+    let handle = thread::spawn(other_thread, 2).expect("failed to spawn the thread");
+    // The other thread saw its own value, even if it yielded in the middle
+    assert_eq!(2, handle.join());
+    // And the boot thread gets its value back
+    assert_eq!(1, read_xmm0());
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("fpu::avx_state_switch...\t");
+    if avx_enabled() {
+        let value = [1, 2, 3, 4];
+        write_ymm0(&value);
+        let handle = thread::spawn(other_avx_thread, 5).expect("failed to spawn the thread");
+        assert_eq!(1, handle.join());
+        // The upper half is saved and restored too, not only XMM0
+        assert_eq!(value, read_ymm0());
+        serial_println!("[\x1b[1;32mOK\x1b[0m]");
+    } else {
+        serial_println!("[\x1b[1;33mSKIPPED\x1b[0m] (AVX is not enabled)");
+    }
+
+    serial_print!("fpu::simd_exception_finishes_user_thread...\t");
+    assert_eq!(
+        FLOATING_POINT_EXCEPTION_EXIT_CODE,
+        run_program(&SIMD_DIVIDE_BY_ZERO)
+    );
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("fpu::x87_exception_finishes_user_thread...\t");
+    assert_eq!(
+        FLOATING_POINT_EXCEPTION_EXIT_CODE,
+        run_program(&X87_DIVIDE_BY_ZERO)
+    );
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    // The exceptions of the programs did not leak to the boot thread state
+    write_xmm0(3);
+    assert_eq!(3, read_xmm0());
+
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
@@ -3,21 +3,19 @@
 #![feature(abi_x86_interrupt)]
 #![allow(clippy::empty_loop)]
 use core::panic::PanicInfo;
-use lazy_static::lazy_static;
 use lil_os::tests::{
     idt::{test_handler, test_handler_body},
     test_panic_handler,
 };
 
+use lil_os::synchronization::lazy::Lazy;
 use x86_64_custom::idt::InterruptDescriptorTable;
 
-lazy_static! {
-    static ref IDT: InterruptDescriptorTable = {
-        let mut idt = InterruptDescriptorTable::new();
-        idt.breakpoint.set_handler_function(test_handler);
-        idt
-    };
-}
+static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
+    let mut idt = InterruptDescriptorTable::new();
+    idt.breakpoint.set_handler_function(test_handler);
+    idt
+});
 
 #[no_mangle]
 pub extern "C" fn _start() -> ! {
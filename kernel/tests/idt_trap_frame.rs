@@ -0,0 +1,52 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::empty_loop)]
+use core::panic::PanicInfo;
+use lil_os::arch::x86_64::initialize_x86_64_arch;
+use lil_os::synchronization::lazy::Lazy;
+use lil_os::tests::{exit_qemu, test_panic_handler, QemuExitCode};
+use lil_os::{serial_print, serial_println};
+use x86_64_custom::create_trap_handler;
+use x86_64_custom::idt::{ExceptionVector, InterruptDescriptorTable, TrapFrame};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+
+/// Length of the `ud2` instruction.
+const UD2_LENGTH: u64 = 2;
+
+create_trap_handler!(
+    INVALID_OPCODE_HANDLER,
+    ExceptionVector::InvalidOpcode as u8,
+    skip_invalid_opcode
+);
+
+/// Emulates the invalid instruction: it "returns" 42 in RAX and execution continues after it.
+extern "C" fn skip_invalid_opcode(frame: &mut TrapFrame) {
+    frame.instruction_pointer += UD2_LENGTH;
+    frame.rax = 42;
+}
+
+static IDT: Lazy<InterruptDescriptorTable> = Lazy::new(|| {
+    let mut idt = InterruptDescriptorTable::new();
+    idt.invalid_opcode.set_trap_handler(INVALID_OPCODE_HANDLER);
+    idt
+});
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial_print!("idt::trap_frame...\t");
+    initialize_x86_64_arch(VirtualMemoryAddress::zero());
+    IDT.load();
+
+    let value: u64;
+    unsafe { core::arch::asm!("mov rax, 0", "ud2", out("rax") value) };
+    assert_eq!(42, value);
+
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
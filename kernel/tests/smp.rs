@@ -0,0 +1,62 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::empty_loop)]
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use lil_os::arch::x86_64::{initialize_x86_64_arch, smp};
+use lil_os::synchronization::spinlock::Mutex;
+use lil_os::tests::{exit_qemu, test_panic_handler, QemuExitCode};
+use lil_os::{serial_print, serial_println};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+use x86_64_custom::memory::tlb;
+
+/// Processors QEMU is started with (see `test-args` in Cargo.toml).
+const QEMU_CPUS: usize = 4;
+
+#[no_mangle]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    serial_print!("smp::application_processors...\t");
+    initialize_x86_64_arch(VirtualMemoryAddress::new(boot_info.physical_memory_offset));
+    lil_os::memory::initialize(&boot_info.memory_map);
+
+    assert_eq!(Ok(()), smp::initialize());
+    assert_eq!(QEMU_CPUS, smp::per_cpu::online_count());
+    for (index, cpu) in smp::per_cpu::online_cpus().enumerate() {
+        assert_eq!(index, cpu.index());
+    }
+    assert_eq!(
+        Some(0),
+        smp::per_cpu::current().map(|cpu| cpu.index()),
+        "the test must run on the bootstrap processor"
+    );
+
+    // Returns only when every application processor invalidated the page
+    tlb::flush(VirtualMemoryAddress::new(0x1000));
+
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("smp::spinlock_disables_preemption...\t");
+    let cpu = smp::per_cpu::current().expect("no per-CPU data");
+    let (first, second) = (Mutex::new(1), Mutex::new(2));
+    assert!(cpu.is_preemptible());
+    {
+        let _first = first.lock();
+        assert!(!cpu.is_preemptible());
+        {
+            let _second = second.lock();
+            // A failed attempt does not leave it disabled
+            assert!(second.try_lock().is_err());
+        }
+        assert!(!cpu.is_preemptible());
+    }
+    assert!(cpu.is_preemptible());
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
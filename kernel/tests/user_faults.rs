@@ -0,0 +1,117 @@
+#![no_std]
+#![no_main]
+#![allow(clippy::empty_loop)]
+use bootloader::BootInfo;
+use core::panic::PanicInfo;
+use lil_os::arch::x86_64::initialize_x86_64_arch;
+use lil_os::loader::{self, elf};
+use lil_os::tests::{exit_qemu, test_panic_handler, QemuExitCode};
+use lil_os::{serial_print, serial_println};
+use x86_64_custom::memory::address::VirtualMemoryAddress;
+
+/// Exit code of the threads finished by a fault.
+const FAULT_EXIT_CODE: usize = usize::MAX;
+
+/// Address where the test programs are loaded.
+const PROGRAM_ADDRESS: u64 = 0x40_0000;
+
+/// Size of the ELF file header.
+const HEADER_SIZE: usize = 64;
+
+/// Room for the code of the test programs.
+const CODE_SIZE: usize = 64;
+
+/// Size of the test programs.
+const PROGRAM_SIZE: usize = HEADER_SIZE + elf::PROGRAM_HEADER_SIZE + CODE_SIZE;
+
+/// Exits with code 0 (`xor edi, edi; xor eax, eax; syscall`), if the exception did not finish
+/// the program before.
+const EXIT: [u8; 6] = [0x31, 0xff, 0x31, 0xc0, 0x0f, 0x05];
+
+/// Raises an invalid opcode exception.
+const UD2: [u8; 2] = [0x0f, 0x0b];
+
+/// Halts the CPU, a privileged instruction that raises a general protection fault in user mode.
+const HLT: [u8; 1] = [0xf4];
+
+/// Disables interrupts, which user mode is not allowed to do either (IOPL is 0).
+const CLI: [u8; 1] = [0xfa];
+
+/// Reads the unmapped first page (`mov rax, [0]`).
+const NULL_READ: [u8; 8] = [0x48, 0x8b, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00];
+
+/// Builds an executable with a single segment holding the whole file. The entry point is the
+/// given code, followed by [EXIT].
+///
+/// # Arguments
+/// * `code` - Code of the program.
+fn program(code: &[u8]) -> [u8; PROGRAM_SIZE] {
+    let mut data = [0u8; PROGRAM_SIZE];
+    let mut write =
+        |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+    // File header: 64 bit, little endian, executable for x86_64
+    write(0, &[0x7f, b'E', b'L', b'F', 2, 1, 1]);
+    write(16, &2u16.to_le_bytes());
+    write(18, &0x3eu16.to_le_bytes());
+    write(20, &1u32.to_le_bytes());
+    let code_offset = HEADER_SIZE + elf::PROGRAM_HEADER_SIZE;
+    write(24, &(PROGRAM_ADDRESS + code_offset as u64).to_le_bytes());
+    write(32, &(HEADER_SIZE as u64).to_le_bytes());
+    write(52, &(HEADER_SIZE as u16).to_le_bytes());
+    write(54, &(elf::PROGRAM_HEADER_SIZE as u16).to_le_bytes());
+    write(56, &1u16.to_le_bytes());
+
+    // Readable and executable segment
+    write(HEADER_SIZE, &elf::PT_LOAD.to_le_bytes());
+    write(HEADER_SIZE + 4, &(elf::PF_X | 4).to_le_bytes());
+    write(HEADER_SIZE + 16, &PROGRAM_ADDRESS.to_le_bytes());
+    write(HEADER_SIZE + 32, &(PROGRAM_SIZE as u64).to_le_bytes());
+    write(HEADER_SIZE + 40, &(PROGRAM_SIZE as u64).to_le_bytes());
+    write(HEADER_SIZE + 48, &0x1000u64.to_le_bytes());
+
+    write(code_offset, code);
+    write(code_offset + code.len(), &EXIT);
+    data
+}
+
+/// Runs a program in user mode and returns its exit code.
+///
+/// # Arguments
+/// * `code` - Code of the program.
+fn run_program(code: &[u8]) -> usize {
+    loader::spawn(&program(code), &["user_faults"], &[])
+        .expect("failed to spawn the program")
+        .join()
+}
+
+#[no_mangle]
+pub extern "C" fn _start(boot_info: &'static BootInfo) -> ! {
+    initialize_x86_64_arch(VirtualMemoryAddress::new(boot_info.physical_memory_offset));
+    lil_os::memory::initialize(&boot_info.memory_map);
+
+    serial_print!("user_faults::exit_code...\t");
+    assert_eq!(0, run_program(&[]));
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("user_faults::invalid_opcode...\t");
+    assert_eq!(FAULT_EXIT_CODE, run_program(&UD2));
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("user_faults::general_protection_fault...\t");
+    assert_eq!(FAULT_EXIT_CODE, run_program(&HLT));
+    assert_eq!(FAULT_EXIT_CODE, run_program(&CLI));
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    serial_print!("user_faults::page_fault...\t");
+    assert_eq!(FAULT_EXIT_CODE, run_program(&NULL_READ));
+    serial_println!("[\x1b[1;32mOK\x1b[0m]");
+
+    exit_qemu(QemuExitCode::Success);
+    loop {}
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    test_panic_handler(info)
+}
@@ -0,0 +1,111 @@
+use core::mem::size_of;
+
+use super::{AcpiTable, SdtHeader};
+
+/// AML opcode that defines a named object.
+const NAME_OP: u8 = 0x08;
+
+/// AML opcode of a package (a list of objects).
+const PACKAGE_OP: u8 = 0x12;
+
+/// AML prefix of the root namespace (`\`).
+const ROOT_PREFIX: u8 = b'\\';
+
+// AML encodings of integer constants
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const BYTE_PREFIX: u8 = 0x0a;
+const WORD_PREFIX: u8 = 0x0b;
+const DWORD_PREFIX: u8 = 0x0c;
+const ONES_OP: u8 = 0xff;
+
+/// Differentiated System Description Table. Its body is AML (ACPI Machine Language) bytecode that
+/// describes the devices of the machine. It is not listed in the RSDT/XSDT, the FADT points to it.
+///
+/// We do not have an AML interpreter, the only objects read are the sleep states packages
+/// (`\_S0` to `\_S5`) when they are defined with constant values, which is what every firmware we
+/// know of does.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Dsdt {
+    pub header: SdtHeader,
+}
+
+impl Dsdt {
+    /// Returns the AML bytecode of the table.
+    pub fn aml(&self) -> &[u8] {
+        let length = (self.header.length as usize).saturating_sub(size_of::<Self>());
+        // Safety: the table is `header.length` bytes long
+        unsafe {
+            core::slice::from_raw_parts(
+                (self as *const Self as *const u8).add(size_of::<Self>()),
+                length,
+            )
+        }
+    }
+
+    /// Returns the values that are written to the SLP_TYP field of the PM1a and PM1b control
+    /// registers to enter a sleep state, or `None` if the state is not defined (or not with
+    /// constants).
+    ///
+    /// The `\_Sx` object is a package whose first two elements are SLP_TYPa and SLP_TYPb:
+    ///
+    ///  NameOp [\] _Sx_ PackageOp PkgLength NumElements SLP_TYPa SLP_TYPb ...
+    ///
+    /// # Arguments
+    /// * `state` - Sleep state, from 0 (working) to 5 (soft off).
+    pub fn sleep_types(&self, state: u8) -> Option<(u8, u8)> {
+        if state > 5 {
+            return None;
+        }
+
+        let aml = self.aml();
+        let name = [b'_', b'S', b'0' + state, b'_'];
+        let position = (0..aml.len()).find(|&index| {
+            let prefix = &aml[..index];
+            aml[index..].starts_with(&name)
+                && (prefix.ends_with(&[NAME_OP]) || prefix.ends_with(&[NAME_OP, ROOT_PREFIX]))
+        })?;
+
+        let mut offset = position + name.len();
+        if *aml.get(offset)? != PACKAGE_OP {
+            return None;
+        }
+        // The two high bits of the first byte of PkgLength tell how many bytes follow it
+        let package_length_bytes = usize::from(*aml.get(offset + 1)? >> 6) + 1;
+        offset += 1 + package_length_bytes + 1;
+
+        let slp_typ_a = integer(aml, &mut offset)?;
+        let slp_typ_b = integer(aml, &mut offset)?;
+        Some((slp_typ_a, slp_typ_b))
+    }
+}
+
+/// Reads an AML integer constant and advances the offset past it. Only the low byte is returned,
+/// the SLP_TYP fields are 3 bits wide.
+///
+/// # Arguments
+/// * `aml` - AML bytecode.
+/// * `offset` - Offset of the integer.
+fn integer(aml: &[u8], offset: &mut usize) -> Option<u8> {
+    let (value, length) = match *aml.get(*offset)? {
+        ZERO_OP => (0, 1),
+        ONE_OP => (1, 1),
+        ONES_OP => (0xff, 1),
+        BYTE_PREFIX => (*aml.get(*offset + 1)?, 2),
+        WORD_PREFIX => (*aml.get(*offset + 1)?, 3),
+        DWORD_PREFIX => (*aml.get(*offset + 1)?, 5),
+        _ => return None,
+    };
+
+    *offset += length;
+    Some(value)
+}
+
+unsafe impl AcpiTable for Dsdt {
+    const SIGNATURE: &'static [u8; 4] = b"DSDT";
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
@@ -0,0 +1,162 @@
+use core::mem::{offset_of, size_of};
+
+use super::{AcpiTable, GenericAddress, SdtHeader};
+use crate::memory::address::PhysicalMemoryAddress;
+
+/// Fixed ACPI Description Table. Describes the fixed hardware of the power management model
+/// (sleep and reset registers, the ACPI timer, ...) and points to the DSDT.
+///
+/// The table grew with every ACPI revision, the older ones are shorter: the fields beyond
+/// `header.length` are not part of the table and must not be used. The methods check it, so they
+/// should be preferred over the fields.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Fadt {
+    pub header: SdtHeader,
+    pub firmware_control: u32,
+
+    /// 32 bit physical address of the DSDT.
+    pub dsdt: u32,
+    pub reserved_1: u8,
+    pub preferred_power_management_profile: u8,
+
+    /// Legacy IRQ of the System Control Interrupt.
+    pub sci_interrupt: u16,
+
+    /// I/O port where `acpi_enable` and `acpi_disable` are written. Zero if the machine is always
+    /// in ACPI mode.
+    pub smi_command_port: u32,
+    pub acpi_enable: u8,
+    pub acpi_disable: u8,
+    pub s4_bios_request: u8,
+    pub pstate_control: u8,
+
+    // I/O ports of the power management register blocks
+    pub pm1a_event_block: u32,
+    pub pm1b_event_block: u32,
+    pub pm1a_control_block: u32,
+    pub pm1b_control_block: u32,
+    pub pm2_control_block: u32,
+    pub pm_timer_block: u32,
+    pub gpe0_block: u32,
+    pub gpe1_block: u32,
+    pub pm1_event_length: u8,
+    pub pm1_control_length: u8,
+    pub pm2_control_length: u8,
+    pub pm_timer_length: u8,
+    pub gpe0_length: u8,
+    pub gpe1_length: u8,
+    pub gpe1_base: u8,
+    pub c_state_control: u8,
+    pub worst_c2_latency: u16,
+    pub worst_c3_latency: u16,
+    pub flush_size: u16,
+    pub flush_stride: u16,
+    pub duty_offset: u8,
+    pub duty_width: u8,
+    pub day_alarm: u8,
+    pub month_alarm: u8,
+
+    /// Index of the century register in the RTC CMOS, zero if there is none.
+    pub century: u8,
+
+    /// IA-PC boot architecture flags (ACPI 2.0 onwards).
+    pub boot_architecture_flags: u16,
+    pub reserved_2: u8,
+    pub flags: u32,
+
+    // ACPI 2.0 fields
+    /// Register written with `reset_value` to reset the machine.
+    pub reset_register: GenericAddress,
+    pub reset_value: u8,
+    pub arm_boot_architecture_flags: u16,
+    pub minor_version: u8,
+    pub x_firmware_control: u64,
+
+    /// 64 bit physical address of the DSDT, it replaces `dsdt` if it is not zero.
+    pub x_dsdt: u64,
+    pub x_pm1a_event_block: GenericAddress,
+    pub x_pm1b_event_block: GenericAddress,
+    pub x_pm1a_control_block: GenericAddress,
+    pub x_pm1b_control_block: GenericAddress,
+    pub x_pm2_control_block: GenericAddress,
+    pub x_pm_timer_block: GenericAddress,
+    pub x_gpe0_block: GenericAddress,
+    pub x_gpe1_block: GenericAddress,
+
+    // ACPI 5.0 fields
+    pub sleep_control_register: GenericAddress,
+    pub sleep_status_register: GenericAddress,
+
+    // ACPI 6.0 fields
+    pub hypervisor_vendor_identity: u64,
+}
+
+impl Fadt {
+    /// Flag set if the reset register is supported.
+    pub const RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+
+    /// Flag set if the PM timer is 32 bits wide (24 bits otherwise).
+    pub const TIMER_VALUE_EXTENDED: u32 = 1 << 8;
+
+    /// Boot architecture flag set if the machine has an 8042 keyboard controller.
+    pub const BOOT_ARCHITECTURE_8042: u16 = 1 << 1;
+
+    /// Returns true if the field at `offset`, of `size` bytes, is part of the table.
+    fn has_field(&self, offset: usize, size: usize) -> bool {
+        offset + size <= self.header.length as usize
+    }
+
+    /// Returns the physical address of the DSDT.
+    pub fn dsdt_address(&self) -> PhysicalMemoryAddress {
+        let x_dsdt = self.x_dsdt;
+        if self.has_field(offset_of!(Self, x_dsdt), size_of::<u64>()) && x_dsdt != 0 {
+            PhysicalMemoryAddress::new(x_dsdt)
+        } else {
+            PhysicalMemoryAddress::new(u64::from(self.dsdt))
+        }
+    }
+
+    /// Returns the I/O ports of the PM1a and PM1b control registers. PM1b is optional, it is
+    /// `None` if the machine does not have it.
+    pub fn pm1_control_ports(&self) -> (u16, Option<u16>) {
+        let pm1b = u16::try_from(self.pm1b_control_block)
+            .ok()
+            .filter(|&port| port != 0);
+        (self.pm1a_control_block as u16, pm1b)
+    }
+
+    /// Returns the index of the century register in the RTC CMOS, if there is one.
+    pub fn century_register(&self) -> Option<u8> {
+        Some(self.century).filter(|&century| century != 0)
+    }
+
+    /// Returns the boot architecture flags. They are zero before ACPI 2.0.
+    pub fn boot_architecture_flags(&self) -> u16 {
+        if self.has_field(offset_of!(Self, boot_architecture_flags), size_of::<u16>()) {
+            self.boot_architecture_flags
+        } else {
+            0
+        }
+    }
+
+    /// Returns the reset register and the value that resets the machine when written to it, if
+    /// the reset register is supported.
+    pub fn reset(&self) -> Option<(GenericAddress, u8)> {
+        if self.has_field(offset_of!(Self, reset_value), size_of::<u8>())
+            && self.flags & Self::RESET_REGISTER_SUPPORTED != 0
+        {
+            Some((self.reset_register, self.reset_value))
+        } else {
+            None
+        }
+    }
+}
+
+unsafe impl AcpiTable for Fadt {
+    const SIGNATURE: &'static [u8; 4] = b"FACP";
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
@@ -0,0 +1,27 @@
+use crate::memory::address::PhysicalMemoryAddress;
+
+/// Generic Address Structure (GAS). Describes the location of a register, that can be in the
+/// memory or the I/O address spaces, among others.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct GenericAddress {
+    /// 0 - System Memory, 1 - System I/O, 2 - PCI Configuration Space, ...
+    pub address_space: u8,
+    pub register_bit_width: u8,
+    pub register_bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+impl GenericAddress {
+    /// System Memory address space.
+    pub const SYSTEM_MEMORY: u8 = 0;
+
+    /// System I/O address space.
+    pub const SYSTEM_IO: u8 = 1;
+
+    /// Returns the address as a physical memory address.
+    pub fn physical_address(&self) -> PhysicalMemoryAddress {
+        PhysicalMemoryAddress::new(self.address)
+    }
+}
@@ -0,0 +1,28 @@
+use super::{AcpiTable, GenericAddress, SdtHeader};
+
+/// HPET description table. Tells where the HPET registers are located.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct HpetTable {
+    pub header: SdtHeader,
+
+    /// Hardware ID of the event timer block (a copy of the first 32 bits of the HPET capabilities
+    /// register).
+    pub event_timer_block_id: u32,
+
+    /// Address of the HPET registers (always in system memory).
+    pub base_address: GenericAddress,
+    pub hpet_number: u8,
+
+    /// Minimum periodic tick (in main counter ticks) that does not lose interrupts.
+    pub minimum_tick: u16,
+    pub page_protection: u8,
+}
+
+unsafe impl AcpiTable for HpetTable {
+    const SIGNATURE: &'static [u8; 4] = b"HPET";
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
@@ -0,0 +1,201 @@
+use core::mem::size_of;
+
+use super::{AcpiTable, SdtHeader};
+use crate::memory::address::PhysicalMemoryAddress;
+
+/// Multiple APIC Description Table. Lists the interrupt controllers of the machine: the local
+/// APIC of every processor, the I/O APICs and how the legacy IRQs are routed to them.
+///
+/// The fixed part is followed by a list of variable length entries, read with
+/// [entries](Madt::entries).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Madt {
+    pub header: SdtHeader,
+
+    /// Physical address of the local APIC registers, unless there is an address override entry.
+    pub local_apic_address: u32,
+
+    /// Bit 0 (`PCAT_COMPAT`) is set if the machine also has the legacy 8259 PICs.
+    pub flags: u32,
+}
+
+/// An entry of the MADT.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MadtEntry {
+    /// A processor and its local APIC.
+    LocalApic {
+        processor_id: u8,
+        apic_id: u8,
+        flags: u32,
+    },
+
+    /// An I/O APIC and the first global system interrupt it handles.
+    IoApic {
+        id: u8,
+        address: u32,
+        global_system_interrupt_base: u32,
+    },
+
+    /// A legacy IRQ that is not identity mapped to a global system interrupt.
+    InterruptSourceOverride {
+        bus: u8,
+        source: u8,
+        global_system_interrupt: u32,
+        flags: u16,
+    },
+
+    /// Local APIC pin connected to the NMI. A processor ID of 0xff means all the processors.
+    LocalApicNmi {
+        processor_id: u8,
+        flags: u16,
+        lint: u8,
+    },
+
+    /// 64 bit physical address of the local APIC registers, it replaces the MADT one.
+    LocalApicAddressOverride { address: u64 },
+
+    /// A processor whose local APIC ID does not fit in 8 bits.
+    LocalX2Apic {
+        x2apic_id: u32,
+        flags: u32,
+        processor_uid: u32,
+    },
+
+    /// An entry type we do not parse.
+    Unknown { kind: u8, length: u8 },
+}
+
+impl MadtEntry {
+    /// Processor flag: the processor is ready to be used.
+    pub const PROCESSOR_ENABLED: u32 = 1 << 0;
+
+    /// Processor flag: the processor is disabled but can be enabled by the OS.
+    pub const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+
+    /// Parses an entry. Returns `None` if the entry is truncated.
+    ///
+    /// # Arguments
+    /// * `bytes` - The entry, starting with its type and length.
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        let u16_at = |offset: usize| -> Option<u16> {
+            Some(u16::from_le_bytes(
+                bytes.get(offset..offset + 2)?.try_into().ok()?,
+            ))
+        };
+        let u32_at = |offset: usize| -> Option<u32> {
+            Some(u32::from_le_bytes(
+                bytes.get(offset..offset + 4)?.try_into().ok()?,
+            ))
+        };
+        let u64_at = |offset: usize| -> Option<u64> {
+            Some(u64::from_le_bytes(
+                bytes.get(offset..offset + 8)?.try_into().ok()?,
+            ))
+        };
+        let u8_at = |offset: usize| bytes.get(offset).copied();
+
+        let entry = match u8_at(0)? {
+            0 => Self::LocalApic {
+                processor_id: u8_at(2)?,
+                apic_id: u8_at(3)?,
+                flags: u32_at(4)?,
+            },
+            1 => Self::IoApic {
+                id: u8_at(2)?,
+                address: u32_at(4)?,
+                global_system_interrupt_base: u32_at(8)?,
+            },
+            2 => Self::InterruptSourceOverride {
+                bus: u8_at(2)?,
+                source: u8_at(3)?,
+                global_system_interrupt: u32_at(4)?,
+                flags: u16_at(8)?,
+            },
+            4 => Self::LocalApicNmi {
+                processor_id: u8_at(2)?,
+                flags: u16_at(3)?,
+                lint: u8_at(5)?,
+            },
+            5 => Self::LocalApicAddressOverride {
+                address: u64_at(4)?,
+            },
+            9 => Self::LocalX2Apic {
+                x2apic_id: u32_at(4)?,
+                flags: u32_at(8)?,
+                processor_uid: u32_at(12)?,
+            },
+            kind => Self::Unknown {
+                kind,
+                length: u8_at(1)?,
+            },
+        };
+
+        Some(entry)
+    }
+}
+
+impl Madt {
+    /// Flag set if the machine also has the legacy 8259 PICs.
+    pub const PCAT_COMPAT: u32 = 1 << 0;
+
+    /// Returns an iterator over the entries of the table. It stops at the first malformed entry.
+    pub fn entries(&self) -> impl Iterator<Item = MadtEntry> + '_ {
+        let length = (self.header.length as usize).saturating_sub(size_of::<Self>());
+        // Safety: the table is `header.length` bytes long, and its checksum was validated when it
+        // was found
+        let bytes = unsafe {
+            core::slice::from_raw_parts(
+                (self as *const Self as *const u8).add(size_of::<Self>()),
+                length,
+            )
+        };
+
+        let mut offset = 0;
+        core::iter::from_fn(move || {
+            let entry = bytes.get(offset..)?;
+            let entry_length = usize::from(*entry.get(1)?);
+            if entry_length < 2 || entry_length > entry.len() {
+                return None;
+            }
+            offset += entry_length;
+            MadtEntry::parse(&entry[..entry_length])
+        })
+    }
+
+    /// Returns the physical address of the local APIC registers.
+    pub fn local_apic_address(&self) -> PhysicalMemoryAddress {
+        let address = self
+            .entries()
+            .find_map(|entry| match entry {
+                MadtEntry::LocalApicAddressOverride { address } => Some(address),
+                _ => None,
+            })
+            .unwrap_or(u64::from(self.local_apic_address));
+
+        PhysicalMemoryAddress::new(address)
+    }
+
+    /// Returns an iterator over the local APIC IDs of the processors that can be used, the ones
+    /// enabled or online capable.
+    pub fn processors(&self) -> impl Iterator<Item = u32> + '_ {
+        let usable = MadtEntry::PROCESSOR_ENABLED | MadtEntry::PROCESSOR_ONLINE_CAPABLE;
+        self.entries().filter_map(move |entry| match entry {
+            MadtEntry::LocalApic { apic_id, flags, .. } if flags & usable != 0 => {
+                Some(u32::from(apic_id))
+            }
+            MadtEntry::LocalX2Apic {
+                x2apic_id, flags, ..
+            } if flags & usable != 0 => Some(x2apic_id),
+            _ => None,
+        })
+    }
+}
+
+unsafe impl AcpiTable for Madt {
+    const SIGNATURE: &'static [u8; 4] = b"APIC";
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
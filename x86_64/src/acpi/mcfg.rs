@@ -0,0 +1,60 @@
+use core::mem::size_of;
+
+use super::{AcpiTable, SdtHeader};
+use crate::memory::address::PhysicalMemoryAddress;
+
+/// PCI Express memory mapped configuration table. Tells where the configuration space of every
+/// PCI segment group is mapped.
+///
+/// The fixed part is followed by a list of [McfgEntry], read with [entries](Mcfg::entries).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Mcfg {
+    pub header: SdtHeader,
+    pub reserved: u64,
+}
+
+/// Configuration space of a range of buses of a PCI segment group. Every function of every
+/// device has 4KiB of configuration space, at `base_address + (bus << 20 | device << 15 |
+/// function << 12)`, where bus is relative to bus zero (not to `start_bus`).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct McfgEntry {
+    pub base_address: u64,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    pub reserved: u32,
+}
+
+impl McfgEntry {
+    /// Returns the physical address of the configuration space of bus zero.
+    pub fn base_address(&self) -> PhysicalMemoryAddress {
+        PhysicalMemoryAddress::new(self.base_address)
+    }
+}
+
+impl Mcfg {
+    /// Returns the entries of the table.
+    pub fn entries(&self) -> &[McfgEntry] {
+        let length = (self.header.length as usize).saturating_sub(size_of::<Self>());
+        // Safety: the table is `header.length` bytes long, and the entries are packed (their
+        // alignment is 1)
+        unsafe {
+            core::slice::from_raw_parts(
+                (self as *const Self as *const u8)
+                    .add(size_of::<Self>())
+                    .cast::<McfgEntry>(),
+                length / size_of::<McfgEntry>(),
+            )
+        }
+    }
+}
+
+unsafe impl AcpiTable for Mcfg {
+    const SIGNATURE: &'static [u8; 4] = b"MCFG";
+
+    fn header(&self) -> &SdtHeader {
+        &self.header
+    }
+}
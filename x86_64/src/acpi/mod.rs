@@ -0,0 +1,225 @@
+//! ACPI (Advanced Configuration and Power Interface) tables
+//!
+//! The firmware describes the hardware of the machine in a set of tables placed in memory. All of
+//! them start with the same header (`SdtHeader`) that contains a signature identifying the table
+//! and a checksum.
+//!
+//!  RSDP ----> RSDT/XSDT ----> HPET
+//!                        |--> MADT (APIC)
+//!                        |--> FADT (FACP) ----> DSDT
+//!                        |--> MCFG
+//!                        |--> ...
+//!
+//! The entry point is the RSDP (Root System Description Pointer), that we need to search in the
+//! BIOS memory areas (unless the bootloader tells us where it is). It points to the RSDT (Root
+//! System Description Table, 32 bits pointers) or, since ACPI 2.0, to the XSDT (eXtended System
+//! Description Table, 64 bits pointers), which contain the addresses of all the other tables.
+//!
+//! All the tables are accessed through the physical memory mapping (`physical_memory_offset`).
+//!
+//! For more info:
+//! https://wiki.osdev.org/RSDP
+//! https://wiki.osdev.org/RSDT
+//! https://uefi.org/specs/ACPI/6.5/05_ACPI_Software_Programming_Model.html
+mod dsdt;
+mod fadt;
+mod generic_address;
+mod hpet;
+mod madt;
+mod mcfg;
+mod rsdp;
+mod sdt;
+
+pub use dsdt::Dsdt;
+pub use fadt::Fadt;
+pub use generic_address::GenericAddress;
+pub use hpet::HpetTable;
+pub use madt::{Madt, MadtEntry};
+pub use mcfg::{Mcfg, McfgEntry};
+pub use rsdp::Rsdp;
+pub use sdt::SdtHeader;
+
+use crate::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+use core::mem::size_of;
+use core::ptr::read_unaligned;
+
+/// Represents all the possible errors that can happen when reading the ACPI tables.
+#[derive(Debug, PartialEq, Eq)]
+pub enum AcpiError {
+    /// The RSDP was not found in the BIOS memory areas.
+    RsdpNotFound,
+
+    /// The checksum of a table is invalid.
+    InvalidChecksum,
+
+    /// A table does not have the expected signature.
+    InvalidSignature,
+
+    /// The requested table is not present.
+    TableNotFound,
+}
+
+/// An ACPI table that can be found through the RSDT/XSDT.
+///
+/// # Safety
+///
+/// The implementer must be a `#[repr(C, packed)]` struct that starts with an `SdtHeader` and
+/// matches the layout of the table identified by `SIGNATURE`.
+pub unsafe trait AcpiTable {
+    /// Signature of the table.
+    const SIGNATURE: &'static [u8; 4];
+
+    /// Returns the header of the table.
+    fn header(&self) -> &SdtHeader;
+}
+
+/// Entry point to the ACPI tables.
+#[derive(Clone, Copy, Debug)]
+pub struct AcpiTables {
+    /// Virtual address where the physical memory is mapped.
+    physical_memory_offset: VirtualMemoryAddress,
+
+    /// Physical address of the RSDT or XSDT.
+    root_table: PhysicalMemoryAddress,
+
+    /// True if `root_table` points to the XSDT (64 bits entries).
+    extended: bool,
+}
+
+impl AcpiTables {
+    /// Searches the RSDP in the BIOS memory areas and validates the root table it points to.
+    ///
+    /// # Arguments
+    /// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the whole physical memory is mapped
+    /// at `physical_memory_offset`.
+    pub unsafe fn search(physical_memory_offset: VirtualMemoryAddress) -> Result<Self, AcpiError> {
+        let rsdp = Rsdp::search(physical_memory_offset)?;
+        Self::from_rsdp(physical_memory_offset, rsdp)
+    }
+
+    /// Validates the root table pointed by the given RSDP.
+    ///
+    /// # Arguments
+    /// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+    /// * `rsdp` - A valid RSDP.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the whole physical memory is mapped
+    /// at `physical_memory_offset`.
+    pub unsafe fn from_rsdp(
+        physical_memory_offset: VirtualMemoryAddress,
+        rsdp: &Rsdp,
+    ) -> Result<Self, AcpiError> {
+        let (root_table, extended, signature) = match rsdp.xsdt_address() {
+            Some(address) => (address, true, b"XSDT"),
+            None => (rsdp.rsdt_address(), false, b"RSDT"),
+        };
+
+        let tables = Self {
+            physical_memory_offset,
+            root_table,
+            extended,
+        };
+
+        let header = tables.header_at(root_table);
+        if header.signature != *signature {
+            return Err(AcpiError::InvalidSignature);
+        }
+        if !header.is_valid() {
+            return Err(AcpiError::InvalidChecksum);
+        }
+
+        Ok(tables)
+    }
+
+    /// Returns the revision of the ACPI specification the tables follow (the revision of the root
+    /// table).
+    pub fn revision(&self) -> u8 {
+        self.header_at(self.root_table).revision
+    }
+
+    /// Returns an iterator over the headers of all the tables listed in the RSDT/XSDT.
+    pub fn headers(&self) -> impl Iterator<Item = &'static SdtHeader> + '_ {
+        let root_table = self.header_at(self.root_table);
+        let entry_size = if self.extended {
+            size_of::<u64>()
+        } else {
+            size_of::<u32>()
+        };
+        let entries =
+            (root_table.length as usize).saturating_sub(size_of::<SdtHeader>()) / entry_size;
+        let first_entry = root_table as *const SdtHeader as usize + size_of::<SdtHeader>();
+
+        (0..entries).map(move |index| {
+            let entry = first_entry + index * entry_size;
+            // The entries are not aligned
+            let address = unsafe {
+                if self.extended {
+                    read_unaligned(entry as *const u64)
+                } else {
+                    u64::from(read_unaligned(entry as *const u32))
+                }
+            };
+            self.header_at(PhysicalMemoryAddress::new(address))
+        })
+    }
+
+    /// Finds a table and validates its checksum.
+    pub fn find<T: AcpiTable>(&self) -> Result<&'static T, AcpiError> {
+        let header = self
+            .headers()
+            .find(|header| header.signature == *T::SIGNATURE)
+            .ok_or(AcpiError::TableNotFound)?;
+
+        if !header.is_valid() {
+            return Err(AcpiError::InvalidChecksum);
+        }
+
+        // Safety: the `AcpiTable` contract guarantees that a table with this signature has the
+        // layout of `T`
+        Ok(unsafe { &*(header as *const SdtHeader as *const T) })
+    }
+
+    /// Returns the DSDT, pointed by the FADT, and validates its checksum.
+    pub fn dsdt(&self) -> Result<&'static Dsdt, AcpiError> {
+        let header = self.header_at(self.find::<Fadt>()?.dsdt_address());
+        if header.signature != *Dsdt::SIGNATURE {
+            return Err(AcpiError::InvalidSignature);
+        }
+        if !header.is_valid() {
+            return Err(AcpiError::InvalidChecksum);
+        }
+
+        // Safety: the signature was checked and `Dsdt` is only the header
+        Ok(unsafe { &*(header as *const SdtHeader as *const Dsdt) })
+    }
+
+    /// Returns the virtual address of the given physical address.
+    pub fn physical_to_virtual(&self, address: PhysicalMemoryAddress) -> VirtualMemoryAddress {
+        self.physical_memory_offset + address
+    }
+
+    /// Returns the header of the table located at the given physical address.
+    fn header_at(&self, address: PhysicalMemoryAddress) -> &'static SdtHeader {
+        // Safety: the constructors guarantee that the physical memory is mapped and the address
+        // comes from the firmware
+        unsafe { &*self.physical_to_virtual(address).as_mut_ptr() }
+    }
+}
+
+/// Returns true if all the bytes of the given memory region add up to zero (the ACPI checksum).
+///
+/// # Safety
+///
+/// This is unsafe because the programmer must be sure that the memory region is readable.
+pub(crate) unsafe fn checksum_is_valid(start: *const u8, length: usize) -> bool {
+    core::slice::from_raw_parts(start, length)
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        == 0
+}
@@ -0,0 +1,132 @@
+use super::{checksum_is_valid, AcpiError};
+use crate::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+
+/// Signature of the RSDP. Note the trailing space.
+const SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// Physical address where the BIOS stores the segment of the EBDA (Extended BIOS Data Area).
+const EBDA_SEGMENT_POINTER: u64 = 0x40e;
+
+/// Size of the EBDA region where the RSDP can be located.
+const EBDA_SEARCH_SIZE: u64 = 1024;
+
+/// Main BIOS area where the RSDP can be located.
+const BIOS_AREA_START: u64 = 0xe0000;
+const BIOS_AREA_END: u64 = 0x100000;
+
+/// The RSDP is always aligned to 16 bytes.
+const RSDP_ALIGNMENT: usize = 16;
+
+/// Size of the ACPI 1.0 part of the RSDP, covered by the first checksum.
+const RSDP_V1_SIZE: usize = 20;
+
+/// Root System Description Pointer.
+///
+/// The ACPI 1.0 version ends at `rsdt_address`, the rest of the fields exist only if `revision` is
+/// 2 or greater.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct Rsdp {
+    pub signature: [u8; 8],
+
+    /// Checksum of the first 20 bytes (the ACPI 1.0 part).
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+
+    /// 0 for ACPI 1.0, 2 for ACPI 2.0 onwards.
+    pub revision: u8,
+    pub rsdt_address: u32,
+
+    // ACPI 2.0 fields
+    /// Length of the whole structure.
+    pub length: u32,
+    pub xsdt_address: u64,
+
+    /// Checksum of the whole structure.
+    pub extended_checksum: u8,
+    pub reserved: [u8; 3],
+}
+
+impl Rsdp {
+    /// Searches the RSDP in the first KiB of the EBDA and in the main BIOS area (0xe0000 -
+    /// 0xfffff).
+    ///
+    /// # Arguments
+    /// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the first MiB of the physical
+    /// memory is mapped at `physical_memory_offset`.
+    pub unsafe fn search(
+        physical_memory_offset: VirtualMemoryAddress,
+    ) -> Result<&'static Self, AcpiError> {
+        let ebda_segment: *const u16 = (physical_memory_offset
+            + PhysicalMemoryAddress::new(EBDA_SEGMENT_POINTER))
+        .as_mut_ptr();
+        let ebda_start = u64::from(ebda_segment.read_unaligned()) << 4;
+
+        let areas = [
+            (ebda_start, ebda_start + EBDA_SEARCH_SIZE),
+            (BIOS_AREA_START, BIOS_AREA_END),
+        ];
+
+        areas
+            .iter()
+            // An EBDA segment of zero means there is no EBDA
+            .filter(|(start, _)| *start != 0)
+            .flat_map(|&(start, end)| (start..end).step_by(RSDP_ALIGNMENT))
+            .map(|address| {
+                &*(physical_memory_offset + PhysicalMemoryAddress::new(address))
+                    .as_mut_ptr::<Self>()
+            })
+            .find(|rsdp: &&Self| rsdp.signature == *SIGNATURE && rsdp.is_valid())
+            .ok_or(AcpiError::RsdpNotFound)
+    }
+
+    /// Creates a reference to the RSDP located at the given address and validates it. Used when
+    /// the bootloader already found the RSDP (for example, from the UEFI system table).
+    ///
+    /// # Arguments
+    /// * `address` - Virtual address of the RSDP.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that `address` is mapped.
+    pub unsafe fn from_address(address: VirtualMemoryAddress) -> Result<&'static Self, AcpiError> {
+        let rsdp: &Self = &*address.as_mut_ptr();
+        if rsdp.signature != *SIGNATURE {
+            return Err(AcpiError::InvalidSignature);
+        }
+        if !rsdp.is_valid() {
+            return Err(AcpiError::InvalidChecksum);
+        }
+
+        Ok(rsdp)
+    }
+
+    /// Returns true if the checksums are valid.
+    pub fn is_valid(&self) -> bool {
+        let start = self as *const Self as *const u8;
+        // Safety: the structure is at least `RSDP_V1_SIZE` long and, if the revision is 2 or
+        // greater, `length` bytes long
+        unsafe {
+            checksum_is_valid(start, RSDP_V1_SIZE)
+                && (self.revision < 2 || checksum_is_valid(start, self.length as usize))
+        }
+    }
+
+    /// Returns the physical address of the RSDT.
+    pub fn rsdt_address(&self) -> PhysicalMemoryAddress {
+        PhysicalMemoryAddress::new(u64::from(self.rsdt_address))
+    }
+
+    /// Returns the physical address of the XSDT, if present (ACPI 2.0 onwards).
+    pub fn xsdt_address(&self) -> Option<PhysicalMemoryAddress> {
+        if self.revision >= 2 && self.xsdt_address != 0 {
+            Some(PhysicalMemoryAddress::new(self.xsdt_address))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,29 @@
+use super::checksum_is_valid;
+
+/// System Description Table header, common to all the ACPI tables (except the RSDP).
+#[repr(C, packed)]
+#[derive(Clone, Copy, Debug)]
+pub struct SdtHeader {
+    /// Identifies the table, for example `APIC` for the MADT.
+    pub signature: [u8; 4],
+
+    /// Length of the table in bytes, including the header.
+    pub length: u32,
+    pub revision: u8,
+
+    /// All the bytes of the table must add up to zero.
+    pub checksum: u8,
+    pub oem_id: [u8; 6],
+    pub oem_table_id: [u8; 8],
+    pub oem_revision: u32,
+    pub creator_id: u32,
+    pub creator_revision: u32,
+}
+
+impl SdtHeader {
+    /// Returns true if the checksum of the whole table is valid.
+    pub fn is_valid(&self) -> bool {
+        // Safety: the header is followed by the rest of the table, `length` bytes in total
+        unsafe { checksum_is_valid(self as *const Self as *const u8, self.length as usize) }
+    }
+}
@@ -0,0 +1,465 @@
+//! CPUID instruction
+//!
+//! The CPUID instruction returns information about the processor. The information is organized in
+//! leaves: the leaf number is passed in EAX (and some of them have subleaves, selected with ECX)
+//! and the result is returned in EAX, EBX, ECX and EDX.
+//!
+//! Basic leaves start at 0x0000_0000 and extended leaves at 0x8000_0000. Leaf 0 (and 0x8000_0000
+//! for the extended ones) returns the highest supported leaf.
+//!
+//! On top of the raw instruction ([cpuid]) this module reads:
+//! - The vendor ([vendor]) and brand ([brand]) strings.
+//! - The feature bits ([Feature]), one at a time with [has] or all of them with [Features].
+//! - The processor topology ([topology]).
+//!
+//! For more info:
+//! https://wiki.osdev.org/CPUID
+//! https://www.felixcloutier.com/x86/cpuid
+use core::arch::asm;
+use core::fmt;
+
+/// Vendor string leaf, it also returns the highest basic leaf.
+const VENDOR_LEAF: u32 = 0x0000_0000;
+
+/// Processor info and feature bits leaf.
+const FEATURES_LEAF: u32 = 0x0000_0001;
+
+/// Position of the initial APIC ID in EBX, in the features leaf.
+const INITIAL_APIC_ID_SHIFT: u32 = 24;
+
+/// Position of the maximum number of logical processors per package in EBX, in the features leaf.
+const LOGICAL_PROCESSORS_SHIFT: u32 = 16;
+
+/// Deterministic cache parameters leaf (Intel), it reports the number of cores per package.
+const CACHE_PARAMETERS_LEAF: u32 = 0x0000_0004;
+
+/// Position of the number of cores per package minus one in EAX, in the cache parameters leaf.
+const CORES_SHIFT: u32 = 26;
+
+/// Structured extended feature flags leaf.
+const EXTENDED_FEATURES_LEAF: u32 = 0x0000_0007;
+
+/// Extended topology enumeration leaf.
+const TOPOLOGY_LEAF: u32 = 0x0000_000b;
+
+// Level types of the extended topology leaf (ECX bits 8 to 15)
+const TOPOLOGY_LEVEL_SMT: u32 = 1;
+const TOPOLOGY_LEVEL_CORE: u32 = 2;
+
+/// First extended leaf.
+const EXTENDED_LEAVES_BASE: u32 = 0x8000_0000;
+
+/// Extended processor info and feature bits leaf.
+const EXTENDED_FEATURES_INFO_LEAF: u32 = 0x8000_0001;
+
+/// First of the three leaves that return the brand string.
+const BRAND_STRING_LEAF: u32 = 0x8000_0002;
+
+/// Advanced power management information leaf.
+const ADVANCED_POWER_MANAGEMENT_LEAF: u32 = 0x8000_0007;
+
+/// Address sizes and core count leaf (AMD reports the number of cores per package here).
+const ADDRESS_SIZES_LEAF: u32 = 0x8000_0008;
+
+/// Registers returned by the CPUID instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuidResult {
+    pub eax: u32,
+    pub ebx: u32,
+    pub ecx: u32,
+    pub edx: u32,
+}
+
+/// Executes the CPUID instruction for the given leaf and subleaf.
+///
+/// Leaves that are not supported by the processor return garbage (usually the highest basic leaf
+/// data), check [max_leaf] or [max_extended_leaf] first.
+///
+/// # Arguments
+/// * `leaf` - Leaf to query (EAX).
+/// * `subleaf` - Subleaf to query (ECX), ignored by most of the leaves.
+pub fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    let eax: u32;
+    let ebx: u32;
+    let ecx: u32;
+    let edx: u32;
+    // RBX is reserved by LLVM, so we can not use it as an output operand. We save it in another
+    // register and swap them back after the instruction.
+    unsafe {
+        asm!(
+            "mov {0:r}, rbx",
+            "cpuid",
+            "xchg {0:r}, rbx",
+            out(reg) ebx,
+            inout("eax") leaf => eax,
+            inout("ecx") subleaf => ecx,
+            out("edx") edx,
+            options(nomem, nostack, preserves_flags)
+        )
+    }
+
+    CpuidResult { eax, ebx, ecx, edx }
+}
+
+/// Returns the highest basic leaf supported.
+pub fn max_leaf() -> u32 {
+    cpuid(0, 0).eax
+}
+
+/// Returns the highest extended leaf supported.
+pub fn max_extended_leaf() -> u32 {
+    cpuid(EXTENDED_LEAVES_BASE, 0).eax
+}
+
+/// Returns true if the processor has a local APIC.
+pub fn has_apic() -> bool {
+    has(Feature::Apic)
+}
+
+/// Returns the initial local APIC ID of the current processor. It identifies the processor even
+/// before the local APIC is initialized.
+pub fn initial_apic_id() -> u8 {
+    (cpuid(FEATURES_LEAF, 0).ebx >> INITIAL_APIC_ID_SHIFT) as u8
+}
+
+/// Returns true if the TSC runs at a constant rate in all ACPI P-, C- and T-states, so it can be
+/// used as a clock source.
+pub fn has_invariant_tsc() -> bool {
+    has(Feature::InvariantTsc)
+}
+
+/// Returns true if the processor supports the given feature.
+///
+/// # Arguments
+/// * `feature` - Feature to check.
+pub fn has(feature: Feature) -> bool {
+    feature.word().read() & (1 << feature.bit()) != 0
+}
+
+/// A fixed length ASCII string returned by CPUID, padded with NULs or spaces.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct CpuidString<const N: usize>([u8; N]);
+
+impl<const N: usize> CpuidString<N> {
+    /// Returns the string without the padding. Returns an empty string if it is not valid UTF-8.
+    pub fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.0)
+            .unwrap_or_default()
+            .trim_matches(|character| character == '\0' || character == ' ')
+    }
+
+    /// Builds a string from the bytes of a sequence of registers, in little endian order. Bytes
+    /// past `N` are ignored, and missing ones are left as zero (padding).
+    ///
+    /// # Arguments
+    /// * `registers` - Registers that contain the string.
+    pub fn from_registers(registers: impl IntoIterator<Item = u32>) -> Self {
+        let mut string = [0; N];
+        let bytes = registers.into_iter().flat_map(u32::to_le_bytes);
+        for (byte, value) in string.iter_mut().zip(bytes) {
+            *byte = value;
+        }
+
+        Self(string)
+    }
+}
+
+impl<const N: usize> fmt::Display for CpuidString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<const N: usize> fmt::Debug for CpuidString<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Returns the vendor string, like "GenuineIntel" or "AuthenticAMD".
+pub fn vendor() -> CpuidString<12> {
+    let result = cpuid(VENDOR_LEAF, 0);
+    CpuidString::from_registers([result.ebx, result.edx, result.ecx])
+}
+
+/// Returns the brand string (the model name of the processor), if the processor reports it.
+pub fn brand() -> Option<CpuidString<48>> {
+    if max_extended_leaf() < BRAND_STRING_LEAF + 2 {
+        return None;
+    }
+
+    let registers = (BRAND_STRING_LEAF..=BRAND_STRING_LEAF + 2).flat_map(|leaf| {
+        let result = cpuid(leaf, 0);
+        [result.eax, result.ebx, result.ecx, result.edx]
+    });
+    Some(CpuidString::from_registers(registers))
+}
+
+/// Register of a leaf where feature bits are reported.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FeatureWord {
+    FeaturesEdx = 0,
+    FeaturesEcx = 1,
+    ExtendedFeaturesEbx = 2,
+    ExtendedFeaturesEcx = 3,
+    ExtendedFeaturesInfoEdx = 4,
+    AdvancedPowerManagementEdx = 5,
+}
+
+impl FeatureWord {
+    /// Number of feature words.
+    const COUNT: usize = 6;
+
+    /// All the feature words, in the order of their discriminants.
+    const ALL: [Self; Self::COUNT] = [
+        Self::FeaturesEdx,
+        Self::FeaturesEcx,
+        Self::ExtendedFeaturesEbx,
+        Self::ExtendedFeaturesEcx,
+        Self::ExtendedFeaturesInfoEdx,
+        Self::AdvancedPowerManagementEdx,
+    ];
+
+    /// Reads the register. Returns zero (no features) if the leaf is not supported.
+    fn read(self) -> u32 {
+        let leaf = match self {
+            Self::FeaturesEdx | Self::FeaturesEcx => FEATURES_LEAF,
+            Self::ExtendedFeaturesEbx | Self::ExtendedFeaturesEcx => EXTENDED_FEATURES_LEAF,
+            Self::ExtendedFeaturesInfoEdx => EXTENDED_FEATURES_INFO_LEAF,
+            Self::AdvancedPowerManagementEdx => ADVANCED_POWER_MANAGEMENT_LEAF,
+        };
+        let max_leaf = if leaf >= EXTENDED_LEAVES_BASE {
+            max_extended_leaf()
+        } else {
+            max_leaf()
+        };
+        if leaf > max_leaf {
+            return 0;
+        }
+
+        let result = cpuid(leaf, 0);
+        match self {
+            Self::FeaturesEcx | Self::ExtendedFeaturesEcx => result.ecx,
+            Self::ExtendedFeaturesEbx => result.ebx,
+            Self::FeaturesEdx
+            | Self::ExtendedFeaturesInfoEdx
+            | Self::AdvancedPowerManagementEdx => result.edx,
+        }
+    }
+}
+
+/// Defines the [Feature] enum, where every feature is a bit of a feature word.
+macro_rules! features {
+    ($($(#[$doc: meta])* $feature: ident = ($word: ident, $bit: literal, $name: literal)),* $(,)?) => {
+        /// A processor feature reported by CPUID.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum Feature {
+            $($(#[$doc])* $feature),*
+        }
+
+        impl Feature {
+            /// All the features.
+            pub const ALL: &'static [Self] = &[$(Self::$feature),*];
+
+            /// Returns the name of the feature, the one Linux uses in `/proc/cpuinfo`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$feature => $name),*
+                }
+            }
+
+            /// Returns the register where the feature is reported.
+            fn word(self) -> FeatureWord {
+                match self {
+                    $(Self::$feature => FeatureWord::$word),*
+                }
+            }
+
+            /// Returns the bit of the register where the feature is reported.
+            fn bit(self) -> u32 {
+                match self {
+                    $(Self::$feature => $bit),*
+                }
+            }
+        }
+    };
+}
+
+features! {
+    /// x87 floating point unit.
+    Fpu = (FeaturesEdx, 0, "fpu"),
+    /// Time stamp counter.
+    Tsc = (FeaturesEdx, 4, "tsc"),
+    /// Model specific registers.
+    Msr = (FeaturesEdx, 5, "msr"),
+    /// Physical address extension.
+    Pae = (FeaturesEdx, 6, "pae"),
+    /// Local APIC.
+    Apic = (FeaturesEdx, 9, "apic"),
+    /// Global pages.
+    GlobalPages = (FeaturesEdx, 13, "pge"),
+    /// Page attribute table.
+    Pat = (FeaturesEdx, 16, "pat"),
+    /// FXSAVE and FXRSTOR instructions.
+    Fxsr = (FeaturesEdx, 24, "fxsr"),
+    Sse = (FeaturesEdx, 25, "sse"),
+    Sse2 = (FeaturesEdx, 26, "sse2"),
+    /// More than one logical processor per package.
+    HyperThreading = (FeaturesEdx, 28, "ht"),
+    Sse3 = (FeaturesEcx, 0, "pni"),
+    Ssse3 = (FeaturesEcx, 9, "ssse3"),
+    /// Fused multiply-add.
+    Fma = (FeaturesEcx, 12, "fma"),
+    /// Process-context identifiers.
+    Pcid = (FeaturesEcx, 17, "pcid"),
+    Sse41 = (FeaturesEcx, 19, "sse4_1"),
+    Sse42 = (FeaturesEcx, 20, "sse4_2"),
+    /// x2APIC mode of the local APIC.
+    X2Apic = (FeaturesEcx, 21, "x2apic"),
+    /// TSC deadline mode of the local APIC timer.
+    TscDeadline = (FeaturesEcx, 24, "tsc_deadline_timer"),
+    /// XSAVE family of instructions.
+    Xsave = (FeaturesEcx, 26, "xsave"),
+    /// The OS enabled XSAVE (CR4.OSXSAVE).
+    OsXsave = (FeaturesEcx, 27, "osxsave"),
+    Avx = (FeaturesEcx, 28, "avx"),
+    /// RDRAND instruction.
+    Rdrand = (FeaturesEcx, 30, "rdrand"),
+    /// Running under a hypervisor.
+    Hypervisor = (FeaturesEcx, 31, "hypervisor"),
+    /// RDFSBASE, WRFSBASE, RDGSBASE and WRGSBASE instructions.
+    FsGsBase = (ExtendedFeaturesEbx, 0, "fsgsbase"),
+    Avx2 = (ExtendedFeaturesEbx, 5, "avx2"),
+    /// Supervisor mode execution prevention.
+    Smep = (ExtendedFeaturesEbx, 7, "smep"),
+    /// INVPCID instruction.
+    Invpcid = (ExtendedFeaturesEbx, 10, "invpcid"),
+    Avx512f = (ExtendedFeaturesEbx, 16, "avx512f"),
+    /// RDSEED instruction.
+    Rdseed = (ExtendedFeaturesEbx, 18, "rdseed"),
+    /// Supervisor mode access prevention.
+    Smap = (ExtendedFeaturesEbx, 20, "smap"),
+    /// User mode instruction prevention.
+    Umip = (ExtendedFeaturesEcx, 2, "umip"),
+    /// SYSCALL and SYSRET instructions.
+    Syscall = (ExtendedFeaturesInfoEdx, 11, "syscall"),
+    /// No-execute page protection.
+    NoExecute = (ExtendedFeaturesInfoEdx, 20, "nx"),
+    /// 1 GiB pages.
+    Pages1GiB = (ExtendedFeaturesInfoEdx, 26, "pdpe1gb"),
+    /// RDTSCP instruction.
+    Rdtscp = (ExtendedFeaturesInfoEdx, 27, "rdtscp"),
+    /// 64 bit mode.
+    LongMode = (ExtendedFeaturesInfoEdx, 29, "lm"),
+    /// The TSC runs at a constant rate in all ACPI P-, C- and T-states.
+    InvariantTsc = (AdvancedPowerManagementEdx, 8, "invariant_tsc"),
+}
+
+/// The feature bits of the processor, read all at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Features {
+    /// Feature words, indexed by their discriminant.
+    words: [u32; FeatureWord::COUNT],
+}
+
+impl Features {
+    /// Reads the feature bits of the current processor.
+    pub fn read() -> Self {
+        Self {
+            words: FeatureWord::ALL.map(FeatureWord::read),
+        }
+    }
+
+    /// Returns true if the processor supports the given feature.
+    ///
+    /// # Arguments
+    /// * `feature` - Feature to check.
+    pub fn has(&self, feature: Feature) -> bool {
+        self.words[feature.word() as usize] & (1 << feature.bit()) != 0
+    }
+
+    /// Returns an iterator over the supported features.
+    pub fn iter(&self) -> impl Iterator<Item = Feature> + '_ {
+        Feature::ALL
+            .iter()
+            .copied()
+            .filter(|&feature| self.has(feature))
+    }
+}
+
+/// How the logical processors are organized in a package.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Topology {
+    /// Logical processors (hardware threads) per core.
+    pub threads_per_core: u32,
+
+    /// Cores per package.
+    pub cores_per_package: u32,
+}
+
+impl Topology {
+    /// Returns the number of logical processors per package.
+    pub fn logical_processors_per_package(&self) -> u32 {
+        self.threads_per_core * self.cores_per_package
+    }
+}
+
+/// Returns the topology of the package of the current processor. It comes from the extended
+/// topology leaf if the processor has it, from the legacy Intel and AMD leaves otherwise.
+pub fn topology() -> Topology {
+    extended_topology().unwrap_or_else(legacy_topology)
+}
+
+/// Reads the topology from the extended topology leaf, where every subleaf describes a level
+/// (SMT, core, ...) and the number of logical processors it contains.
+fn extended_topology() -> Option<Topology> {
+    if max_leaf() < TOPOLOGY_LEAF {
+        return None;
+    }
+
+    let (mut threads, mut logical_processors) = (None, None);
+    for subleaf in 0.. {
+        let result = cpuid(TOPOLOGY_LEAF, subleaf);
+        let count = result.ebx & 0xffff;
+        if count == 0 {
+            break;
+        }
+        match (result.ecx >> 8) & 0xff {
+            TOPOLOGY_LEVEL_SMT => threads = Some(count),
+            TOPOLOGY_LEVEL_CORE => logical_processors = Some(count),
+            _ => {}
+        }
+    }
+
+    let threads_per_core = threads.unwrap_or(1);
+    Some(Topology {
+        threads_per_core,
+        cores_per_package: (logical_processors? / threads_per_core).max(1),
+    })
+}
+
+/// Reads the topology from the features leaf and the vendor specific core count leaves.
+fn legacy_topology() -> Topology {
+    let logical_processors = if has(Feature::HyperThreading) {
+        ((cpuid(FEATURES_LEAF, 0).ebx >> LOGICAL_PROCESSORS_SHIFT) & 0xff).max(1)
+    } else {
+        1
+    };
+
+    let cores_per_package = if vendor().as_str() == "AuthenticAMD" {
+        if max_extended_leaf() >= ADDRESS_SIZES_LEAF {
+            (cpuid(ADDRESS_SIZES_LEAF, 0).ecx & 0xff) + 1
+        } else {
+            1
+        }
+    } else if max_leaf() >= CACHE_PARAMETERS_LEAF {
+        (cpuid(CACHE_PARAMETERS_LEAF, 0).eax >> CORES_SHIFT) + 1
+    } else {
+        1
+    };
+
+    Topology {
+        threads_per_core: (logical_processors / cores_per_package).max(1),
+        cores_per_package,
+    }
+}
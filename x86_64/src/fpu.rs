@@ -0,0 +1,165 @@
+//! x87 FPU, SSE and AVX state
+//!
+//! The x87, SSE and AVX units have their own registers (ST0 - ST7, XMM0 - XMM15, the upper halves
+//! of YMM0 - YMM15, and their control and status registers), that are not saved by the interrupts
+//! nor by the usual context switch code. They are saved to and restored from memory with:
+//!
+//! - FXSAVE/FXRSTOR: the x87 and SSE registers, in a 512 bytes area.
+//! - XSAVE/XRSTOR: the components enabled in XCR0 (x87, SSE, AVX...). The first 512 bytes are the
+//!   FXSAVE area, followed by a 64 bytes header and the rest of the components.
+//!
+//!  Offset
+//!  0      | FCW (x87 control word), FSW, FTW, ...
+//!  24     | MXCSR (SSE control and status)
+//!  32     | ST0 - ST7
+//!  160    | XMM0 - XMM15
+//!  512    | XSAVE header (which components are saved, XSTATE_BV)
+//!  576    | AVX (YMM0 - YMM15 upper halves)
+//!
+//! For more info:
+//! https://wiki.osdev.org/SSE
+//! https://www.felixcloutier.com/x86/fxsave
+//! https://www.felixcloutier.com/x86/xsave
+//! Intel 64 and IA-32 Architectures Software Developer's Manual, Volume 1, Chapter 13
+use core::arch::asm;
+
+use crate::cpuid;
+
+/// Processor extended state enumeration leaf.
+const EXTENDED_STATE_LEAF: u32 = 0x0000_000d;
+
+/// Value of the x87 control word after FNINIT: every exception masked, 64 bit precision and
+/// round to nearest.
+pub const DEFAULT_CONTROL_WORD: u16 = 0x037f;
+
+/// Value of MXCSR after reset: every exception masked and round to nearest.
+pub const DEFAULT_MXCSR: u32 = 0x1f80;
+
+/// Offset of the x87 control word in the save area.
+const CONTROL_WORD_OFFSET: usize = 0;
+
+/// Offset of MXCSR in the save area.
+const MXCSR_OFFSET: usize = 24;
+
+/// Save area of the x87, SSE and AVX registers of a task.
+///
+/// It is big enough for the FXSAVE area and for an XSAVE area with the x87, SSE and AVX
+/// components (832 bytes). Check [xsave_area_size] before enabling more components in XCR0.
+#[repr(C, align(64))]
+#[derive(Clone)]
+pub struct ExtendedState([u8; ExtendedState::SIZE]);
+
+impl ExtendedState {
+    /// Size of the area in bytes.
+    pub const SIZE: usize = 1024;
+
+    /// Creates an area with the initial state: registers cleared and the default x87 control
+    /// word and MXCSR. The XSAVE header is zeroed, so XRSTOR puts every component in its initial
+    /// state too.
+    pub const fn new() -> Self {
+        let mut area = [0; Self::SIZE];
+        let control_word = DEFAULT_CONTROL_WORD.to_le_bytes();
+        area[CONTROL_WORD_OFFSET] = control_word[0];
+        area[CONTROL_WORD_OFFSET + 1] = control_word[1];
+        let mxcsr = DEFAULT_MXCSR.to_le_bytes();
+        let mut index = 0;
+        while index < mxcsr.len() {
+            area[MXCSR_OFFSET + index] = mxcsr[index];
+            index += 1;
+        }
+
+        Self(area)
+    }
+
+    /// Saves the x87 and SSE registers with FXSAVE.
+    ///
+    /// # Safety
+    /// CR4.OSFXSR must be set and CR0.TS clear.
+    #[inline]
+    pub unsafe fn fxsave(&mut self) {
+        asm!("fxsave64 [{}]", in(reg) self.0.as_mut_ptr(), options(nostack, preserves_flags))
+    }
+
+    /// Restores the x87 and SSE registers with FXRSTOR.
+    ///
+    /// # Safety
+    /// CR4.OSFXSR must be set and CR0.TS clear. The area must contain a valid state (MXCSR
+    /// reserved bits clear), as the ones created with [new](Self::new) or saved with
+    /// [fxsave](Self::fxsave).
+    #[inline]
+    pub unsafe fn fxrstor(&self) {
+        asm!("fxrstor64 [{}]", in(reg) self.0.as_ptr(), options(nostack, preserves_flags))
+    }
+
+    /// Saves the components selected by `mask` (and enabled in XCR0) with XSAVE.
+    ///
+    /// # Arguments
+    /// * `mask` - Components to save, with the XCR0 bits.
+    ///
+    /// # Safety
+    /// CR4.OSXSAVE must be set and CR0.TS clear, and the enabled components must fit in the area.
+    #[inline]
+    pub unsafe fn xsave(&mut self, mask: u64) {
+        asm!(
+            "xsave64 [{}]",
+            in(reg) self.0.as_mut_ptr(),
+            in("eax") mask as u32,
+            in("edx") (mask >> 32) as u32,
+            options(nostack, preserves_flags)
+        )
+    }
+
+    /// Restores the components selected by `mask` (and enabled in XCR0) with XRSTOR.
+    ///
+    /// # Arguments
+    /// * `mask` - Components to restore, with the XCR0 bits.
+    ///
+    /// # Safety
+    /// CR4.OSXSAVE must be set and CR0.TS clear. The area must contain a valid state, as the ones
+    /// created with [new](Self::new) or saved with [xsave](Self::xsave).
+    #[inline]
+    pub unsafe fn xrstor(&self, mask: u64) {
+        asm!(
+            "xrstor64 [{}]",
+            in(reg) self.0.as_ptr(),
+            in("eax") mask as u32,
+            in("edx") (mask >> 32) as u32,
+            options(nostack, preserves_flags)
+        )
+    }
+}
+
+impl Default for ExtendedState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Resets the x87 FPU: default control word, empty register stack and no pending exceptions.
+///
+/// # Safety
+/// CR0.EM and CR0.TS must be clear. The x87 state of the running code is lost.
+#[inline]
+pub unsafe fn initialize_x87() {
+    asm!("fninit", options(nomem, nostack, preserves_flags))
+}
+
+/// Returns the size of the XSAVE area needed for the components currently enabled in XCR0, or
+/// `None` if the processor does not support XSAVE.
+pub fn xsave_area_size() -> Option<usize> {
+    if !cpuid::has(cpuid::Feature::Xsave) || cpuid::max_leaf() < EXTENDED_STATE_LEAF {
+        return None;
+    }
+
+    Some(cpuid::cpuid(EXTENDED_STATE_LEAF, 0).ebx as usize)
+}
+
+/// Returns the components (XCR0 bits) the processor supports.
+pub fn supported_components() -> u64 {
+    if !cpuid::has(cpuid::Feature::Xsave) || cpuid::max_leaf() < EXTENDED_STATE_LEAF {
+        return 0;
+    }
+
+    let result = cpuid::cpuid(EXTENDED_STATE_LEAF, 0);
+    u64::from(result.edx) << 32 | u64::from(result.eax)
+}
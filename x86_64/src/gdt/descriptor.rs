@@ -24,6 +24,7 @@
 use bit_field::BitField;
 
 use super::tss::TaskStateSegment;
+use crate::privilege::PrivilegeLevel;
 pub enum Descriptor {
     /// Descriptor for a code or data segment
     UserSegment(u64),
@@ -95,6 +96,17 @@ impl Descriptor {
         Self::UserSegment(Self::COMMON | Self::EXECUTABLE | Self::LONG_MODE | Self::DPL_RING_3)
     }
 
+    /// Returns the Descriptor Privilege Level (DPL) of the segment. It is the privilege level
+    /// that must be used in the selectors that point to it.
+    pub fn privilege_level(&self) -> PrivilegeLevel {
+        match self {
+            Descriptor::UserSegment(descriptor) => {
+                PrivilegeLevel::from_bits(descriptor.get_bits(45..47))
+            }
+            Descriptor::SystemSegment(low, _) => PrivilegeLevel::from_bits(low.get_bits(45..47)),
+        }
+    }
+
     /// Returns a System Segment Descriptor that points to the TSS
     #[inline]
     pub fn task_state_segment(tss: &'static TaskStateSegment) -> Descriptor {
@@ -17,7 +17,7 @@
 //! - Data Segment for Kernel (Ring 0)
 //! - Data Segment for User (Ring 3)
 //! - Code Segment for Kernel (Ring 0)
-//! - Code Segment for User (Ring 0)
+//! - Code Segment for User (Ring 3)
 //!
 
 mod descriptor;
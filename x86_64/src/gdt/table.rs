@@ -1,10 +1,7 @@
 use crate::memory::address::VirtualMemoryAddress;
 use core::arch::asm;
 
-use crate::{
-    privilege::PrivilegeLevel,
-    registers::segments::{SegmentSelector, CS},
-};
+use crate::registers::segments::{SegmentSelector, CS, DS, ES, SS};
 
 use super::descriptor::Descriptor;
 
@@ -14,8 +11,21 @@ pub enum GdtError {
     TableIsFull,
 }
 
+/// Segment selectors of the GDT entries used by the kernel.
 pub struct GDTSelectors {
+    /// Kernel code segment.
     pub cs: SegmentSelector,
+
+    /// Kernel data segment, also loaded in SS, DS and ES.
+    pub ds: SegmentSelector,
+
+    /// User code segment (RPL 3).
+    pub user_cs: SegmentSelector,
+
+    /// User data segment (RPL 3).
+    pub user_ds: SegmentSelector,
+
+    /// Task State Segment.
     pub tss: SegmentSelector,
 }
 
@@ -45,11 +55,13 @@ impl GlobalDescriptorTable {
     /// Adds a new entry into the GDT
     ///
     /// If the table is full and this function is used, the kernel will panic. Returns the segment
-    /// selector (GDT index) used for this entry.
+    /// selector (GDT index) used for this entry, with the privilege level of the descriptor as
+    /// requested privilege level.
     ///
     /// # Arguments
     /// * `entry` - A segment descriptor that will be added to the GDT.
     pub fn add_entry(&mut self, entry: Descriptor) -> Result<SegmentSelector, GdtError> {
+        let privilege_level = entry.privilege_level();
         let index = match entry {
             Descriptor::UserSegment(us) => {
                 if self.len == MAX_LENGTH {
@@ -72,19 +84,21 @@ impl GlobalDescriptorTable {
             }
         };
 
-        // TODO: At the moment we only use Ring0 but this must change
-        Ok(SegmentSelector::new(index as u16, PrivilegeLevel::Ring0))
+        Ok(SegmentSelector::new(index as u16, privilege_level))
     }
 
-    /// Updates the processor's segment registers.
+    /// Updates the processor's segment registers: CS with the kernel code segment and SS, DS and
+    /// ES with the kernel data segment.
     ///
     /// # Safety
     /// The caller must be sure that the GDT is already loaded and the Segment Selectors given are
     /// valid
     pub fn update_selector_registers(selectors: &GDTSelectors) {
-        // Update CS
         unsafe {
             CS::set_register(*selectors.cs as u8);
+            SS::set_register(*selectors.ds);
+            DS::set_register(*selectors.ds);
+            ES::set_register(*selectors.ds);
         }
     }
 
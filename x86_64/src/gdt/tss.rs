@@ -1,4 +1,8 @@
+use core::cell::UnsafeCell;
+use core::ptr;
+
 use crate::memory::address::VirtualMemoryAddress;
+use crate::privilege::PrivilegeLevel;
 
 /// Index for the double fault interrupt stack. Could be any number from 0 to 7, we chose 0.
 pub const DOUBLE_FAULT_IST_INDEX: usize = 0;
@@ -23,7 +27,10 @@ macro_rules! create_stack {
 #[repr(C, packed)]
 pub struct TaskStateSegment {
     reserved_1: u32,
-    privilege_stack_table: [VirtualMemoryAddress; 3],
+    /// Stacks loaded when an interrupt raises the privilege level to ring 0, 1 or 2. It changes
+    /// while the TSS is in use (the kernel stack of every task is different), so it is mutable
+    /// through a shared reference.
+    privilege_stack_table: UnsafeCell<[VirtualMemoryAddress; 3]>,
     reserved_2: u64,
     interrupt_stack_table: [VirtualMemoryAddress; 7],
     reserved_3: u64,
@@ -35,7 +42,7 @@ impl TaskStateSegment {
     /// Creates a new TSS.
     pub const fn new() -> Self {
         Self {
-            privilege_stack_table: [VirtualMemoryAddress::zero(); 3],
+            privilege_stack_table: UnsafeCell::new([VirtualMemoryAddress::zero(); 3]),
             interrupt_stack_table: [VirtualMemoryAddress::zero(); 7],
             io_map_base_address: 0,
             reserved_1: 0,
@@ -52,4 +59,50 @@ impl TaskStateSegment {
         // Initialize double fault interruption exception stack
         self.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX] = create_stack!(4096 * 5);
     }
+
+    /// Sets a stack of the interrupt stack table. The processor switches to it when an interrupt
+    /// whose IDT entry has this stack index arrives, whatever the privilege level is.
+    ///
+    /// Unlike [init](Self::init), every TSS can get its own stacks, as needed when there are
+    /// several processors.
+    ///
+    /// # Arguments
+    /// * `index` - Index of the stack in the table, from 0 to 6.
+    /// * `stack_top` - Top address of the stack.
+    pub fn set_interrupt_stack(&mut self, index: usize, stack_top: VirtualMemoryAddress) {
+        self.interrupt_stack_table[index] = stack_top;
+    }
+
+    /// Sets the stack the processor switches to when an interrupt (or exception) raises the
+    /// privilege level to `privilege_level`. For example, the ring 0 stack is used when an
+    /// interrupt arrives while running user code.
+    ///
+    /// # Arguments
+    /// * `privilege_level` - Privilege level of the stack. It must not be `Ring3`.
+    /// * `stack_top` - Top address of the stack.
+    ///
+    /// # Safety
+    /// The caller must be sure that the stack is valid and that no other processor is using this
+    /// TSS.
+    pub unsafe fn set_privilege_stack(
+        &self,
+        privilege_level: PrivilegeLevel,
+        stack_top: VirtualMemoryAddress,
+    ) {
+        assert!(
+            privilege_level != PrivilegeLevel::Ring3,
+            "there is no ring 3 privilege stack"
+        );
+
+        // The TSS is packed, the table can not be referenced
+        let table = UnsafeCell::raw_get(ptr::addr_of!(self.privilege_stack_table));
+        table
+            .cast::<VirtualMemoryAddress>()
+            .add(privilege_level as usize)
+            .write_unaligned(stack_top);
+    }
 }
+
+// The only field that changes through a shared reference is the privilege stack table, whose
+// writes are unsafe.
+unsafe impl Sync for TaskStateSegment {}
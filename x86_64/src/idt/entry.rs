@@ -8,8 +8,12 @@ use super::handlers::{
     HandlerFuncWithErrCodeDiverging,
     // PageFaultHandlerFunc,
 };
-use crate::registers::segments::CS;
+use super::trap_frame::TrapEntry;
+use crate::memory::address::VirtualMemoryAddress;
+use crate::privilege::PrivilegeLevel;
+use crate::registers::segments::{SegmentSelector, CS};
 use bit_field::BitField;
+use core::fmt::Debug;
 use core::marker::PhantomData;
 
 /// Represents an entry un the Interrupt descriptor table.
@@ -50,6 +54,94 @@ impl<F> Entry<F> {
             phantom: PhantomData,
         }
     }
+
+    /// Sets the address of the handler for the given entry. Returns a mutable reference to the
+    /// handler options in case we need to modify them beyond the standard options.
+    ///
+    /// This is meant for handlers written in assembly, that can not be expressed with the handler
+    /// function types.
+    ///
+    /// # Arguments
+    /// * `address` - Address of the first instruction of the handler.
+    ///
+    /// # Safety
+    /// The caller must be sure that the address points to code that handles the interrupt
+    /// (preserving the interrupted state) and returns with `iretq`.
+    pub unsafe fn set_handler_address(&mut self, address: VirtualMemoryAddress) -> &mut Options {
+        // Set the function pointer
+        let address = address.as_u64();
+        self.function_pointer_low = address as u16;
+        self.function_pointer_middle = (address >> 16) as u16;
+        self.function_pointer_high = (address >> 32) as u32;
+
+        // Set the gdt_selector to code segment
+        self.gdt_selector = *CS::get_register();
+
+        // Set the present flag
+        self.options.set_present(true);
+        self.options.disable_interrupts(true);
+
+        &mut self.options
+    }
+
+    /// Sets a trap handler for the given entry: a handler that can read and modify every register
+    /// of the interrupted code. Returns a mutable reference to the handler options in case we need
+    /// to modify them beyond the standard options.
+    ///
+    /// # Arguments
+    /// * `entry` - Entry point of the handler, created with
+    ///   [create_trap_handler](crate::create_trap_handler). It must be created with the
+    ///   `error_code` option if and only if the CPU pushes an error code for this entry.
+    pub fn set_trap_handler(&mut self, entry: TrapEntry) -> &mut Options {
+        // The entry point preserves the interrupted state and returns with `iretq`
+        unsafe { self.set_handler_address(VirtualMemoryAddress::new(entry.address())) }
+    }
+
+    /// Sets the code segment loaded when the handler is called. By default, it is the code
+    /// segment that was active when the handler was set.
+    ///
+    /// # Arguments
+    /// * `selector` - Code segment selector.
+    ///
+    /// # Safety
+    /// The caller must be sure that the selector points to a valid 64 bit code segment of the GDT
+    /// (or LDT) loaded when the interrupt arrives.
+    pub unsafe fn set_code_selector(&mut self, selector: SegmentSelector) -> &mut Options {
+        self.gdt_selector = *selector;
+        &mut self.options
+    }
+
+    /// Returns the address of the handler.
+    pub fn handler_address(&self) -> VirtualMemoryAddress {
+        VirtualMemoryAddress::new(
+            u64::from(self.function_pointer_low)
+                | (u64::from(self.function_pointer_middle) << 16)
+                | (u64::from(self.function_pointer_high) << 32),
+        )
+    }
+
+    /// Returns the code segment loaded when the handler is called.
+    pub fn code_selector(&self) -> u16 {
+        self.gdt_selector
+    }
+
+    /// Returns the entry options.
+    pub fn options(&self) -> Options {
+        self.options
+    }
+}
+
+impl<F> Debug for Entry<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Entry")
+            .field(
+                "handler_address",
+                &format_args!("0x{:x}", self.handler_address().as_u64()),
+            )
+            .field("code_selector", &format_args!("0x{:x}", self.gdt_selector))
+            .field("options", &self.options)
+            .finish()
+    }
 }
 
 macro_rules! implement_set_handler_function {
@@ -61,20 +153,9 @@ macro_rules! implement_set_handler_function {
             /// # Arguments
             /// * `hanlder` - A handler function.
             pub fn set_handler_function(&mut self, handler: $t) -> &mut Options {
-                // Set the function pointer
-                let handler = handler as usize;
-                self.function_pointer_low = handler as u16;
-                self.function_pointer_middle = (handler >> 16) as u16;
-                self.function_pointer_high = (handler >> 32) as u32;
-
-                // Set the gdt_selector to code segment
-                self.gdt_selector = *CS::get_register();
-
-                // Set the present flag
-                self.options.set_present(true);
-                self.options.disable_interrupts(true);
-
-                &mut self.options
+                let address = VirtualMemoryAddress::new(handler as usize as u64);
+                // The function types guarantee the interrupt calling convention
+                unsafe { self.set_handler_address(address) }
             }
         }
     };
@@ -91,17 +172,26 @@ implement_set_handler_function!(HandlerFuncWithErrCode);
 /// * 0 - 2: Interrupt Stack Table Index - 0: Don’t switch stacks, 1-7: Switch to the n-th stack in
 ///   the Interrupt Stack Table when this handler is called.
 /// * 3 - 7: (Reserved)
-/// * 8: Interrupt/Trap gate - If this bit is 0, interrupts are disabled when this handler is
-///   called.
-/// * 9 - 11: Must be one (try to expand this)
-/// * 12: Must be zero (try to expand this)
+/// * 8 - 11: Gate type - Interrupt gate (0b1110) or trap gate (0b1111). They only differ in the
+///   bit 8: if it is 0 (interrupt gate), interrupts are disabled when this handler is called.
+/// * 12: Must be zero
 /// * 13-14: Descriptor Privilege Level - The minimal privilege level required for calling this
-/// handler.
+///   handler.
 /// * 15: Present - If the entry is present.
 #[repr(transparent)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone)]
 pub struct Options(u16);
 
+/// Type of an IDT gate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GateType {
+    /// Interrupts are disabled while the handler runs. Used for hardware interrupts.
+    Interrupt = 0b1110,
+
+    /// Interrupts are left as they were. Used for exceptions and system calls.
+    Trap = 0b1111,
+}
+
 impl Options {
     /// Returns default Options.
     ///
@@ -115,8 +205,14 @@ impl Options {
     }
 
     /// Sets if the entry is present
-    pub fn set_present(&mut self, present: bool) {
+    pub fn set_present(&mut self, present: bool) -> &mut Self {
         self.0.set_bit(15, present);
+        self
+    }
+
+    /// Returns if the entry is present.
+    pub fn is_present(&self) -> bool {
+        self.0.get_bit(15)
     }
 
     /// Disables/Enables interrupts when another interrupt is beign handled.
@@ -125,6 +221,40 @@ impl Options {
         self
     }
 
+    /// Sets the gate type. It is the same as [disable_interrupts](Self::disable_interrupts), but
+    /// with the name used by the Intel manuals.
+    ///
+    /// # Arguments
+    /// * `gate_type` - Interrupt or trap gate.
+    pub fn set_gate_type(&mut self, gate_type: GateType) -> &mut Self {
+        self.0.set_bits(8..=11, gate_type as u16);
+        self
+    }
+
+    /// Returns the gate type.
+    pub fn gate_type(&self) -> GateType {
+        if self.0.get_bit(8) {
+            GateType::Trap
+        } else {
+            GateType::Interrupt
+        }
+    }
+
+    /// Sets the Descriptor Privilege Level: the minimum privilege level that can trigger this
+    /// interrupt with the `int` instruction. Hardware interrupts and exceptions ignore it.
+    ///
+    /// # Arguments
+    /// * `privilege_level` - Minimum privilege level.
+    pub fn set_privilege_level(&mut self, privilege_level: PrivilegeLevel) -> &mut Self {
+        self.0.set_bits(13..=14, privilege_level as u16);
+        self
+    }
+
+    /// Returns the Descriptor Privilege Level.
+    pub fn privilege_level(&self) -> PrivilegeLevel {
+        PrivilegeLevel::from_bits(u64::from(self.0.get_bits(13..=14)))
+    }
+
     /// Sets the stack index to be used for this interrupt
     pub fn set_stack_index(&mut self, index: usize) -> &mut Self {
         // We add 1 to the index because the hardware IST index starts at 1 but our indexing starts
@@ -132,4 +262,24 @@ impl Options {
         self.0.set_bits(0..=2, (index + 1) as u16);
         self
     }
+
+    /// Returns the stack index used for this interrupt, or `None` if the stack is not switched.
+    /// The index starts at 0, like in [set_stack_index](Self::set_stack_index).
+    pub fn stack_index(&self) -> Option<usize> {
+        match self.0.get_bits(0..=2) {
+            0 => None,
+            index => Some(usize::from(index) - 1),
+        }
+    }
+}
+
+impl Debug for Options {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Options")
+            .field("present", &self.is_present())
+            .field("gate_type", &self.gate_type())
+            .field("privilege_level", &self.privilege_level())
+            .field("stack_index", &self.stack_index())
+            .finish()
+    }
 }
@@ -43,6 +43,13 @@ pub struct InterruptStackFrame {
     stack_segment: u64,
 }
 
+impl InterruptStackFrame {
+    /// Returns if the interrupted code was running in user mode (ring 3).
+    pub fn from_user_mode(&self) -> bool {
+        self.code_segment & 0b11 == 3
+    }
+}
+
 impl Display for InterruptStackFrame {
     fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
         writeln!(f, "Instruction Pointer: 0x{:x}", self.instruction_pointer)?;
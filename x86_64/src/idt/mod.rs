@@ -6,6 +6,11 @@
 mod entry;
 mod handlers;
 mod table;
+mod trap_frame;
+mod vector;
 
+pub use entry::{Entry, GateType, Options};
 pub use handlers::InterruptStackFrame;
 pub use table::InterruptDescriptorTable;
+pub use trap_frame::{TrapEntry, TrapFrame, TrapHandlerFunc};
+pub use vector::{exception_name, ExceptionVector};
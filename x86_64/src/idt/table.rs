@@ -11,6 +11,7 @@ use super::{
 };
 use core::{
     arch::asm,
+    fmt::Debug,
     ops::{Index, IndexMut},
 };
 
@@ -98,6 +99,32 @@ impl InterruptDescriptorTable {
 
         unsafe { asm!("lidt [{}]", in(reg) &pointer, options(readonly, nostack, preserves_flags)) }
     }
+
+    /// Returns the 256 entries of the table, indexed by vector, without the handler types.
+    fn entries(&self) -> &[Entry<()>; 256] {
+        // Every entry has the same layout, the handler type is only a marker
+        unsafe { &*(self as *const Self).cast() }
+    }
+}
+
+impl Default for InterruptDescriptorTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Debug for InterruptDescriptorTable {
+    /// Lists the present entries, by vector number.
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_map()
+            .entries(
+                self.entries()
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, entry)| entry.options().is_present()),
+            )
+            .finish()
+    }
 }
 
 // NOTE: Indexes implementations taken from
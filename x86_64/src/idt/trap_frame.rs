@@ -0,0 +1,232 @@
+//! Full register trap frame
+//!
+//! The `x86-interrupt` handlers only get the [InterruptStackFrame](super::InterruptStackFrame),
+//! read only, and the compiler saves the general purpose registers where the handler can not see
+//! them. Trap handlers get a [TrapFrame] instead: every general purpose register, the vector, the
+//! error code and the frame pushed by the CPU. Everything can be modified, and the modified state
+//! is the one restored when the handler returns. This allows, for example, skipping the faulting
+//! instruction, emulating it (writing its results in the registers) or returning to another
+//! thread.
+//!
+//! Trap handlers are plain `extern "C"` functions. The entry point that builds the frame and
+//! restores it is generated by [create_trap_handler](crate::create_trap_handler).
+//!
+//! The entry points expect the kernel GS base (its per-CPU data) to be in IA32_GS_BASE while the
+//! kernel runs and in IA32_KERNEL_GS_BASE while user code runs: they execute `swapgs` when they
+//! interrupt user code and again when they return to it, so handlers always run with the kernel
+//! GS base.
+use core::fmt::{Display, Error, Formatter};
+
+/// Trap handler: gets the state of the interrupted code, that is restored when it returns.
+pub type TrapHandlerFunc = extern "C" fn(frame: &mut TrapFrame);
+
+/// Saved state of the interrupted code. The order matches the entry point pushes, the first field
+/// is the last one pushed.
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct TrapFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+
+    /// Vector number of the interrupt.
+    pub vector: u64,
+
+    /// Error code pushed by the CPU, zero for the vectors without one.
+    pub error_code: u64,
+
+    /// Instruction executed when the handler returns.
+    pub instruction_pointer: u64,
+
+    /// Code segment selector of the interrupted code.
+    pub code_segment: u64,
+
+    /// RFLAGS of the interrupted code.
+    pub cpu_flags: u64,
+
+    /// Stack pointer of the interrupted code.
+    pub stack_pointer: u64,
+
+    /// Stack segment selector of the interrupted code.
+    pub stack_segment: u64,
+}
+
+impl TrapFrame {
+    /// Returns if the interrupted code was running in user mode (ring 3).
+    pub fn from_user_mode(&self) -> bool {
+        self.code_segment & 0b11 == 3
+    }
+}
+
+impl Display for TrapFrame {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), Error> {
+        writeln!(
+            f,
+            "Vector: {} Error code: 0x{:x}",
+            self.vector, self.error_code
+        )?;
+        writeln!(
+            f,
+            "RIP: 0x{:016x} CS: 0x{:x} RFLAGS: 0x{:x}",
+            self.instruction_pointer, self.code_segment, self.cpu_flags
+        )?;
+        writeln!(
+            f,
+            "RSP: 0x{:016x} SS: 0x{:x}",
+            self.stack_pointer, self.stack_segment
+        )?;
+        let registers = [
+            ("RAX", self.rax),
+            ("RBX", self.rbx),
+            ("RCX", self.rcx),
+            ("RDX", self.rdx),
+            ("RSI", self.rsi),
+            ("RDI", self.rdi),
+            ("RBP", self.rbp),
+            ("R8", self.r8),
+            ("R9", self.r9),
+            ("R10", self.r10),
+            ("R11", self.r11),
+            ("R12", self.r12),
+            ("R13", self.r13),
+            ("R14", self.r14),
+            ("R15", self.r15),
+        ];
+        for (index, (name, value)) in registers.iter().enumerate() {
+            write!(f, "{name:>3}: 0x{value:016x}")?;
+            if index % 3 == 2 {
+                writeln!(f)?;
+            } else {
+                write!(f, " ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Entry point of a trap handler, generated by [create_trap_handler](crate::create_trap_handler).
+#[derive(Clone, Copy)]
+pub struct TrapEntry(unsafe extern "C" fn());
+
+impl TrapEntry {
+    /// Creates a trap entry point.
+    ///
+    /// # Arguments
+    /// * `entry` - Entry point code.
+    ///
+    /// # Safety
+    /// The code must save the state of the interrupted code and restore it with `iretq`. Use
+    /// [create_trap_handler](crate::create_trap_handler) instead.
+    pub const unsafe fn new(entry: unsafe extern "C" fn()) -> Self {
+        Self(entry)
+    }
+
+    /// Returns the address of the entry point.
+    pub fn address(&self) -> u64 {
+        self.0 as usize as u64
+    }
+}
+
+/// Creates the entry point of a [trap handler](TrapHandlerFunc), as a [TrapEntry] constant.
+///
+/// The entry point pushes a zero error code (for the vectors where the CPU does not push one),
+/// the vector and every general purpose register, and calls the handler with a pointer to them.
+/// When the handler returns, the registers are restored from the frame and the entry point returns
+/// with `iretq` to the (maybe modified) instruction and stack pointers of the frame.
+///
+/// If the interrupted code was running in user mode, the entry point executes `swapgs` before
+/// building the frame. Before returning, it executes `swapgs` if the frame returns to user mode,
+/// so handlers that change the code segment of the frame get the right GS base too.
+///
+/// The CPU aligns the stack to 16 bytes before pushing its frame, so it is still aligned after
+/// the 22 words of the trap frame, as the System V ABI requires when calling the handler.
+///
+/// # Arguments
+/// * `$name` - Name of the generated constant.
+/// * `$vector` - Vector number of the interrupt.
+/// * `$handler` - Trap handler.
+/// * `error_code` - Must be added for the exceptions where the CPU pushes an error code.
+#[macro_export]
+macro_rules! create_trap_handler {
+    ($name: ident, $vector: expr, $handler: path) => {
+        $crate::create_trap_handler!(@entry $name, $vector, $handler, "push 0",);
+    };
+    ($name: ident, $vector: expr, $handler: path, error_code) => {
+        $crate::create_trap_handler!(@entry $name, $vector, $handler, "",);
+    };
+    (@entry $name: ident, $vector: expr, $handler: path, $push_error_code: literal,) => {
+        pub const $name: $crate::idt::TrapEntry = {
+            #[unsafe(naked)]
+            unsafe extern "C" fn entry() {
+                core::arch::naked_asm!(
+                    $push_error_code,
+                    // Code segment of the interrupted code, after the error code and RIP
+                    "test qword ptr [rsp + 16], 3",
+                    "jz 2f",
+                    "swapgs",
+                    "2:",
+                    "push {vector}",
+                    "push rax",
+                    "push rbx",
+                    "push rcx",
+                    "push rdx",
+                    "push rsi",
+                    "push rdi",
+                    "push rbp",
+                    "push r8",
+                    "push r9",
+                    "push r10",
+                    "push r11",
+                    "push r12",
+                    "push r13",
+                    "push r14",
+                    "push r15",
+                    "mov rdi, rsp",
+                    "cld",
+                    "call {handler}",
+                    "pop r15",
+                    "pop r14",
+                    "pop r13",
+                    "pop r12",
+                    "pop r11",
+                    "pop r10",
+                    "pop r9",
+                    "pop r8",
+                    "pop rbp",
+                    "pop rdi",
+                    "pop rsi",
+                    "pop rdx",
+                    "pop rcx",
+                    "pop rbx",
+                    "pop rax",
+                    // Code segment returned to, after the vector, the error code and RIP
+                    "test qword ptr [rsp + 24], 3",
+                    "jz 3f",
+                    "swapgs",
+                    "3:",
+                    // Vector and error code
+                    "add rsp, 16",
+                    "iretq",
+                    vector = const $vector,
+                    handler = sym $handler,
+                )
+            }
+
+            // The handler type is checked here, `sym` accepts any function
+            const _: $crate::idt::TrapHandlerFunc = $handler;
+            unsafe { $crate::idt::TrapEntry::new(entry) }
+        };
+    };
+}
@@ -0,0 +1,78 @@
+//! CPU exception vectors
+//!
+//! The first 32 entries of the IDT are reserved for CPU exceptions. This module gives a name to
+//! each one of them.
+//!
+//! For more information:
+//! https://wiki.osdev.org/Exceptions
+
+/// Vector number of every CPU exception.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug)]
+pub enum ExceptionVector {
+    DivideByZero = 0,
+    Debug = 1,
+    NonMaskableInterrupt = 2,
+    Breakpoint = 3,
+    Overflow = 4,
+    BoundRangeExceeded = 5,
+    InvalidOpcode = 6,
+    DeviceNotAvailable = 7,
+    DoubleFault = 8,
+    CoprocessorSegmentOverrun = 9,
+    InvalidTss = 10,
+    SegmentNotPresent = 11,
+    StackSegmentFault = 12,
+    GeneralProtectionFault = 13,
+    PageFault = 14,
+    X87FloatingPoint = 16,
+    AlignmentCheck = 17,
+    MachineCheck = 18,
+    SimdFloatingPoint = 19,
+    Virtualization = 20,
+    SecurityException = 30,
+}
+
+impl ExceptionVector {
+    pub fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+/// Returns a human readable name for the exception with the given vector number. Vectors that
+/// are reserved or that are not exceptions (32 to 255) return `None`.
+///
+/// # Arguments
+/// * `vector` - IDT vector number.
+pub fn exception_name(vector: u8) -> Option<&'static str> {
+    let name = match vector {
+        0 => "Divide By Zero",
+        1 => "Debug",
+        2 => "Non Maskable Interrupt",
+        3 => "Breakpoint",
+        4 => "Overflow",
+        5 => "Bound Range Exceeded",
+        6 => "Invalid Opcode",
+        7 => "Device Not Available",
+        8 => "Double Fault",
+        9 => "Coprocessor Segment Overrun",
+        10 => "Invalid TSS",
+        11 => "Segment Not Present",
+        12 => "Stack Segment Fault",
+        13 => "General Protection Fault",
+        14 => "Page Fault",
+        16 => "x87 Floating Point",
+        17 => "Alignment Check",
+        18 => "Machine Check",
+        19 => "SIMD Floating Point",
+        20 => "Virtualization",
+        30 => "Security Exception",
+        _ => return None,
+    };
+
+    Some(name)
+}
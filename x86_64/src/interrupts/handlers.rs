@@ -2,21 +2,48 @@ use crate::idt::InterruptStackFrame;
 
 type HardwareInterruptHandler = extern "x86-interrupt" fn(_stack_frame: InterruptStackFrame);
 
-/// Creates an interrupt handler for an specific IRQ.
+/// Creates an interrupt handler for an specific IRQ, as a [TrapEntry](crate::idt::TrapEntry)
+/// constant.
 ///
-/// An interupt handler should always send and end of interrupt command. It also uses the
-/// "x86-interrupt" foreing calling convention.
+/// An interupt handler should always send and end of interrupt command. The handler is a
+/// [trap handler](crate::create_trap_handler), so its entry point switches to the kernel GS base
+/// when the interrupt arrives while running user code.
+///
+/// Every call is counted in the global
+/// [interrupt statistics](crate::interrupts::statistics::INTERRUPT_STATISTICS), and if latency
+/// tracking is enabled the duration of the handler (including the end of interrupt command) is
+/// measured too.
+///
+/// An optional `$exit` expression runs after the end of interrupt command and the measurement.
+/// It is the place to call the scheduler: by then the interrupt controller can deliver new
+/// interrupts, even if the handler switches to another task before returning.
 #[macro_export]
 macro_rules! create_interrupt_handler {
     ($name: ident, $irq: expr, $interrupt_controller: expr, $body: expr) => {
-        pub extern "x86-interrupt" fn $name(_stack_frame: $crate::idt::InterruptStackFrame) {
-            $body
+        $crate::create_interrupt_handler!($name, $irq, $interrupt_controller, $body, {});
+    };
+    ($name: ident, $irq: expr, $interrupt_controller: expr, $body: expr, $exit: expr) => {
+        pub const $name: $crate::idt::TrapEntry = {
+            extern "C" fn handler(_frame: &mut $crate::idt::TrapFrame) {
+                let measurement = $crate::interrupts::statistics::INTERRUPT_STATISTICS
+                    .start_measurement($irq.as_u8());
+
+                $body
 
-            unsafe {
-                $interrupt_controller.lock()
-                    .end_of_interrupt($irq.as_u8());
+                unsafe {
+                    $interrupt_controller.lock()
+                        .end_of_interrupt($irq.as_u8());
+                }
+
+                $crate::interrupts::statistics::INTERRUPT_STATISTICS
+                    .finish_measurement($irq.as_u8(), measurement);
+
+                $exit
             }
-        }
+
+            $crate::create_trap_handler!(ENTRY, $irq.as_u8(), handler);
+            ENTRY
+        };
     };
 }
 
@@ -36,6 +36,7 @@ use crate::interrupts::pic8259::{
 };
 
 use super::pic8259::Pic8259;
+use bit_field::BitField;
 
 // I/O Command port number
 const PIC_1_COMMAND: u8 = 0x20;
@@ -54,6 +55,9 @@ const PIC_2_DATA: u8 = PIC_2_COMMAND + 1;
 const PIC_1_OFFSET: u8 = 0x20;
 const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 
+/// IRQ line of the primary PIC where the secondary one is connected.
+const CASCADE_IRQ: u8 = 2;
+
 // TODO: IDK if this structure makes sense, check it when APIC is programmed
 /// Interrupts' indexes.
 ///
@@ -64,10 +68,11 @@ const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
     Keyboard = PIC_1_OFFSET + 1,
+    RealTimeClock = PIC_2_OFFSET,
 }
 
 impl InterruptIndex {
-    pub fn as_u8(self) -> u8 {
+    pub const fn as_u8(self) -> u8 {
         self as u8
     }
 
@@ -76,6 +81,35 @@ impl InterruptIndex {
     }
 }
 
+/// Returns the name of the device wired to the IRQ line that arrives at the CPU with the given
+/// vector number, following the IBM PC/AT layout. Vectors outside the remapped PICs range return
+/// `None`.
+///
+/// # Arguments
+/// * `vector` - IDT vector number.
+pub fn irq_line_name(vector: u8) -> Option<&'static str> {
+    let name = match vector.checked_sub(PIC_1_OFFSET)? {
+        0 => "Timer",
+        1 => "Keyboard",
+        2 => "Cascade",
+        3 => "Serial Port 2",
+        4 => "Serial Port 1",
+        5 => "Parallel Port 2/3",
+        6 => "Floppy Disk",
+        7 => "Parallel Port 1",
+        8 => "Real Time Clock",
+        9 => "ACPI",
+        10 | 11 => "Available",
+        12 => "Mouse",
+        13 => "Co-Processor",
+        14 => "Primary ATA",
+        15 => "Secondary ATA",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
 /// Struct that implements the IBM PC/AT 8259 architecture.
 pub struct IBMPcAt8259 {
     pic1: Pic8259,
@@ -136,6 +170,28 @@ impl IBMPcAt8259 {
         pic.write_mask(mask)
     }
 
+    /// Masks (disables) or unmasks (enables) a single IRQ line.
+    ///
+    /// Unmasking a line of the secondary PIC also unmasks the cascade line (IRQ 2) of the primary
+    /// one, otherwise the interrupt would never reach the CPU.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because:
+    /// - IRQ index must be valid (0 <= IRQ <= 15)
+    /// - Programmer must be sure that the I/O port we are using is valid and initialized.
+    /// - There must be a handler for the IRQ when it is unmasked.
+    pub unsafe fn set_masked(&mut self, irq: u8, masked: bool) {
+        let pic = self.get_pic(irq);
+        let mut mask = pic.read_mask();
+        mask.set_bit(usize::from(irq % 8), masked);
+        pic.write_mask(mask);
+
+        if irq >= 8 && !masked {
+            self.set_masked(CASCADE_IRQ, false);
+        }
+    }
+
     pub unsafe fn disable(&mut self) {
         self.pic1.disable();
         self.pic2.disable();
@@ -0,0 +1,348 @@
+//! Implementation for the Local APIC (Advanced Programmable Interrupt Controller)
+//!
+//! Every processor has its own local APIC. It receives the interrupts from the I/O APIC (or the
+//! legacy PIC, through the LINT0 pin in virtual wire mode) and other processors, and it has a
+//! timer that can fire interrupts in one-shot or periodic mode. It also sends interrupts to other
+//! processors (IPIs, Inter-Processor Interrupts), which is how they are started and notified.
+//!
+//! The local APIC is configured through memory mapped registers, 4 KiB aligned at the address
+//! given by the IA32_APIC_BASE MSR (usually 0xfee00000). The registers used here are:
+//!
+//!  Offset | Register
+//!  0x020  | Local APIC ID
+//!  0x0b0  | End Of Interrupt (write only)
+//!  0x0f0  | Spurious Interrupt Vector (bit 8 enables the APIC)
+//!  0x300  | Interrupt Command, low half (writing it sends the IPI)
+//!  0x310  | Interrupt Command, high half (destination APIC ID in bits 24..32)
+//!  0x320  | LVT Timer (vector, mask and mode of the timer interrupt)
+//!  0x380  | Timer Initial Count
+//!  0x390  | Timer Current Count
+//!  0x3e0  | Timer Divide Configuration
+//!
+//! The timer counts down from the initial count at the bus (or core crystal) frequency divided by
+//! the divide configuration. That frequency is not reported by the processor, so the timer must be
+//! calibrated against another timer.
+//!
+//! This code and comments are havily based on:
+//! - https://wiki.osdev.org/APIC
+//! - https://wiki.osdev.org/APIC_Timer
+//! - https://wiki.osdev.org/Symmetric_Multiprocessing
+//! - Intel 64 and IA-32 Architectures Software Developer's Manual, Volume 3, Chapter 11
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress};
+use crate::registers::msr::Msr;
+
+// Registers offsets
+const ID: u64 = 0x020;
+const END_OF_INTERRUPT: u64 = 0x0b0;
+const SPURIOUS_INTERRUPT_VECTOR: u64 = 0x0f0;
+const INTERRUPT_COMMAND_LOW: u64 = 0x300;
+const INTERRUPT_COMMAND_HIGH: u64 = 0x310;
+const LVT_TIMER: u64 = 0x320;
+const TIMER_INITIAL_COUNT: u64 = 0x380;
+const TIMER_CURRENT_COUNT: u64 = 0x390;
+const TIMER_DIVIDE_CONFIGURATION: u64 = 0x3e0;
+
+// IA32_APIC_BASE MSR bits
+const APIC_GLOBAL_ENABLE: u64 = 1 << 11;
+const APIC_BASE_MASK: u64 = 0x000f_ffff_ffff_f000;
+
+// Spurious Interrupt Vector register bits
+const APIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+
+// LVT Timer register bits
+const LVT_MASKED: u32 = 1 << 16;
+const TIMER_PERIODIC: u32 = 1 << 17;
+
+// Interrupt Command register bits
+const DELIVERY_MODE_INIT: u32 = 0b101 << 8;
+const DELIVERY_MODE_STARTUP: u32 = 0b110 << 8;
+const DELIVERY_PENDING: u32 = 1 << 12;
+const LEVEL_ASSERT: u32 = 1 << 14;
+const DESTINATION_ALL_EXCLUDING_SELF: u32 = 0b11 << 18;
+
+/// Interrupt vectors used by the local APIC. They are placed after the legacy PIC ones.
+#[derive(Debug, Clone, Copy)]
+#[repr(u8)]
+pub enum LocalApicInterruptIndex {
+    Timer = 0x30,
+
+    /// Sent to the other processors to invalidate a page in their TLBs.
+    TlbShootdown = 0x31,
+
+    /// Fired when an interrupt is withdrawn before it is delivered. It must not be acknowledged.
+    Spurious = 0xff,
+}
+
+impl LocalApicInterruptIndex {
+    pub const fn as_u8(self) -> u8 {
+        self as u8
+    }
+
+    pub fn as_usize(self) -> usize {
+        usize::from(self.as_u8())
+    }
+}
+
+/// Returns the name of a local APIC vector.
+///
+/// # Arguments
+/// * `vector` - Interrupt vector.
+pub fn local_apic_vector_name(vector: u8) -> Option<&'static str> {
+    match vector {
+        v if v == LocalApicInterruptIndex::Timer.as_u8() => Some("Local APIC Timer"),
+        v if v == LocalApicInterruptIndex::TlbShootdown.as_u8() => Some("TLB Shootdown"),
+        v if v == LocalApicInterruptIndex::Spurious.as_u8() => Some("Local APIC Spurious"),
+        _ => None,
+    }
+}
+
+/// Operating mode of the local APIC timer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LocalApicTimerMode {
+    /// The timer fires once when the count reaches zero.
+    OneShot,
+
+    /// The count is reloaded with the initial count every time it reaches zero.
+    Periodic,
+}
+
+/// Value the timer counter frequency is divided by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum LocalApicTimerDivide {
+    By1 = 0b1011,
+    By2 = 0b0000,
+    By4 = 0b0001,
+    By8 = 0b0010,
+    By16 = 0b0011,
+    By32 = 0b1000,
+    By64 = 0b1001,
+    By128 = 0b1010,
+}
+
+/// The local APIC of the current processor.
+pub struct LocalApic {
+    /// Virtual address where the registers are mapped, zero if not initialized.
+    base: VirtualMemoryAddress,
+}
+
+impl LocalApic {
+    /// Creates a new, uninitialized, instance of the local APIC.
+    pub const fn new() -> Self {
+        Self {
+            base: VirtualMemoryAddress::zero(),
+        }
+    }
+
+    /// Returns the physical address of the local APIC registers.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the processor has a local APIC
+    /// (otherwise the MSR does not exist).
+    pub unsafe fn physical_base() -> PhysicalMemoryAddress {
+        PhysicalMemoryAddress::new(Msr::IA32_APIC_BASE.read() & APIC_BASE_MASK)
+    }
+
+    /// Enables the local APIC. The timer is masked until it is started.
+    ///
+    /// # Arguments
+    /// * `physical_memory_offset` - Virtual address where the physical memory is mapped.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because:
+    /// - The processor must have a local APIC.
+    /// - The physical memory must be mapped at `physical_memory_offset` (including the local APIC
+    ///   registers, whose page must not be cached).
+    /// - There must be a handler for the spurious interrupt vector.
+    pub unsafe fn initialize(&mut self, physical_memory_offset: VirtualMemoryAddress) {
+        let mut apic_base = Msr::IA32_APIC_BASE;
+        apic_base.write(apic_base.read() | APIC_GLOBAL_ENABLE);
+
+        self.base = physical_memory_offset + Self::physical_base();
+        self.write(LVT_TIMER, LVT_MASKED);
+        self.write(
+            SPURIOUS_INTERRUPT_VECTOR,
+            APIC_SOFTWARE_ENABLE | u32::from(LocalApicInterruptIndex::Spurious.as_u8()),
+        );
+    }
+
+    /// Returns true if the local APIC was initialized.
+    pub fn is_initialized(&self) -> bool {
+        self.base.as_u64() != 0
+    }
+
+    /// Returns the ID of the local APIC.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized.
+    pub unsafe fn id(&self) -> u8 {
+        (self.read(ID) >> 24) as u8
+    }
+
+    /// Notifies the local APIC that the interrupt handler finished. The vector is not needed, the
+    /// APIC knows which interrupt is being served, but it keeps the same interface as the PIC.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized.
+    pub unsafe fn end_of_interrupt(&mut self, _vector: u8) {
+        self.write(END_OF_INTERRUPT, 0);
+    }
+
+    /// Starts the timer. It fires the `LocalApicInterruptIndex::Timer` vector.
+    ///
+    /// # Arguments
+    /// * `initial_count` - Value the timer counts down from.
+    /// * `divide` - Value the counter frequency is divided by.
+    /// * `mode` - Timer mode.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized and there must be a handler for
+    /// the timer vector.
+    pub unsafe fn start_timer(
+        &mut self,
+        initial_count: u32,
+        divide: LocalApicTimerDivide,
+        mode: LocalApicTimerMode,
+    ) {
+        let mode = match mode {
+            LocalApicTimerMode::OneShot => 0,
+            LocalApicTimerMode::Periodic => TIMER_PERIODIC,
+        };
+
+        self.write(TIMER_DIVIDE_CONFIGURATION, divide as u32);
+        self.write(
+            LVT_TIMER,
+            mode | u32::from(LocalApicInterruptIndex::Timer.as_u8()),
+        );
+        // Writing the initial count starts the timer
+        self.write(TIMER_INITIAL_COUNT, initial_count);
+    }
+
+    /// Starts the timer with its interrupt masked. Used to calibrate it.
+    ///
+    /// # Arguments
+    /// * `initial_count` - Value the timer counts down from.
+    /// * `divide` - Value the counter frequency is divided by.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized.
+    pub unsafe fn start_masked_timer(&mut self, initial_count: u32, divide: LocalApicTimerDivide) {
+        self.write(TIMER_DIVIDE_CONFIGURATION, divide as u32);
+        self.write(LVT_TIMER, LVT_MASKED);
+        self.write(TIMER_INITIAL_COUNT, initial_count);
+    }
+
+    /// Stops the timer.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized.
+    pub unsafe fn stop_timer(&mut self) {
+        self.write(TIMER_INITIAL_COUNT, 0);
+        self.write(LVT_TIMER, LVT_MASKED);
+    }
+
+    /// Returns the current value of the timer count down.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized.
+    pub unsafe fn timer_current_count(&self) -> u32 {
+        self.read(TIMER_CURRENT_COUNT)
+    }
+
+    /// Sends an INIT IPI to a processor, which resets it and leaves it waiting for a startup IPI.
+    ///
+    /// # Arguments
+    /// * `apic_id` - Local APIC ID of the processor.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized and the processor must not be
+    /// running anything.
+    pub unsafe fn send_init(&mut self, apic_id: u8) {
+        self.send_interrupt_command(apic_id, DELIVERY_MODE_INIT | LEVEL_ASSERT);
+    }
+
+    /// Sends a startup IPI to a processor waiting for it after an INIT IPI. The processor starts
+    /// in real mode at the beginning of the given page (`CS:IP` = `page << 8:0`).
+    ///
+    /// # Arguments
+    /// * `apic_id` - Local APIC ID of the processor.
+    /// * `page` - Number of the 4KiB page where the start up code is, in the first MiB.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized and the page must contain the
+    /// real mode start up code.
+    pub unsafe fn send_startup(&mut self, apic_id: u8, page: u8) {
+        self.send_interrupt_command(
+            apic_id,
+            DELIVERY_MODE_STARTUP | LEVEL_ASSERT | u32::from(page),
+        );
+    }
+
+    /// Sends an interrupt to a processor.
+    ///
+    /// # Arguments
+    /// * `apic_id` - Local APIC ID of the processor.
+    /// * `vector` - Interrupt vector.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized and the processor must have a
+    /// handler for the vector.
+    pub unsafe fn send_ipi(&mut self, apic_id: u8, vector: u8) {
+        self.send_interrupt_command(apic_id, u32::from(vector));
+    }
+
+    /// Sends an interrupt to every processor except the current one.
+    ///
+    /// # Arguments
+    /// * `vector` - Interrupt vector.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the local APIC must be initialized and every processor must have a
+    /// handler for the vector.
+    pub unsafe fn broadcast_ipi(&mut self, vector: u8) {
+        self.send_interrupt_command(0, DESTINATION_ALL_EXCLUDING_SELF | u32::from(vector));
+    }
+
+    /// Writes the Interrupt Command register, which sends the IPI, and waits until it is
+    /// delivered.
+    unsafe fn send_interrupt_command(&mut self, apic_id: u8, command: u32) {
+        self.write(INTERRUPT_COMMAND_HIGH, u32::from(apic_id) << 24);
+        self.write(INTERRUPT_COMMAND_LOW, command);
+        while self.read(INTERRUPT_COMMAND_LOW) & DELIVERY_PENDING != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Reads a register.
+    unsafe fn read(&self, offset: u64) -> u32 {
+        read_volatile(VirtualMemoryAddress::new(self.base.as_u64() + offset).as_mut_ptr())
+    }
+
+    /// Writes a register.
+    unsafe fn write(&mut self, offset: u64, value: u32) {
+        write_volatile(
+            VirtualMemoryAddress::new(self.base.as_u64() + offset).as_mut_ptr(),
+            value,
+        )
+    }
+}
+
+impl Default for LocalApic {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,11 @@
 pub mod handlers;
 mod ibm_pc_at_8259;
+mod local_apic;
 pub(crate) mod pic8259;
+pub mod statistics;
 
-pub use self::ibm_pc_at_8259::{IBMPcAt8259, InterruptIndex};
+pub use self::ibm_pc_at_8259::{irq_line_name, IBMPcAt8259, InterruptIndex};
+pub use self::local_apic::{
+    local_apic_vector_name, LocalApic, LocalApicInterruptIndex, LocalApicTimerDivide,
+    LocalApicTimerMode,
+};
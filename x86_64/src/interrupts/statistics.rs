@@ -0,0 +1,240 @@
+//! Interrupt statistics and latency accounting
+//!
+//! Keeps a counter for every IDT vector (exceptions and IRQs) with the number of times it was
+//! handled. Optionally, it also measures how long the handlers take to run using the Time Stamp
+//! Counter and keeps a histogram per vector.
+//!
+//! Everything is stored in atomics, so the statistics can be updated from inside any interrupt
+//! handler without taking a lock (taking a lock inside a handler is a good recipe for a deadlock).
+//!
+//! The counters are updated by the handlers generated with the
+//! [create_interrupt_handler](crate::create_interrupt_handler) macro. Exception handlers must call
+//! [record](InterruptStatistics::record) by hand.
+use core::fmt::{Display, Formatter, Result};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::idt::exception_name;
+use crate::interrupts::{irq_line_name, local_apic_vector_name};
+use crate::registers::tsc::Tsc;
+
+/// Number of entries in the IDT.
+const VECTORS: usize = 256;
+
+/// Number of buckets of the latency histograms.
+pub const HISTOGRAM_BUCKETS: usize = 16;
+
+/// The first bucket of the histograms holds the handlers that took less than
+/// 2^FIRST_BUCKET_SHIFT cycles. Every following bucket doubles the limit of the previous one, and
+/// the last one holds everything that did not fit in the others.
+const FIRST_BUCKET_SHIFT: u32 = 10;
+
+/// Global interrupt statistics.
+pub static INTERRUPT_STATISTICS: InterruptStatistics = InterruptStatistics::new();
+
+/// Per-vector interrupt counters and handler duration histograms.
+pub struct InterruptStatistics {
+    /// Number of times each vector was handled.
+    counters: [AtomicU64; VECTORS],
+
+    /// Wether the handlers durations should be measured or not.
+    latency_tracking: AtomicBool,
+
+    /// Handler durations (in TSC cycles) histogram for every vector.
+    histograms: [[AtomicU64; HISTOGRAM_BUCKETS]; VECTORS],
+
+    /// Longest handler duration (in TSC cycles) for every vector.
+    max_cycles: [AtomicU64; VECTORS],
+}
+
+impl InterruptStatistics {
+    /// Creates a new set of statistics with all the counters in zero and latency tracking
+    /// disabled.
+    pub const fn new() -> Self {
+        Self {
+            counters: [const { AtomicU64::new(0) }; VECTORS],
+            latency_tracking: AtomicBool::new(false),
+            histograms: [const { [const { AtomicU64::new(0) }; HISTOGRAM_BUCKETS] }; VECTORS],
+            max_cycles: [const { AtomicU64::new(0) }; VECTORS],
+        }
+    }
+
+    /// Enables or disables the handlers duration measurement.
+    ///
+    /// Reading the TSC twice per interrupt is cheap but not free, that is why this is disabled by
+    /// default.
+    pub fn set_latency_tracking(&self, enabled: bool) {
+        self.latency_tracking.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns if the handlers duration is being measured.
+    pub fn is_latency_tracking_enabled(&self) -> bool {
+        self.latency_tracking.load(Ordering::Relaxed)
+    }
+
+    /// Counts one occurrence of the given vector.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    #[inline]
+    pub fn record(&self, vector: u8) {
+        self.counters[usize::from(vector)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Counts one occurrence of the given vector and, if latency tracking is enabled, returns the
+    /// TSC value at the beginning of the handler. The returned value must be passed to
+    /// [finish_measurement](Self::finish_measurement) when the handler is done.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    #[inline]
+    pub fn start_measurement(&self, vector: u8) -> Option<u64> {
+        self.record(vector);
+        self.is_latency_tracking_enabled().then(Tsc::read)
+    }
+
+    /// Adds the time elapsed since `start` to the vector's histogram.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    /// * `start` - Value returned by [start_measurement](Self::start_measurement).
+    #[inline]
+    pub fn finish_measurement(&self, vector: u8, start: Option<u64>) {
+        let Some(start) = start else {
+            return;
+        };
+
+        let cycles = Tsc::read().wrapping_sub(start);
+        let vector = usize::from(vector);
+        self.histograms[vector][Self::bucket(cycles)].fetch_add(1, Ordering::Relaxed);
+        self.max_cycles[vector].fetch_max(cycles, Ordering::Relaxed);
+    }
+
+    /// Returns how many times the given vector was handled.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    pub fn count(&self, vector: u8) -> u64 {
+        self.counters[usize::from(vector)].load(Ordering::Relaxed)
+    }
+
+    /// Returns the total number of handled interrupts.
+    pub fn total(&self) -> u64 {
+        self.counters
+            .iter()
+            .map(|counter| counter.load(Ordering::Relaxed))
+            .sum()
+    }
+
+    /// Returns a copy of the handler duration histogram of the given vector. Bucket `i` holds the
+    /// handlers that took less than [bucket_limit(i)](Self::bucket_limit) cycles.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    pub fn histogram(&self, vector: u8) -> [u64; HISTOGRAM_BUCKETS] {
+        let histogram = &self.histograms[usize::from(vector)];
+        core::array::from_fn(|bucket| histogram[bucket].load(Ordering::Relaxed))
+    }
+
+    /// Returns the longest measured handler duration (in TSC cycles) for the given vector.
+    ///
+    /// # Arguments
+    /// * `vector` - IDT vector number.
+    pub fn max_cycles(&self, vector: u8) -> u64 {
+        self.max_cycles[usize::from(vector)].load(Ordering::Relaxed)
+    }
+
+    /// Returns the upper limit (exclusive, in cycles) of the given histogram bucket. The last
+    /// bucket has no limit.
+    ///
+    /// # Arguments
+    /// * `bucket` - Histogram bucket index.
+    pub fn bucket_limit(bucket: usize) -> Option<u64> {
+        (bucket < HISTOGRAM_BUCKETS - 1).then(|| 1 << (FIRST_BUCKET_SHIFT + bucket as u32))
+    }
+
+    /// Sets all the counters and histograms to zero.
+    pub fn reset(&self) {
+        for vector in 0..VECTORS {
+            self.counters[vector].store(0, Ordering::Relaxed);
+            self.max_cycles[vector].store(0, Ordering::Relaxed);
+            for bucket in &self.histograms[vector] {
+                bucket.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Returns a displayable view of the latency histograms of the vectors that have at least one
+    /// measurement.
+    pub fn latency(&self) -> LatencyHistograms<'_> {
+        LatencyHistograms(self)
+    }
+
+    /// Returns the histogram bucket where a handler that took `cycles` cycles falls in.
+    fn bucket(cycles: u64) -> usize {
+        // Number of bits needed to represent the cycles, minus the bits covered by the first
+        // bucket.
+        let bits = (u64::BITS - cycles.leading_zeros()).saturating_sub(FIRST_BUCKET_SHIFT);
+        (bits as usize).min(HISTOGRAM_BUCKETS - 1)
+    }
+}
+
+impl Default for InterruptStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns a name for the given vector.
+fn vector_name(vector: u8) -> &'static str {
+    exception_name(vector)
+        .or_else(|| irq_line_name(vector))
+        .or_else(|| local_apic_vector_name(vector))
+        .unwrap_or("")
+}
+
+/// Prints a `/proc/interrupts` like table with the vectors that were handled at least once.
+impl Display for InterruptStatistics {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        writeln!(f, "Vector  {:<28} {:>12}", "Name", "Count")?;
+        for vector in 0..=u8::MAX {
+            let count = self.count(vector);
+            if count > 0 {
+                writeln!(
+                    f,
+                    "0x{vector:02x}    {:<28} {count:>12}",
+                    vector_name(vector)
+                )?;
+            }
+        }
+
+        writeln!(f, "{:<36} {:>12}", "Total", self.total())
+    }
+}
+
+/// Displayable view of the latency histograms. It is created with
+/// [InterruptStatistics::latency].
+pub struct LatencyHistograms<'a>(&'a InterruptStatistics);
+
+/// Prints one line per measured vector with the non empty buckets of its histogram in the format
+/// `<limit in cycles>:<count>`, followed by the longest measured duration.
+impl<'a> Display for LatencyHistograms<'a> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        for vector in 0..=u8::MAX {
+            let histogram = self.0.histogram(vector);
+            if histogram.iter().all(|count| *count == 0) {
+                continue;
+            }
+
+            write!(f, "0x{vector:02x} {}:", vector_name(vector))?;
+            for (bucket, count) in histogram.iter().enumerate().filter(|(_, c)| **c > 0) {
+                match InterruptStatistics::bucket_limit(bucket) {
+                    Some(limit) => write!(f, " <{limit}:{count}")?,
+                    None => write!(f, " inf:{count}")?,
+                }
+            }
+            writeln!(f, " (max {} cycles)", self.0.max_cycles(vector))?;
+        }
+
+        Ok(())
+    }
+}
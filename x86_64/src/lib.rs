@@ -8,9 +8,13 @@
 // Enable x86 interrupt ABI
 #![feature(abi_x86_interrupt)]
 
+pub mod acpi;
+pub mod cpuid;
+pub mod fpu;
 pub mod gdt;
 pub mod idt;
 pub mod interrupts;
 pub mod memory;
 pub mod privilege;
 pub mod registers;
+pub mod timers;
@@ -15,6 +15,25 @@ pub struct PhysicalFrame<PS: PageSize> {
     size: PhantomData<PS>,
 }
 
+impl<PS: PageSize> PhysicalFrame<PS> {
+    /// Creates a new physical frame.
+    ///
+    /// # Arguments
+    /// * `frame_address` - Physical address where the frame starts. It must be aligned to the
+    ///   page size.
+    pub const fn new(frame_address: PhysicalMemoryAddress) -> Self {
+        Self {
+            frame_address,
+            size: PhantomData,
+        }
+    }
+
+    /// Returns the physical address where the frame starts.
+    pub fn start_address(&self) -> PhysicalMemoryAddress {
+        self.frame_address
+    }
+}
+
 pub trait FrameAllocator<PS: PageSize> {
     /// Allocates a frame
     /// This method is unsafe because the implementer must guarantee that the allocator yields only
@@ -1,14 +1,16 @@
 use core::marker::PhantomData;
 
 use super::{
-    address::VirtualMemoryAddress,
+    address::{PhysicalMemoryAddress, VirtualMemoryAddress},
     frame_allocator::FrameAllocator,
     paging::{
         frame::Frame,
         page::Page,
         page_size::{Size1GiB, Size2MiB, Size4KiB},
         page_table::{PageTable, PageTableEntry, PageTableEntryFlags, PageTableLevel},
+        paging_error::PagingError,
     },
+    tlb,
 };
 use crate::{memory::paging::page_size::PageSize, registers::control::Cr3};
 
@@ -98,6 +100,47 @@ macro_rules! impl_mapper_for_size {
                 );
                 next_page_table[last_level_page_table_index].is_used()
             }
+
+            /// Unmaps a virtual page and flushes its TLB entry. Returns the frame the page was
+            /// mapped to.
+            ///
+            /// The intermediate page tables are not freed, even if they end up empty.
+            ///
+            /// # Safety
+            /// This function is unsafe because the caller must asure that nothing is going to
+            /// access the page after unmapping it.
+            pub unsafe fn unmap(&self, page: Page<$size>) -> Result<Frame<$size>, PagingError> {
+                let mut page_table: &mut PageTable =
+                    &mut *(self.physical_memory_offset + Cr3::read()).as_mut_ptr();
+
+                // Transverse tables until the last level
+                for page_table_level in &Self::PAGE_TABLE_LEVELS[..$pt_levels_qty - 1] {
+                    let page_table_entry =
+                        &page_table[page.get_page_table_index(*page_table_level)];
+                    if !page_table_entry.is_present() {
+                        return Err(PagingError::PageNotMapped);
+                    }
+                    if page_table_entry.is_huge() {
+                        return Err(PagingError::HugePage);
+                    }
+
+                    page_table = &mut *(self.physical_memory_offset + page_table_entry.address())
+                        .as_mut_ptr();
+                }
+
+                let last_level_page_table_index =
+                    page.get_page_table_index(Self::PAGE_TABLE_LEVELS[$pt_levels_qty - 1]);
+                let page_table_entry = &mut page_table[last_level_page_table_index];
+                if !page_table_entry.is_present() {
+                    return Err(PagingError::PageNotMapped);
+                }
+
+                let frame = Frame::<$size>::containing_address(page_table_entry.address());
+                *page_table_entry = PageTableEntry::new(0, PhysicalMemoryAddress::new(0));
+                tlb::flush(page.start_address());
+
+                Ok(frame)
+            }
         }
     };
 }
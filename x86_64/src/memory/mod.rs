@@ -2,6 +2,7 @@ pub mod address;
 pub mod frame_allocator;
 pub mod mapper;
 pub mod paging;
+pub mod tlb;
 mod translator;
 
 pub use translator::Translator;
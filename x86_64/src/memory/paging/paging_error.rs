@@ -1,5 +1,12 @@
 /// Type that compress all Paging errors
+#[derive(Debug, PartialEq, Eq)]
 pub enum PagingError {
     /// Happens when we try to crate a new page with an address that is not aligned.
     InvalidAlign,
+
+    /// Happens when we try to unmap a page that is not mapped.
+    PageNotMapped,
+
+    /// Happens when a huge page is found while looking for a page of a smaller size.
+    HugePage,
 }
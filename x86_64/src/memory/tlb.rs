@@ -0,0 +1,79 @@
+//! TLB (Translation Lookaside Buffer) management
+//!
+//! The TLB caches the translations of virtual addresses. When a page table entry is changed, the
+//! cached translation is not updated automatically, it must be invalidated so the CPU walks the
+//! page tables again the next time the page is accessed.
+//!
+//! Every processor has its own TLB, and `invlpg` only invalidates the entries of the one that runs
+//! it. When there are several processors, the others must be asked to invalidate the page too (a
+//! TLB shootdown). This crate does not know how the processors are started or notified, so the
+//! kernel registers the function that does it with [set_shootdown_handler].
+//!
+//! For more information:
+//! https://wiki.osdev.org/TLB
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::memory::address::VirtualMemoryAddress;
+
+/// Function that invalidates a page in the TLB of the other processors, zero if there is none.
+static SHOOTDOWN_HANDLER: AtomicUsize = AtomicUsize::new(0);
+
+/// Sets the function that invalidates a page in the TLB of the other processors. It is called by
+/// [flush] after invalidating the page in the current processor.
+///
+/// # Arguments
+/// * `handler` - Function that receives the address whose translation must be invalidated.
+///
+/// # Safety
+/// The handler must not flush the TLB with [flush] (it would call itself), and it must be
+/// callable from any context where a page is unmapped.
+pub unsafe fn set_shootdown_handler(handler: fn(VirtualMemoryAddress)) {
+    SHOOTDOWN_HANDLER.store(handler as usize, Ordering::Release);
+}
+
+/// Invalidates the TLB entry of the page containing the given address, in every processor.
+///
+/// # Arguments
+/// * `address` - Virtual address whose translation must be invalidated.
+#[inline]
+pub fn flush(address: VirtualMemoryAddress) {
+    flush_local(address);
+
+    let handler = SHOOTDOWN_HANDLER.load(Ordering::Acquire);
+    if handler != 0 {
+        // Safety: the only non zero values stored are `fn(VirtualMemoryAddress)` pointers
+        let handler: fn(VirtualMemoryAddress) = unsafe { core::mem::transmute(handler) };
+        handler(address);
+    }
+}
+
+/// Invalidates the TLB entry of the page containing the given address, only in the current
+/// processor.
+///
+/// # Arguments
+/// * `address` - Virtual address whose translation must be invalidated.
+#[inline]
+pub fn flush_local(address: VirtualMemoryAddress) {
+    unsafe {
+        asm!(
+            "invlpg [{}]",
+            in(reg) address.as_u64(),
+            options(nostack, preserves_flags)
+        )
+    }
+}
+
+/// Invalidates all the TLB entries of the current processor, except the ones of global pages, by
+/// reloading the CR3 register.
+#[inline]
+pub fn flush_all() {
+    unsafe {
+        asm!(
+            "mov {0}, cr3",
+            "mov cr3, {0}",
+            out(reg) _,
+            options(nostack, preserves_flags)
+        )
+    }
+}
@@ -26,7 +26,7 @@
 //! https://os.phil-opp.com/paging-implementation/#identity-mapping
 use crate::{
     memory::address::{PhysicalMemoryAddress, VirtualMemoryAddress},
-    memory::paging::page_table::{PageTable, PageTableLevel},
+    memory::paging::page_table::{PageTable, PageTableEntryFlags, PageTableLevel},
     registers::control::Cr3,
 };
 
@@ -117,4 +117,47 @@ impl Translator {
             next_page_table_physical_address.0 + address.get_page_offset() as u64,
         ))
     }
+
+    /// Returns the effective flags of the page that contains a virtual address, or `None` if it is
+    /// not mapped.
+    ///
+    /// The flags are the ones of the entry that maps the page (level 1 entry, or the huge page
+    /// entry), but `WRITABLE` and `USER_ACCESSIBLE` are only kept if every level of the walk
+    /// allows them, since that is how the CPU checks the access rights.
+    ///
+    /// # Safety
+    /// Caller must be sure that the page tables are mapped at the physical memory offset.
+    pub unsafe fn page_flags(&self, address: VirtualMemoryAddress) -> Option<u64> {
+        let tables_indexes = [
+            address.get_page_table_index(PageTableLevel::Level4),
+            address.get_page_table_index(PageTableLevel::Level3),
+            address.get_page_table_index(PageTableLevel::Level2),
+            address.get_page_table_index(PageTableLevel::Level1),
+        ];
+        let access_flags = PageTableEntryFlags::WRITABLE | PageTableEntryFlags::USER_ACCESSIBLE;
+
+        let mut allowed_access = access_flags;
+        let mut next_page_table_physical_address = Cr3::read();
+        for (transversed_level, table_index) in tables_indexes.iter().enumerate() {
+            let next_page_table_virtual_address =
+                self.physical_memory_offset + next_page_table_physical_address;
+            let next_table: &PageTable = &*next_page_table_virtual_address.as_mut_ptr();
+            let entry = &next_table[*table_index];
+
+            if !entry.is_present() {
+                return None;
+            }
+
+            allowed_access &= entry.get_flags();
+            // Huge pages are mapped by a level 3 or level 2 entry, there are no more levels
+            let is_last_level = transversed_level == tables_indexes.len() - 1;
+            if is_last_level || entry.is_huge() {
+                return Some((entry.get_flags() & !access_flags) | allowed_access);
+            }
+
+            next_page_table_physical_address = entry.address();
+        }
+
+        None
+    }
 }
@@ -1,6 +1,25 @@
+/// Privilege levels (rings) of the processor. Ring 0 is the most privileged one (the kernel) and
+/// ring 3 the least privileged one (user programs).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PrivilegeLevel {
     Ring0 = 0,
     Ring1 = 1,
     Ring2 = 2,
     Ring3 = 3,
 }
+
+impl PrivilegeLevel {
+    /// Returns the privilege level encoded in the two lowest bits of `bits`.
+    ///
+    /// # Arguments
+    /// * `bits` - Value with the privilege level in its two lowest bits (for example, the RPL of
+    ///   a segment selector).
+    pub const fn from_bits(bits: u64) -> Self {
+        match bits & 0b11 {
+            0 => Self::Ring0,
+            1 => Self::Ring1,
+            2 => Self::Ring2,
+            _ => Self::Ring3,
+        }
+    }
+}
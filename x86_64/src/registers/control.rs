@@ -41,4 +41,167 @@ impl Cr3 {
 
         PhysicalMemoryAddress::new(value)
     }
+
+    /// Loads a new PML4, switching to its address space. The whole TLB is flushed (except for the
+    /// global pages).
+    ///
+    /// # Arguments
+    /// * `address` - Physical address of the PML4. It must be 4KiB aligned.
+    ///
+    /// # Safety
+    /// The caller must be sure that the new address space maps the code being executed, the
+    /// current stack and every kernel structure that is accessed afterwards.
+    #[inline]
+    pub unsafe fn write(address: PhysicalMemoryAddress) {
+        asm!("mov cr3, {}", in(reg) address.as_u64(), options(nostack, preserves_flags))
+    }
+}
+
+/// The CR0 register controls the operating mode of the processor: protected mode (bit 0),
+/// paging (bit 31), write protection of read only pages in ring 0 (bit 16) and the FPU behaviour,
+/// among others.
+///
+/// For more info:
+/// https://wiki.osdev.org/CPU_Registers_x86-64#CR0
+pub struct Cr0;
+
+impl Cr0 {
+    /// Monitor coprocessor: together with TS, makes WAIT/FWAIT raise #NM too.
+    pub const MONITOR_COPROCESSOR: u64 = 1 << 1;
+
+    /// x87 emulation: every x87 and SSE instruction raises #NM (or #UD). Must be clear to use them.
+    pub const EMULATION: u64 = 1 << 2;
+
+    /// Task switched: the next x87, SSE or AVX instruction raises #NM. Used to save and restore
+    /// their registers lazily.
+    pub const TASK_SWITCHED: u64 = 1 << 3;
+
+    /// Numeric error: x87 errors raise #MF instead of the legacy IRQ 13.
+    pub const NUMERIC_ERROR: u64 = 1 << 5;
+
+    /// Reads the value of the register.
+    #[inline]
+    pub fn read() -> u64 {
+        let value: u64;
+        unsafe { asm!("mov {}, cr0", out(reg) value, options(nomem, nostack, preserves_flags)) }
+        value
+    }
+
+    /// Writes the value of the register.
+    ///
+    /// # Arguments
+    /// * `value` - New value.
+    ///
+    /// # Safety
+    /// The caller must be sure that the new value keeps the processor in a state where the
+    /// running code is still valid (for example, paging can not be disabled in long mode).
+    #[inline]
+    pub unsafe fn write(value: u64) {
+        asm!("mov cr0, {}", in(reg) value, options(nostack, preserves_flags))
+    }
+
+    /// Clears the task switched flag (TS), so the x87, SSE and AVX instructions can be used.
+    ///
+    /// # Safety
+    /// The registers of those units must belong to the code that runs next.
+    #[inline]
+    pub unsafe fn clear_task_switched() {
+        asm!("clts", options(nomem, nostack, preserves_flags))
+    }
+}
+
+/// The CR4 register enables extensions of the processor: PAE (bit 5, required by long mode),
+/// global pages (bit 7), SSE support (bits 9 and 10) and PCIDs (bit 17), among others.
+///
+/// For more info:
+/// https://wiki.osdev.org/CPU_Registers_x86-64#CR4
+pub struct Cr4;
+
+impl Cr4 {
+    /// The OS saves the SSE registers with FXSAVE/FXRSTOR, SSE instructions can be used.
+    pub const OSFXSR: u64 = 1 << 9;
+
+    /// The OS handles the SIMD floating point exceptions (#XM), otherwise they raise #UD.
+    pub const OSXMMEXCPT: u64 = 1 << 10;
+
+    /// Process Context Identifiers enable bit. It can only be set in long mode.
+    pub const PCID_ENABLE: u64 = 1 << 17;
+
+    /// XSAVE and the extended control registers (XCR0) are enabled.
+    pub const OSXSAVE: u64 = 1 << 18;
+
+    /// Reads the value of the register.
+    #[inline]
+    pub fn read() -> u64 {
+        let value: u64;
+        unsafe { asm!("mov {}, cr4", out(reg) value, options(nomem, nostack, preserves_flags)) }
+        value
+    }
+
+    /// Writes the value of the register.
+    ///
+    /// # Arguments
+    /// * `value` - New value.
+    ///
+    /// # Safety
+    /// The caller must be sure that the processor supports the enabled extensions and that they
+    /// do not break the running code (for example, PAE can not be disabled in long mode).
+    #[inline]
+    pub unsafe fn write(value: u64) {
+        asm!("mov cr4, {}", in(reg) value, options(nostack, preserves_flags))
+    }
+}
+
+/// The XCR0 extended control register selects the state components (x87, SSE, AVX...) that the
+/// processor lets use and that XSAVE/XRSTOR save and restore. It is only accessible if
+/// CR4.OSXSAVE is set.
+///
+/// For more info:
+/// https://wiki.osdev.org/CPU_Registers_x86-64#XCR0
+pub struct Xcr0;
+
+impl Xcr0 {
+    /// x87 state, it must always be set.
+    pub const X87: u64 = 1 << 0;
+
+    /// SSE state (XMM registers and MXCSR).
+    pub const SSE: u64 = 1 << 1;
+
+    /// AVX state (upper halves of the YMM registers). Requires SSE.
+    pub const AVX: u64 = 1 << 2;
+
+    /// Reads the value of the register.
+    #[inline]
+    pub fn read() -> u64 {
+        let (low, high): (u32, u32);
+        unsafe {
+            asm!(
+                "xgetbv",
+                in("ecx") 0,
+                out("eax") low,
+                out("edx") high,
+                options(nomem, nostack, preserves_flags)
+            )
+        }
+        u64::from(high) << 32 | u64::from(low)
+    }
+
+    /// Writes the value of the register.
+    ///
+    /// # Arguments
+    /// * `value` - New value.
+    ///
+    /// # Safety
+    /// The caller must be sure that the processor supports the enabled components and that the
+    /// XSAVE areas used afterwards are big enough for them.
+    #[inline]
+    pub unsafe fn write(value: u64) {
+        asm!(
+            "xsetbv",
+            in("ecx") 0,
+            in("eax") value as u32,
+            in("edx") (value >> 32) as u32,
+            options(nomem, nostack, preserves_flags)
+        )
+    }
 }
@@ -1,3 +1,5 @@
 //! This module contains abstractions to work with CPU registers
 pub mod control;
+pub mod msr;
 pub mod segments;
+pub mod tsc;
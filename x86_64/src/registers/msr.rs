@@ -0,0 +1,74 @@
+//! Model Specific Registers
+use core::arch::asm;
+
+/// A Model Specific Register (MSR). MSRs are read and written with the `rdmsr` and `wrmsr`
+/// instructions, selecting the register with its number.
+///
+/// For more info:
+/// https://wiki.osdev.org/Model_Specific_Registers
+#[derive(Clone, Copy, Debug)]
+pub struct Msr(u32);
+
+impl Msr {
+    /// Local APIC base address and enable flag.
+    pub const IA32_APIC_BASE: Msr = Msr(0x1b);
+
+    /// Extended Feature Enable Register. Bit 0 (SCE) enables the `syscall` and `sysret`
+    /// instructions.
+    pub const IA32_EFER: Msr = Msr(0xc000_0080);
+
+    /// Segment selectors loaded by `syscall` (bits 32..48) and `sysret` (bits 48..64).
+    pub const IA32_STAR: Msr = Msr(0xc000_0081);
+
+    /// Address of the `syscall` entry point in 64 bit mode.
+    pub const IA32_LSTAR: Msr = Msr(0xc000_0082);
+
+    /// RFLAGS mask (also known as SFMASK): the bits set here are cleared from RFLAGS by
+    /// `syscall`.
+    pub const IA32_FMASK: Msr = Msr(0xc000_0084);
+
+    /// Base address of the GS segment. Used to reach the data of the current processor.
+    pub const IA32_GS_BASE: Msr = Msr(0xc000_0101);
+
+    /// Value exchanged with `IA32_GS_BASE` by the `swapgs` instruction.
+    pub const IA32_KERNEL_GS_BASE: Msr = Msr(0xc000_0102);
+
+    /// Creates a new MSR.
+    ///
+    /// # Arguments
+    /// * `register` - MSR number.
+    pub const fn new(register: u32) -> Self {
+        Self(register)
+    }
+
+    /// Reads the value of the MSR.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because reading an MSR that does not exist causes a general protection
+    /// fault, and some of them have side effects.
+    #[inline]
+    pub unsafe fn read(&self) -> u64 {
+        let low: u32;
+        let high: u32;
+        asm!("rdmsr", in("ecx") self.0, out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags));
+
+        (u64::from(high) << 32) | u64::from(low)
+    }
+
+    /// Writes a value to the MSR.
+    ///
+    /// # Arguments
+    /// * `value` - Value to write.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because writing an MSR that does not exist causes a general protection
+    /// fault, and they control low level processor features that can violate memory safety.
+    #[inline]
+    pub unsafe fn write(&mut self, value: u64) {
+        let low = value as u32;
+        let high = (value >> 32) as u32;
+        asm!("wrmsr", in("ecx") self.0, in("eax") low, in("edx") high, options(nostack, preserves_flags));
+    }
+}
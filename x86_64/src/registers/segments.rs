@@ -7,7 +7,7 @@ use crate::privilege::PrivilegeLevel;
 
 /// An element from a GDT/LDT table to load into a segment
 #[repr(transparent)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct SegmentSelector(u16);
 
 impl SegmentSelector {
@@ -19,6 +19,11 @@ impl SegmentSelector {
     pub fn new(index: u16, rpl: PrivilegeLevel) -> Self {
         Self(index << 3 | rpl as u16)
     }
+
+    /// Returns the requested privilege level of the selector.
+    pub fn rpl(&self) -> PrivilegeLevel {
+        PrivilegeLevel::from_bits(u64::from(self.0))
+    }
 }
 
 impl Deref for SegmentSelector {
@@ -67,3 +72,48 @@ impl CS {
         SegmentSelector(segment)
     }
 }
+
+/// Implements a data segment register, that can be directly set with a `mov` instruction.
+macro_rules! data_segment_register {
+    ($name: ident, $register: literal) => {
+        pub struct $name;
+
+        impl $name {
+            #[doc = concat!("Sets the ", $register, " register with the given segment selector.")]
+            ///
+            /// # Arguments
+            /// * `segment_selector` - A segment selector (GDT index and requested privilege
+            ///   level).
+            ///
+            /// # Safety
+            /// This functions is unsafe because the caller be sure that the selector points to a
+            /// valid data segment (or is NULL, where allowed) when setting the register.
+            pub unsafe fn set_register(segment_selector: u16) {
+                unsafe {
+                    asm!(
+                        concat!("mov ", $register, ", {0:x}"),
+                        in(reg) segment_selector,
+                        options(nostack, preserves_flags)
+                    );
+                }
+            }
+
+            #[doc = concat!("Gets the value of the ", $register, " register")]
+            pub fn get_register() -> SegmentSelector {
+                let mut segment: u16;
+                unsafe {
+                    asm!(
+                        concat!("mov {0:x}, ", $register),
+                        out(reg) segment,
+                        options(nomem, nostack, preserves_flags)
+                    );
+                }
+                SegmentSelector(segment)
+            }
+        }
+    };
+}
+
+data_segment_register!(SS, "ss");
+data_segment_register!(DS, "ds");
+data_segment_register!(ES, "es");
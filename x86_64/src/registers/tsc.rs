@@ -0,0 +1,28 @@
+//! Time Stamp Counter
+use core::arch::asm;
+
+/// The Time Stamp Counter (TSC) is a 64 bit register present in all x86 processors since the
+/// Pentium. It counts the number of cycles since reset.
+///
+/// On older processors the counter increments with every internal processor clock cycle, so its
+/// rate changes with the CPU frequency. Newer processors have an invariant TSC that runs at a
+/// constant rate in all ACPI P-, C- and T-states.
+///
+/// For more info:
+/// https://wiki.osdev.org/TSC
+/// https://en.wikipedia.org/wiki/Time_Stamp_Counter
+pub struct Tsc;
+
+impl Tsc {
+    /// Reads the current value of the TSC.
+    #[inline]
+    pub fn read() -> u64 {
+        let low: u32;
+        let high: u32;
+        unsafe {
+            asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack, preserves_flags))
+        }
+
+        (u64::from(high) << 32) | u64::from(low)
+    }
+}
@@ -0,0 +1,240 @@
+//! Implementation for the CMOS RTC (Real Time Clock)
+//!
+//! The CMOS is a tiny battery powered memory that keeps the BIOS settings and a clock that keeps
+//! running while the computer is off. It is accessed through two I/O ports: 0x70 selects the
+//! register and 0x71 reads or writes it.
+//!
+//! The clock registers are:
+//!
+//!  Register | Contents
+//!  0x00     | Seconds (0 - 59)
+//!  0x02     | Minutes (0 - 59)
+//!  0x04     | Hours (0 - 23 in 24-hour mode, 1 - 12 in 12-hour mode, highest bit set if PM)
+//!  0x07     | Day of month (1 - 31)
+//!  0x08     | Month (1 - 12)
+//!  0x09     | Year (0 - 99)
+//!  0x0a     | Status Register A (bit 7 is set while the RTC is updating the registers)
+//!  0x0b     | Status Register B (format and interrupts configuration)
+//!  0x0c     | Status Register C (which interrupt happened, reading it acknowledges it)
+//!
+//! The values can be stored in binary or in BCD (Binary Coded Decimal) and the hours in 12 or
+//! 24-hour format, depending on the Status Register B. There is no standard century register, the
+//! ACPI FADT tells us if there is one and where.
+//!
+//! The RTC is also able to generate interrupts (IRQ 8) once per second after every update, at a
+//! periodic rate or when an alarm time is reached.
+//!
+//! This code and comments are havily based on:
+//! - https://wiki.osdev.org/CMOS
+//! - https://wiki.osdev.org/RTC
+use x86_64::instructions::port::Port;
+
+// I/O ports
+const REGISTER_SELECT: u16 = 0x70;
+const REGISTER_DATA: u16 = 0x71;
+
+// Registers
+const SECONDS: u8 = 0x00;
+const MINUTES: u8 = 0x02;
+const HOURS: u8 = 0x04;
+const DAY_OF_MONTH: u8 = 0x07;
+const MONTH: u8 = 0x08;
+const YEAR: u8 = 0x09;
+const STATUS_A: u8 = 0x0a;
+const STATUS_B: u8 = 0x0b;
+const STATUS_C: u8 = 0x0c;
+
+// Status Register A bits
+const UPDATE_IN_PROGRESS: u8 = 1 << 7;
+
+// Status Register B bits
+const HOUR_FORMAT_24: u8 = 1 << 1;
+const BINARY_MODE: u8 = 1 << 2;
+const UPDATE_ENDED_INTERRUPT: u8 = 1 << 4;
+
+/// Set in the hours register when the time is PM in 12-hour mode.
+const HOUR_PM: u8 = 1 << 7;
+
+/// Year used when there is no century register. The RTC year has only two digits, we asume we
+/// are in the 21st century.
+const DEFAULT_CENTURY: u16 = 20;
+
+/// Date and time as read from the RTC, already converted to binary and 24-hour format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RtcTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+/// Raw register values, used to detect if an update happened in the middle of a read.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct RawRegisters {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    century: u8,
+}
+
+/// The CMOS Real Time Clock.
+pub struct CmosRtc {
+    /// The I/O port used to select a register.
+    select: Port<u8>,
+
+    /// The I/O port used to read and write the selected register.
+    data: Port<u8>,
+}
+
+impl CmosRtc {
+    /// Creates a new instance of the RTC.
+    pub const fn new() -> Self {
+        Self {
+            select: Port::new(REGISTER_SELECT),
+            data: Port::new(REGISTER_DATA),
+        }
+    }
+
+    /// Reads the current date and time.
+    ///
+    /// The RTC updates its registers once per second, if we read them while it is updating we can
+    /// get inconsistent values (for example 10:59:59 -> 10:00:00 -> 11:00:00). To avoid that, we
+    /// wait until no update is in progress and read all the registers until we get the same values
+    /// twice in a row.
+    ///
+    /// # Arguments
+    /// * `century_register` - CMOS register that holds the century, if there is one (the ACPI
+    ///   FADT `century` field).
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because:
+    /// - Programmer must be sure that the I/O ports are valid.
+    /// - The CMOS must not be accessed concurrently, the register selection and the read are two
+    ///   different steps.
+    pub unsafe fn read(&mut self, century_register: Option<u8>) -> RtcTime {
+        let mut registers = self.read_registers(century_register);
+        loop {
+            let last = registers;
+            registers = self.read_registers(century_register);
+            if registers == last {
+                break;
+            }
+        }
+
+        let status_b = self.read_register(STATUS_B);
+        Self::decode(registers, status_b, century_register.is_some())
+    }
+
+    /// Enables the update-ended interrupt (IRQ 8 fires once per second).
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because:
+    /// - Programmer must be sure that the I/O ports are valid.
+    /// - The CMOS must not be accessed concurrently.
+    /// - There must be a handler for the IRQ 8 that calls
+    ///   [acknowledge_interrupt](Self::acknowledge_interrupt), otherwise the RTC will not fire it
+    ///   again.
+    pub unsafe fn enable_update_interrupt(&mut self) {
+        let status_b = self.read_register(STATUS_B);
+        self.write_register(STATUS_B, status_b | UPDATE_ENDED_INTERRUPT);
+        self.acknowledge_interrupt();
+    }
+
+    /// Acknowledges an RTC interrupt. Returns the Status Register C, that tells which interrupt
+    /// happened.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because:
+    /// - Programmer must be sure that the I/O ports are valid.
+    /// - The CMOS must not be accessed concurrently.
+    pub unsafe fn acknowledge_interrupt(&mut self) -> u8 {
+        self.read_register(STATUS_C)
+    }
+
+    /// Converts the raw registers into binary, 24-hour values.
+    fn decode(registers: RawRegisters, status_b: u8, has_century: bool) -> RtcTime {
+        let binary_mode = status_b & BINARY_MODE != 0;
+        let to_binary = |value: u8| {
+            if binary_mode {
+                value
+            } else {
+                bcd_to_binary(value)
+            }
+        };
+
+        // In 12-hour mode the PM flag is the highest bit of the hour, it must be removed before
+        // the BCD conversion
+        let pm = registers.hour & HOUR_PM != 0;
+        let mut hour = to_binary(registers.hour & !HOUR_PM);
+        if status_b & HOUR_FORMAT_24 == 0 {
+            // 12 AM is midnight (0) and 12 PM is noon (12)
+            hour %= 12;
+            if pm {
+                hour += 12;
+            }
+        }
+
+        let century = if has_century {
+            u16::from(to_binary(registers.century))
+        } else {
+            DEFAULT_CENTURY
+        };
+
+        RtcTime {
+            year: century * 100 + u16::from(to_binary(registers.year)),
+            month: to_binary(registers.month),
+            day: to_binary(registers.day),
+            hour,
+            minute: to_binary(registers.minute),
+            second: to_binary(registers.second),
+        }
+    }
+
+    /// Waits until there is no update in progress and reads all the time registers.
+    unsafe fn read_registers(&mut self, century_register: Option<u8>) -> RawRegisters {
+        while self.read_register(STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+            core::hint::spin_loop();
+        }
+
+        RawRegisters {
+            second: self.read_register(SECONDS),
+            minute: self.read_register(MINUTES),
+            hour: self.read_register(HOURS),
+            day: self.read_register(DAY_OF_MONTH),
+            month: self.read_register(MONTH),
+            year: self.read_register(YEAR),
+            century: century_register.map_or(0, |register| self.read_register(register)),
+        }
+    }
+
+    /// Reads a CMOS register.
+    unsafe fn read_register(&mut self, register: u8) -> u8 {
+        self.select.write(register);
+        self.data.read()
+    }
+
+    /// Writes a CMOS register.
+    unsafe fn write_register(&mut self, register: u8, value: u8) {
+        self.select.write(register);
+        self.data.write(value);
+    }
+}
+
+impl Default for CmosRtc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Converts a Binary Coded Decimal value (each nibble is a decimal digit) to binary.
+fn bcd_to_binary(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0f)
+}
@@ -0,0 +1,130 @@
+//! Implementation for the HPET (High Precision Event Timer)
+//!
+//! The HPET has a main counter that is incremented at a constant frequency (at least 10 MHz) and a
+//! set of comparators that can generate interrupts when the counter reaches a value. Unlike the
+//! PIT, it is configured through memory mapped registers. The base address of the registers is
+//! given by the ACPI HPET table.
+//!
+//! The registers used here are:
+//!
+//!  Offset | Register
+//!  0x000  | General Capabilities and ID (bits 63 - 32 are the counter period in femtoseconds)
+//!  0x010  | General Configuration (bit 0 enables the main counter)
+//!  0x0f0  | Main Counter Value
+//!
+//! This code and comments are havily based on:
+//! - https://wiki.osdev.org/HPET
+//! - IA-PC HPET (High Precision Event Timers) Specification 1.0a
+use core::ptr::{read_volatile, write_volatile};
+
+use crate::memory::address::VirtualMemoryAddress;
+
+/// Number of femtoseconds in a second.
+const FEMTOSECONDS_PER_SECOND: u64 = 1_000_000_000_000_000;
+
+// Registers offsets
+const GENERAL_CAPABILITIES: u64 = 0x000;
+const GENERAL_CONFIGURATION: u64 = 0x010;
+const MAIN_COUNTER: u64 = 0x0f0;
+
+// General Capabilities and ID register bits
+const COUNTER_SIZE_64_BITS: u64 = 1 << 13;
+const NUMBER_OF_TIMERS_SHIFT: u64 = 8;
+const NUMBER_OF_TIMERS_MASK: u64 = 0x1f;
+const COUNTER_PERIOD_SHIFT: u64 = 32;
+
+// General Configuration register bits
+const ENABLE_COUNTER: u64 = 1 << 0;
+
+/// The HPET registers block.
+#[derive(Clone, Copy, Debug)]
+pub struct Hpet {
+    /// Virtual address where the registers are mapped.
+    base: VirtualMemoryAddress,
+}
+
+impl Hpet {
+    /// Creates a new instance of the HPET.
+    ///
+    /// # Arguments
+    /// * `base` - Virtual address where the HPET registers are mapped.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the HPET registers are mapped at
+    /// `base` (and that the mapping is not cached).
+    pub const unsafe fn new(base: VirtualMemoryAddress) -> Self {
+        Self { base }
+    }
+
+    /// Returns the virtual address where the HPET registers are mapped.
+    pub fn base(&self) -> VirtualMemoryAddress {
+        self.base
+    }
+
+    /// Returns the period of the main counter in femtoseconds (10^-15 seconds).
+    pub fn period_femtoseconds(&self) -> u64 {
+        self.read(GENERAL_CAPABILITIES) >> COUNTER_PERIOD_SHIFT
+    }
+
+    /// Returns the frequency of the main counter in Hz.
+    pub fn frequency(&self) -> u64 {
+        FEMTOSECONDS_PER_SECOND / self.period_femtoseconds().max(1)
+    }
+
+    /// Returns true if the main counter is 64 bits wide. Otherwise it is 32 bits wide and wraps
+    /// around every few minutes.
+    pub fn is_64_bits(&self) -> bool {
+        self.read(GENERAL_CAPABILITIES) & COUNTER_SIZE_64_BITS != 0
+    }
+
+    /// Returns the number of comparators (timers).
+    pub fn number_of_timers(&self) -> u8 {
+        ((self.read(GENERAL_CAPABILITIES) >> NUMBER_OF_TIMERS_SHIFT) & NUMBER_OF_TIMERS_MASK) as u8
+            + 1
+    }
+
+    /// Returns the current value of the main counter.
+    #[inline]
+    pub fn main_counter(&self) -> u64 {
+        self.read(MAIN_COUNTER)
+    }
+
+    /// Starts the main counter.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that no code depends on the counter
+    /// being stopped (the comparators start firing if they are configured).
+    pub unsafe fn enable(&mut self) {
+        let configuration = self.read(GENERAL_CONFIGURATION);
+        self.write(GENERAL_CONFIGURATION, configuration | ENABLE_COUNTER);
+    }
+
+    /// Stops the main counter.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that no code depends on the counter
+    /// running.
+    pub unsafe fn disable(&mut self) {
+        let configuration = self.read(GENERAL_CONFIGURATION);
+        self.write(GENERAL_CONFIGURATION, configuration & !ENABLE_COUNTER);
+    }
+
+    /// Reads a register.
+    fn read(&self, offset: u64) -> u64 {
+        // Safety: the constructor contract guarantees the registers are mapped
+        unsafe {
+            read_volatile(VirtualMemoryAddress::new(self.base.as_u64() + offset).as_mut_ptr())
+        }
+    }
+
+    /// Writes a register.
+    unsafe fn write(&mut self, offset: u64, value: u64) {
+        write_volatile(
+            VirtualMemoryAddress::new(self.base.as_u64() + offset).as_mut_ptr(),
+            value,
+        )
+    }
+}
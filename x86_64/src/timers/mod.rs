@@ -0,0 +1,8 @@
+//! Hardware timers
+pub mod cmos_rtc;
+pub mod hpet;
+pub mod pit8254;
+
+pub use cmos_rtc::{CmosRtc, RtcTime};
+pub use hpet::Hpet;
+pub use pit8254::{Pit8254, PitMode, PIT_BASE_FREQUENCY};
@@ -0,0 +1,204 @@
+//! Implementation for the PIT (Programmable Interval Timer) 8253/8254
+//!
+//! The PIT chip has an oscillator that runs at (roughly) 1.193182 MHz and three independent
+//! frequency dividers (channels). Each channel has a 16 bit counter that is decremented on every
+//! oscillator tick, when it reaches zero the channel output changes.
+//!
+//!                 ____________
+//!  1.193182 MHz  |            |--> Channel 0 ---> IRQ 0 (Timer)
+//!  Oscillator -->|  PIT 8254  |--> Channel 1 ---> (DRAM refresh, unusable)
+//!                |____________|--> Channel 2 ---> PC Speaker
+//!
+//! Channel 0 output is connected to the IRQ 0 line of the primary PIC, so programming its divider
+//! sets the timer interrupt frequency. Channel 2 is connected to the PC speaker, but its gate and
+//! output can also be controlled and read through the port 0x61, which makes it useful for
+//! measuring time by polling without involving interrupts.
+//!
+//! The chip is configured through the I/O ports 0x40 - 0x42 (channels data ports) and 0x43 (mode
+//! command register).
+//!
+//! This code and comments are havily based on:
+//! - https://wiki.osdev.org/Programmable_Interval_Timer
+//! - http://www.brokenthorn.com/Resources/OSDevPit.html
+use x86_64::instructions::port::Port;
+
+/// Frequency (in Hz) of the PIT oscillator.
+pub const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+
+// I/O ports
+const CHANNEL_0_DATA: u16 = 0x40;
+const CHANNEL_2_DATA: u16 = 0x42;
+const MODE_COMMAND: u16 = 0x43;
+const CHANNEL_2_GATE: u16 = 0x61;
+
+// Mode command register bits:
+// * 6 - 7: Channel select.
+// * 4 - 5: Access mode. 00 is the latch count value command.
+// * 1 - 3: Operating mode.
+// * 0: BCD/Binary mode (we always use binary).
+const SELECT_CHANNEL_0: u8 = 0b00 << 6;
+const SELECT_CHANNEL_2: u8 = 0b10 << 6;
+const ACCESS_LATCH_COUNT: u8 = 0b00 << 4;
+const ACCESS_LOW_HIGH_BYTE: u8 = 0b11 << 4;
+
+// Port 0x61 bits used to control the channel 2.
+const CHANNEL_2_GATE_ENABLE: u8 = 1 << 0;
+const SPEAKER_ENABLE: u8 = 1 << 1;
+const CHANNEL_2_OUTPUT: u8 = 1 << 5;
+
+/// Operating mode of a PIT channel.
+#[derive(Clone, Copy, Debug)]
+pub enum PitMode {
+    /// Mode 0 - Interrupt on terminal count. The output goes high once when the counter reaches
+    /// zero and stays there until the channel is reprogrammed.
+    OneShot,
+
+    /// Mode 2 - Rate generator. The counter is reloaded every time it reaches zero, generating a
+    /// pulse with the programmed frequency.
+    Periodic,
+}
+
+impl PitMode {
+    fn as_command_bits(self) -> u8 {
+        match self {
+            PitMode::OneShot => 0b000 << 1,
+            PitMode::Periodic => 0b010 << 1,
+        }
+    }
+}
+
+/// The PIT 8253/8254 chip.
+pub struct Pit8254 {
+    /// Channel 0 data port.
+    channel_0: Port<u8>,
+
+    /// Channel 2 data port.
+    channel_2: Port<u8>,
+
+    /// Mode/command register port.
+    command: Port<u8>,
+
+    /// Channel 2 gate and output port.
+    channel_2_gate: Port<u8>,
+}
+
+impl Pit8254 {
+    /// Creates a new instance of the PIT.
+    pub const fn new() -> Self {
+        Self {
+            channel_0: Port::new(CHANNEL_0_DATA),
+            channel_2: Port::new(CHANNEL_2_DATA),
+            command: Port::new(MODE_COMMAND),
+            channel_2_gate: Port::new(CHANNEL_2_GATE),
+        }
+    }
+
+    /// Returns the divisor needed to get the closest possible frequency to the given one. The
+    /// divisor is clamped to the 16 bits range the PIT supports (18.2 Hz to 1.193182 MHz).
+    ///
+    /// # Arguments
+    /// * `frequency` - Desired frequency in Hz.
+    pub fn divisor_for(frequency: u32) -> u16 {
+        let divisor = PIT_BASE_FREQUENCY / frequency.max(1);
+        // A divisor of 0 is interpreted by the chip as 65536
+        divisor.clamp(1, u32::from(u16::MAX)) as u16
+    }
+
+    /// Returns the frequency (in Hz) produced by the given divisor.
+    ///
+    /// # Arguments
+    /// * `divisor` - Channel divisor.
+    pub fn frequency_for(divisor: u16) -> u32 {
+        PIT_BASE_FREQUENCY / u32::from(divisor.max(1))
+    }
+
+    /// Programs the channel 0 (timer IRQ) to fire with the closest possible frequency to the
+    /// given one. Returns the actual frequency programmed.
+    ///
+    /// # Arguments
+    /// * `frequency` - Desired frequency in Hz.
+    /// * `mode` - Channel operating mode.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the I/O ports are valid and that
+    /// changing the timer frequency does not break code that depends on it.
+    pub unsafe fn set_frequency(&mut self, frequency: u32, mode: PitMode) -> u32 {
+        let divisor = Self::divisor_for(frequency);
+        self.set_divisor(divisor, mode);
+        Self::frequency_for(divisor)
+    }
+
+    /// Programs the channel 0 with the given divisor.
+    ///
+    /// In one-shot mode, the timer interrupt will fire once after `divisor` oscillator ticks.
+    ///
+    /// # Arguments
+    /// * `divisor` - Channel divisor.
+    /// * `mode` - Channel operating mode.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the I/O ports are valid and that
+    /// changing the timer frequency does not break code that depends on it.
+    pub unsafe fn set_divisor(&mut self, divisor: u16, mode: PitMode) {
+        self.command
+            .write(SELECT_CHANNEL_0 | ACCESS_LOW_HIGH_BYTE | mode.as_command_bits());
+        self.channel_0.write(divisor as u8);
+        self.channel_0.write((divisor >> 8) as u8);
+    }
+
+    /// Reads the current value of the channel 0 counter.
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the I/O ports are valid and that
+    /// the counter is not read concurrently (the value is read in two steps).
+    pub unsafe fn read_count(&mut self) -> u16 {
+        // Latch the counter so it does not change between the two reads
+        self.command.write(SELECT_CHANNEL_0 | ACCESS_LATCH_COUNT);
+        let low = self.channel_0.read();
+        let high = self.channel_0.read();
+
+        u16::from_le_bytes([low, high])
+    }
+
+    /// Busy-waits for the given number of oscillator ticks using the channel 2 in one-shot mode.
+    ///
+    /// This does not use interrupts, so it can be used before they are enabled, for example to
+    /// calibrate other timers against the PIT. The speaker output is disconnected while waiting.
+    ///
+    /// # Arguments
+    /// * `ticks` - Number of oscillator ticks to wait (at 1.193182 MHz).
+    ///
+    /// # Safety
+    ///
+    /// This is unsafe because the programmer must be sure that the I/O ports are valid and
+    /// nothing else is using the channel 2.
+    pub unsafe fn wait_ticks(&mut self, ticks: u16) {
+        // Disable the speaker and the gate while the channel is programmed
+        let gate = self.channel_2_gate.read() & !(SPEAKER_ENABLE | CHANNEL_2_GATE_ENABLE);
+        self.channel_2_gate.write(gate);
+
+        self.command
+            .write(SELECT_CHANNEL_2 | ACCESS_LOW_HIGH_BYTE | PitMode::OneShot.as_command_bits());
+        self.channel_2.write(ticks as u8);
+        self.channel_2.write((ticks >> 8) as u8);
+
+        // Raising the gate starts the count down
+        self.channel_2_gate.write(gate | CHANNEL_2_GATE_ENABLE);
+
+        // The output goes high when the counter reaches zero
+        while self.channel_2_gate.read() & CHANNEL_2_OUTPUT == 0 {
+            core::hint::spin_loop();
+        }
+
+        self.channel_2_gate.write(gate);
+    }
+}
+
+impl Default for Pit8254 {
+    fn default() -> Self {
+        Self::new()
+    }
+}